@@ -1,178 +1,1022 @@
 use crate::config::{Config, HostConfig};
-use crate::dns::{self, DnsResult};
-use crate::ping::{self, PingResult};
+use crate::dns::DnsResult;
+use crate::interpolate;
+use crate::netns;
+use crate::network::{Pinger, Resolver, SystemPinger, SystemResolver};
+use crate::ping::PingResult;
+use crate::probe::{CheckOutcome, Factory, Registry};
+use crate::sweep::Sweep;
+use colored::*;
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::IpAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use surge_ping::Client as PingClient;
-use tokio::sync::Semaphore;
+use tokio::sync::{Semaphore, mpsc};
 use tokio::task::JoinSet;
+use tokio::time::Instant;
 
-/// Maximum number of concurrent checks
-const MAX_CONCURRENT_CHECKS: usize = 20;
+/// Number of hosts checked at once unless configured otherwise
+pub const DEFAULT_CONCURRENCY: usize = 20;
+
+/// Bounds on how hard and how long a run may probe
+#[derive(Debug, Clone)]
+pub struct RunLimits {
+    /// Hosts checked at once
+    pub concurrency: usize,
+    /// Hosts checked at once within each group that sets its own limit
+    pub group_concurrency: HashMap<String, usize>,
+    /// Most probes (pings, lookups, TCP connects, MTU probes) started per second
+    pub rate_limit: Option<u32>,
+    /// Time after which hosts not yet checked are given up on
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RunLimits {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            group_concurrency: HashMap::new(),
+            rate_limit: None,
+            deadline: None,
+        }
+    }
+}
+
+/// Spaces out probes to a fixed rate, shared by every task of a run
+struct RateLimiter {
+    period: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        Self {
+            period: Duration::from_secs(1) / per_second.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for this caller's start slot
+    async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.period;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Concurrency, rate and deadline state for one run, shared across dependency levels
+struct Pacing {
+    registry: Arc<Registry>,
+    jobs: Arc<Semaphore>,
+    groups: HashMap<String, Arc<Semaphore>>,
+    rate: Option<Arc<RateLimiter>>,
+    deadline: Option<Instant>,
+}
+
+impl Pacing {
+    fn new(registry: &Arc<Registry>, limits: &RunLimits, parallel: bool) -> Self {
+        Self {
+            registry: registry.clone(),
+            jobs: Arc::new(Semaphore::new(if parallel { limits.concurrency.max(1) } else { 1 })),
+            groups: limits
+                .group_concurrency
+                .iter()
+                .map(|(name, limit)| (name.clone(), Arc::new(Semaphore::new((*limit).max(1)))))
+                .collect(),
+            rate: limits.rate_limit.map(|rate| Arc::new(RateLimiter::new(rate))),
+            deadline: limits.deadline.map(|deadline| Instant::now() + deadline),
+        }
+    }
+
+    /// Check a host once its group and the run have a free slot, pacing its
+    /// probes to the rate and giving up when the deadline passes (even mid-check)
+    fn check(&self, host: HostConfig, clients: Arc<Clients>) -> impl Future<Output = CheckResult> + Send + 'static {
+        let registry = self.registry.clone();
+        let jobs = self.jobs.clone();
+        let group = host.group.as_ref().and_then(|g| self.groups.get(g)).cloned();
+        let rate = self.rate.clone();
+        let deadline = self.deadline;
+        async move {
+            let work = async {
+                // The group slot comes first so a busy group doesn't hold run slots others could use
+                let _group = match group {
+                    Some(group) => Some(group.acquire_owned().await.expect("semaphore is never closed")),
+                    None => None,
+                };
+                let _job = jobs.acquire_owned().await.expect("semaphore is never closed");
+                check_host_in_netns(&host, &clients, &registry, rate).await
+            };
+            match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, work)
+                    .await
+                    .unwrap_or_else(|_| CheckResult::not_checked(&host)),
+                None => work.await,
+            }
+        }
+    }
+}
+
+/// Runs host checks with long-lived clients and caches
+///
+/// Build one with [`Checker::builder`]. A checker keeps its ping socket,
+/// resolvers, resolved addresses (until their DNS TTL runs out) and per-host
+/// ping sessions for as long as it lives, so reusing it across runs, as watch
+/// mode does, avoids rebuilding them every time. Clones share all of these.
+///
+/// ```no_run
+/// use cxn::{Checker, HostConfig};
+///
+/// # async fn example() -> eyre::Result<()> {
+/// let checker = Checker::builder().concurrency(10).build()?;
+/// let gateway = HostConfig {
+///     name: "Gateway".to_string(),
+///     address: "10.0.0.1".to_string(),
+///     ping: true,
+///     tcp: vec![22],
+///     ..Default::default()
+/// };
+/// for result in checker.run(&[gateway]).await {
+///     println!("{}: {}", result.name, result.status());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Checker {
+    clients: Arc<Clients>,
+    registry: Arc<Registry>,
+    limits: RunLimits,
+    parallel: bool,
+}
+
+impl Checker {
+    /// Start building a checker with the default limits and the system's name servers
+    pub fn builder() -> CheckerBuilder {
+        CheckerBuilder::default()
+    }
+
+    /// Limits each run is held to
+    pub fn limits(&self) -> &RunLimits {
+        &self.limits
+    }
+
+    /// The same checker, sharing its clients and caches, with other run limits
+    pub fn with_limits(&self, limits: RunLimits) -> Self {
+        Self { limits, ..self.clone() }
+    }
+
+    /// Names of the check types hosts can use, in the order they run
+    pub fn check_types(&self) -> impl Iterator<Item = &str> {
+        self.registry.names()
+    }
+
+    /// Name servers the shared resolver queries (empty for the system's)
+    pub fn nameservers(&self) -> &[IpAddr] {
+        &self.clients.nameservers
+    }
+
+    /// Drop cached addresses and ping sessions of hosts not in `names`, e.g. after a config reload
+    pub fn retain_hosts(&self, names: &[String]) {
+        self.clients.retain_hosts(names);
+    }
+
+    /// Check a single host right away, ignoring the run limits and its dependencies
+    pub async fn check_host(&self, host: &HostConfig) -> CheckResult {
+        check_host_in_netns(host, &self.clients, &self.registry, None).await
+    }
+
+    /// Check the given hosts, returning results in the same order
+    ///
+    /// Hosts are checked in dependency order: parents first, then their dependents.
+    /// A host whose parent is down is not probed; its result is marked blocked
+    /// by the root cause instead. Hosts still unchecked when the deadline
+    /// passes are marked not checked.
+    pub async fn run(&self, hosts: &[HostConfig]) -> Vec<CheckResult> {
+        run_checks_reporting(self, hosts, &[], |_, _| {}).await
+    }
+
+    /// Check hosts like [`Checker::run`], taking the state of parents that aren't
+    /// among `hosts` from `previous` results (e.g. the last watch cycle's)
+    pub async fn run_after(&self, hosts: &[HostConfig], previous: &[CheckResult]) -> Vec<CheckResult> {
+        run_checks_reporting(self, hosts, previous, |_, _| {}).await
+    }
+
+    /// Check hosts like [`Checker::run`], also sending each result with its
+    /// host's index as soon as it is known
+    ///
+    /// Results arrive in completion order; the channel closes when the run ends.
+    pub async fn stream(
+        &self,
+        hosts: &[HostConfig],
+        results: mpsc::UnboundedSender<(usize, CheckResult)>,
+    ) -> Vec<CheckResult> {
+        // A receiver that has gone away just stops caring; the run still completes
+        let report = |idx: usize, result: &CheckResult| {
+            let _ = results.send((idx, result.clone()));
+        };
+        run_checks_reporting(self, hosts, &[], report).await
+    }
+}
+
+/// Builder for a [`Checker`]
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// # fn example() -> eyre::Result<()> {
+/// let checker = cxn::Checker::builder()
+///     .nameservers(vec!["10.1.1.53".parse()?])
+///     .concurrency(50)
+///     .group_concurrency("branch", 2)
+///     .rate_limit(100)
+///     .deadline(Duration::from_secs(30))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CheckerBuilder {
+    nameservers: Vec<IpAddr>,
+    pinger: Option<Arc<dyn Pinger>>,
+    resolver: Option<Arc<dyn Resolver>>,
+    registry: Registry,
+    limits: RunLimits,
+    sequential: bool,
+}
+
+impl CheckerBuilder {
+    /// Take the name servers and run limits from a loaded config
+    pub fn config(self, config: &Config) -> Self {
+        Self {
+            nameservers: config.nameservers.clone(),
+            limits: config.limits(),
+            ..self
+        }
+    }
+
+    /// Query these DNS servers instead of the system's
+    pub fn nameservers(mut self, nameservers: Vec<IpAddr>) -> Self {
+        self.nameservers = nameservers;
+        self
+    }
+
+    /// Add a check type to the built-in ones, or replace the one of the same name
+    ///
+    /// See [`crate::probe::Check`] for writing one.
+    pub fn check_type(mut self, name: impl Into<String>, factory: Factory) -> Self {
+        self.registry.register(name, factory);
+        self
+    }
+
+    /// Replace all run limits at once
+    pub fn limits(mut self, limits: RunLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Check at most this many hosts at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.limits.concurrency = concurrency;
+        self
+    }
+
+    /// Check at most this many hosts of `group` at once
+    pub fn group_concurrency(mut self, group: impl Into<String>, concurrency: usize) -> Self {
+        self.limits.group_concurrency.insert(group.into(), concurrency);
+        self
+    }
+
+    /// Start at most this many probes per second
+    pub fn rate_limit(mut self, per_second: u32) -> Self {
+        self.limits.rate_limit = Some(per_second);
+        self
+    }
+
+    /// Give up on hosts not checked within this time of a run's start
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.limits.deadline = Some(deadline);
+        self
+    }
+
+    /// Check one host at a time
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.sequential = sequential;
+        self
+    }
+
+    /// Ping through this instead of ICMP sockets, e.g. a `FakePinger` from `cxn::fake` in tests
+    ///
+    /// Hosts with a network namespace still use the system network.
+    pub fn pinger(mut self, pinger: Arc<dyn Pinger>) -> Self {
+        self.pinger = Some(pinger);
+        self
+    }
+
+    /// Look names up through this instead of DNS, e.g. a `FakeResolver` from `cxn::fake` in tests
+    ///
+    /// Hosts with a network namespace still use the system network.
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Create the checker's ping socket and resolver, unless given others
+    pub fn build(self) -> eyre::Result<Checker> {
+        let pinger: Arc<dyn Pinger> = match self.pinger {
+            Some(pinger) => pinger,
+            None => Arc::new(SystemPinger::new()?),
+        };
+        let resolver: Arc<dyn Resolver> = match self.resolver {
+            Some(resolver) => resolver,
+            None => Arc::new(SystemResolver::new(&self.nameservers)?),
+        };
+        Ok(Checker {
+            clients: Arc::new(Clients::with(pinger, resolver, &self.nameservers)),
+            registry: Arc::new(self.registry),
+            limits: self.limits,
+            parallel: !self.sequential,
+        })
+    }
+}
+
+/// Network access and caches behind a [`Checker`], shared by its clones
+struct Clients {
+    pinger: Arc<dyn Pinger>,
+    resolver: Arc<dyn Resolver>,
+    nameservers: Vec<IpAddr>,
+    addresses: AddressCache,
+}
+
+impl Clients {
+    /// Create clients on the system network, resolving through `nameservers` (the system's if empty)
+    fn new(nameservers: &[IpAddr]) -> eyre::Result<Self> {
+        Ok(Self::with(
+            Arc::new(SystemPinger::new()?),
+            Arc::new(SystemResolver::new(nameservers)?),
+            nameservers,
+        ))
+    }
+
+    /// Create clients that ping and resolve through the given implementations
+    fn with(pinger: Arc<dyn Pinger>, resolver: Arc<dyn Resolver>, nameservers: &[IpAddr]) -> Self {
+        Self {
+            pinger,
+            resolver,
+            nameservers: nameservers.to_vec(),
+            addresses: AddressCache::default(),
+        }
+    }
+
+    /// Drop cached addresses and ping sessions of hosts not in `names`
+    fn retain_hosts(&self, names: &[String]) {
+        self.addresses.retain(names);
+        self.pinger.retain_hosts(names);
+    }
+}
+
+/// Addresses resolved for hosts' ping, MTU and TCP checks, kept until their DNS TTL runs out
+#[derive(Default)]
+struct AddressCache {
+    /// Host name to (address as configured, resolved address, expiry)
+    entries: Mutex<HashMap<String, (String, IpAddr, Instant)>>,
+}
+
+impl AddressCache {
+    /// The host's resolved address, if its address is unchanged and the TTL hasn't run out
+    fn get(&self, host: &HostConfig) -> Option<IpAddr> {
+        let entries = self.entries.lock().unwrap();
+        let (address, ip, expires) = entries.get(&host.name)?;
+        (*address == host.address && Instant::now() < *expires).then_some(*ip)
+    }
+
+    /// Remember a host's resolved address until `expires`
+    fn insert(&self, host: &HostConfig, ip: IpAddr, expires: Instant) {
+        let entry = (host.address.clone(), ip, expires);
+        self.entries.lock().unwrap().insert(host.name.clone(), entry);
+    }
+
+    /// Forget the addresses of hosts not in `names`
+    fn retain(&self, names: &[String]) {
+        self.entries.lock().unwrap().retain(|name, _| names.contains(name));
+    }
+}
+
+/// What a [`Check`](crate::probe::Check) gets to probe a host with
+///
+/// Checks of one host share the context, so the address the DNS check
+/// resolves is the one the others probe.
+pub struct CheckContext<'a> {
+    host: &'a HostConfig,
+    clients: &'a Clients,
+    rate: Option<&'a RateLimiter>,
+    /// Address to probe, once known (`Some(None)` if resolving it failed)
+    address: Mutex<Option<Option<IpAddr>>>,
+}
+
+impl<'a> CheckContext<'a> {
+    fn new(host: &'a HostConfig, clients: &'a Clients, rate: Option<&'a RateLimiter>) -> Self {
+        let literal = host.address.parse::<IpAddr>().ok();
+        Self {
+            host,
+            clients,
+            rate,
+            address: Mutex::new(literal.map(Some)),
+        }
+    }
+
+    /// The host being checked
+    pub fn host(&self) -> &HostConfig {
+        self.host
+    }
+
+    /// Address to probe: the literal IP, the DNS check's answer, or else a
+    /// cached or fresh IPv4 lookup (retried like the host's checks)
+    pub async fn address(&self) -> Option<IpAddr> {
+        if let Some(known) = *self.address.lock().unwrap() {
+            return known;
+        }
+        let mut ip = self.clients.addresses.get(self.host);
+        if ip.is_none() {
+            let attempt = || async {
+                self.pace().await;
+                self.clients.resolver.resolve(self.host, false).await
+            };
+            let (result, expires) = retry(self.host.retries, attempt, |r| r.0.success).await;
+            ip = result.addresses.first().copied().filter(|_| result.success);
+            if let (Some(ip), Some(expires)) = (ip, expires) {
+                self.clients.addresses.insert(self.host, ip, expires);
+            }
+        }
+        *self.address.lock().unwrap() = Some(ip);
+        ip
+    }
+
+    /// Look the host name up (A and AAAA) through the host's resolver, making
+    /// the first answer the address to probe
+    pub async fn lookup(&self) -> DnsResult {
+        self.pace().await;
+        let (result, expires) = self.clients.resolver.resolve(self.host, true).await;
+        let ip = result.addresses.first().copied().filter(|_| result.success);
+        if self.host.is_ip_address() {
+            return result;
+        }
+        if let (Some(ip), Some(expires)) = (ip, expires) {
+            self.clients.addresses.insert(self.host, ip, expires);
+        }
+        *self.address.lock().unwrap() = Some(ip);
+        result
+    }
+
+    /// Ping the host at `ip`, continuing its ping session across runs
+    pub async fn ping(&self, ip: IpAddr) -> PingResult {
+        self.pace().await;
+        self.clients.pinger.ping(self.host, ip).await
+    }
+
+    /// Wait until the run's rate limit allows another probe
+    ///
+    /// [`CheckContext::lookup`] and [`CheckContext::ping`] already wait;
+    /// checks that probe the host themselves call this before each probe.
+    pub async fn pace(&self) {
+        if let Some(rate) = self.rate {
+            rate.wait().await;
+        }
+    }
+}
 
 /// Result of checking a single host
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CheckResult {
     /// Display name from config
     pub name: String,
     /// Original address from config
     pub address: String,
-    /// DNS resolution result (if performed)
-    pub dns: Option<DnsResult>,
-    /// Ping result (if performed)
-    pub ping: Option<PingResult>,
+    /// Group the host belongs to, for grouped output
+    pub group: Option<String>,
+    /// Entry the host was expanded from, for summarized output
+    pub sweep: Option<Sweep>,
+    /// Outcomes of the host's checks, in the order they ran
+    pub outcomes: Vec<CheckOutcome>,
+    /// Why the host could not be checked at all (e.g. namespace not found)
+    pub error: Option<String>,
+    /// Down host this one depends on, so its own checks were skipped
+    pub blocked_by: Option<String>,
+    /// The run's deadline passed before this host's checks finished
+    pub not_checked: bool,
 }
 
 impl CheckResult {
+    /// Create a result for a host whose checks could not be run
+    pub fn failed(host: &HostConfig, error: String) -> Self {
+        Self {
+            name: host.name.clone(),
+            address: host.address.clone(),
+            group: host.group.clone(),
+            sweep: host.sweep.clone(),
+            outcomes: Vec::new(),
+            error: Some(error),
+            blocked_by: None,
+            not_checked: false,
+        }
+    }
+
+    /// Create a result for a host the run's deadline cut off
+    pub fn not_checked(host: &HostConfig) -> Self {
+        Self {
+            error: None,
+            not_checked: true,
+            ..Self::failed(host, String::new())
+        }
+    }
+
+    /// Create a result for a host skipped because `parent` (the root cause) is down
+    pub fn blocked(host: &HostConfig, parent: String) -> Self {
+        Self {
+            error: None,
+            blocked_by: Some(parent),
+            ..Self::failed(host, String::new())
+        }
+    }
+
+    /// Format the skipped-dependency notice for display
+    pub fn format_blocked(&self) -> Option<String> {
+        self.blocked_by.as_ref().map(|parent| {
+            format!(
+                "  {} {}",
+                "⊘".yellow(),
+                format!("unreachable (parent {} down)", interpolate::redact(parent)).yellow()
+            )
+        })
+    }
+
+    /// Format the deadline notice for display
+    pub fn format_not_checked(&self) -> Option<String> {
+        self.not_checked
+            .then(|| format!("  {} {}", "⧗".yellow(), "not checked (deadline reached)".yellow()))
+    }
+
+    /// Format the host-level error for display
+    pub fn format_error(&self) -> Option<String> {
+        self.error
+            .as_ref()
+            .map(|e| format!("  {} {}", "✗".red(), interpolate::redact(e)))
+    }
+
+    /// Outcomes of the named check type, e.g. one per port for "tcp"
+    pub fn outcomes_of<'a>(&'a self, check: &'a str) -> impl Iterator<Item = &'a CheckOutcome> {
+        self.outcomes.iter().filter(move |o| o.check == check)
+    }
+
+    /// Check if any check was performed (or attempted and errored)
+    pub fn has_checks(&self) -> bool {
+        !self.outcomes.is_empty() || self.error.is_some() || self.blocked_by.is_some() || self.not_checked
+    }
+
     /// Check if all performed checks were successful
     pub fn is_success(&self) -> bool {
-        let dns_ok = self.dns.as_ref().is_none_or(|r| r.success);
-        let ping_ok = self.ping.as_ref().is_none_or(|r| r.success);
-        dns_ok && ping_ok
+        if self.error.is_some() || self.blocked_by.is_some() || self.not_checked {
+            return false;
+        }
+        self.outcomes.iter().all(|o| o.status.passed())
+    }
+
+    /// One-word outcome: ok, failed, unreachable, not_checked or skipped (no checks configured)
+    ///
+    /// ```
+    /// use cxn::{CheckResult, HostConfig};
+    ///
+    /// let host = HostConfig {
+    ///     name: "Wiki".to_string(),
+    ///     address: "wiki.internal".to_string(),
+    ///     ping: true,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(CheckResult::blocked(&host, "VPN".to_string()).status(), "unreachable");
+    /// assert_eq!(CheckResult::failed(&host, "netns vpn: not found".to_string()).status(), "failed");
+    /// ```
+    pub fn status(&self) -> &'static str {
+        if !self.has_checks() {
+            "skipped"
+        } else if self.blocked_by.is_some() {
+            "unreachable"
+        } else if self.not_checked {
+            "not_checked"
+        } else if self.is_success() {
+            "ok"
+        } else {
+            "failed"
+        }
+    }
+
+    /// The result as a JSON object for machine-readable output, secrets redacted
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "name": interpolate::redact(&self.name),
+            "address": interpolate::redact(&self.address),
+            "group": self.group,
+            "sweep": self.sweep.as_ref().map(|s| interpolate::redact(&s.name)),
+            "status": self.status(),
+            "checks": self.outcomes.iter().map(CheckOutcome::to_json).collect::<Vec<_>>(),
+            "error": self.error.as_deref().map(interpolate::redact),
+            "blocked_by": self.blocked_by.as_deref().map(interpolate::redact),
+        })
     }
 }
 
-/// Run all configured host checks
+/// Split results into runs of consecutive hosts from the same group
 ///
-/// If `parallel` is true, runs checks concurrently with bounded concurrency.
+/// Config order keeps each group contiguous, so this yields one slice per group.
+pub fn group_results(results: &[CheckResult]) -> impl Iterator<Item = &[CheckResult]> {
+    results.chunk_by(|a, b| a.group == b.group)
+}
+
+/// Split results into single hosts and runs of hosts expanded from one sweep entry
+pub fn sweep_results(results: &[CheckResult]) -> impl Iterator<Item = &[CheckResult]> {
+    results.chunk_by(|a, b| a.sweep.is_some() && a.sweep == b.sweep)
+}
+
+/// Addresses of the hosts in a sweep run that failed, shortened for display
+pub fn sweep_down(run: &[CheckResult]) -> String {
+    const SHOWN: usize = 8;
+    let down: Vec<&str> = run
+        .iter()
+        .filter(|r| r.has_checks() && !r.is_success() && !r.not_checked)
+        .map(|r| r.address.as_str())
+        .collect();
+    match down.len() {
+        n if n > SHOWN => format!("{} and {} more", down[..SHOWN].join(", "), n - SHOWN),
+        _ => down.join(", "),
+    }
+}
+
+/// Down hosts that blocked others, with how many hosts each made unreachable, in first-seen order
+pub fn root_causes(results: &[CheckResult]) -> Vec<(&str, usize)> {
+    let mut causes: Vec<(&str, usize)> = Vec::new();
+    for root in results.iter().filter_map(|r| r.blocked_by.as_deref()) {
+        match causes.iter_mut().find(|(name, _)| *name == root) {
+            Some((_, count)) => *count += 1,
+            None => causes.push((root, 1)),
+        }
+    }
+    causes
+}
+
+/// Count successful and checked hosts in a set of results
+pub fn summarize(results: &[CheckResult]) -> (usize, usize) {
+    let tally = Tally::of(results);
+    (tally.ok, tally.checked())
+}
+
+/// How many hosts in a set of results ended each way, by [`CheckResult::status`]
+///
+/// Every count comes from the results themselves, so they always add up to
+/// [`Tally::checked`]; hosts without checks are left out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tally {
+    pub ok: usize,
+    pub failed: usize,
+    pub unreachable: usize,
+    pub not_checked: usize,
+}
+
+impl Tally {
+    /// Count a set of results
+    pub fn of(results: &[CheckResult]) -> Self {
+        let mut tally = Self::default();
+        for result in results {
+            match result.status() {
+                "ok" => tally.ok += 1,
+                "failed" => tally.failed += 1,
+                "unreachable" => tally.unreachable += 1,
+                "not_checked" => tally.not_checked += 1,
+                _ => {}
+            }
+        }
+        tally
+    }
+
+    /// Hosts with checks, whatever their outcome
+    pub fn checked(&self) -> usize {
+        self.ok + self.failed + self.unreachable + self.not_checked
+    }
+
+    /// Check if every host with checks succeeded
+    pub fn all_ok(&self) -> bool {
+        self.ok == self.checked()
+    }
+}
+
+/// Check all configured hosts with a checker built from the config
+///
+/// If `parallel` is true, runs checks concurrently within the config's limits.
 /// Otherwise, runs checks sequentially.
-pub async fn run_all_checks(
-    config: &Config,
-    ping_client: Arc<PingClient>,
-    dns_resolver: Arc<hickory_resolver::TokioAsyncResolver>,
-    parallel: bool,
+pub async fn run_all_checks(config: &Config, parallel: bool) -> eyre::Result<Vec<CheckResult>> {
+    let checker = Checker::builder().config(config).sequential(!parallel).build()?;
+    Ok(checker.run(&config.hosts()).await)
+}
+
+/// Run checks level by level, calling `report` with each host's index and result as it completes
+///
+/// Parents not among `hosts` are judged by their results in `previous`.
+async fn run_checks_reporting(
+    checker: &Checker,
+    hosts: &[HostConfig],
+    previous: &[CheckResult],
+    report: impl Fn(usize, &CheckResult),
 ) -> Vec<CheckResult> {
-    let timeout = Duration::from_millis(config.timeout);
+    let clients = &checker.clients;
+    let parallel = checker.parallel;
+    let pacing = Pacing::new(&checker.registry, &checker.limits, parallel);
+    let levels = dependency_levels(hosts);
+    let mut results: Vec<Option<CheckResult>> = vec![None; hosts.len()];
+
+    for level in 0..=levels.iter().copied().max().unwrap_or(0) {
+        let mut batch = Vec::new();
+        for (idx, host) in hosts.iter().enumerate().filter(|(idx, _)| levels[*idx] == level) {
+            match down_parent(host, hosts, &results, previous) {
+                Some(root) => {
+                    let result = CheckResult::blocked(host, root);
+                    report(idx, &result);
+                    results[idx] = Some(result);
+                }
+                None => batch.push(idx),
+            }
+        }
+
+        let batch_hosts: Vec<HostConfig> = batch.iter().map(|&idx| hosts[idx].clone()).collect();
+        let batch_report = |i: usize, result: &CheckResult| report(batch[i], result);
+        let batch_results = if parallel {
+            run_parallel_checks(clients, &batch_hosts, &pacing, batch_report).await
+        } else {
+            run_sequential_checks(clients, &batch_hosts, &pacing, batch_report).await
+        };
+        for (i, result) in batch_results {
+            results[batch[i]] = Some(result);
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|r| r.expect("every level is checked"))
+        .collect()
+}
+
+/// Check if a host is the named one, or was expanded from a sweep entry of that name
+fn is_named(host: &HostConfig, name: &str) -> bool {
+    host.name == name || host.sweep.as_ref().is_some_and(|s| s.name == name)
+}
 
-    if parallel {
-        run_parallel_checks(config, ping_client, dns_resolver, timeout).await
-    } else {
-        run_sequential_checks(config, ping_client, dns_resolver, timeout).await
+/// Level of each host in the dependency graph: 0 without parents, else one
+/// more than its deepest parent. Parents not in `hosts` (e.g. filtered out) are ignored.
+fn dependency_levels(hosts: &[HostConfig]) -> Vec<usize> {
+    fn level(idx: usize, hosts: &[HostConfig], levels: &mut [Option<usize>], depth: usize) -> usize {
+        if let Some(level) = levels[idx] {
+            return level;
+        }
+        // Config validation rejects cycles; the depth guard keeps a bad graph from recursing forever
+        if depth > hosts.len() {
+            return 0;
+        }
+        let parents: Vec<usize> = hosts[idx]
+            .depends_on
+            .iter()
+            .flat_map(|parent| {
+                hosts
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, h)| is_named(h, parent))
+                    .map(|(i, _)| i)
+            })
+            .collect();
+        let result = parents
+            .into_iter()
+            .map(|parent| level(parent, hosts, levels, depth + 1) + 1)
+            .max()
+            .unwrap_or(0);
+        levels[idx] = Some(result);
+        result
     }
+
+    let mut levels = vec![None; hosts.len()];
+    (0..hosts.len()).map(|idx| level(idx, hosts, &mut levels, 0)).collect()
+}
+
+/// The root cause if any parent of `host` is down
+///
+/// A parent is down when none of its hosts succeeded; if the parent was itself
+/// blocked, its root cause is passed on. A parent that isn't among `hosts` is
+/// judged by its results in `previous`, if any.
+fn down_parent(
+    host: &HostConfig,
+    hosts: &[HostConfig],
+    results: &[Option<CheckResult>],
+    previous: &[CheckResult],
+) -> Option<String> {
+    host.depends_on.iter().find_map(|parent| {
+        let parent_results: Vec<&CheckResult> = if hosts.iter().any(|h| is_named(h, parent)) {
+            results
+                .iter()
+                .zip(hosts)
+                .filter(|(_, h)| is_named(h, parent))
+                .filter_map(|(r, _)| r.as_ref())
+                .collect()
+        } else {
+            previous
+                .iter()
+                .filter(|r| r.name == *parent || r.sweep.as_ref().is_some_and(|s| s.name == *parent))
+                .collect()
+        };
+        let parent_results: Vec<_> = parent_results
+            .into_iter()
+            .filter(|r| r.has_checks() && !r.not_checked)
+            .collect();
+        if parent_results.is_empty() || parent_results.iter().any(|r| r.is_success()) {
+            return None;
+        }
+        Some(parent_results[0].blocked_by.clone().unwrap_or_else(|| parent.clone()))
+    })
 }
 
-/// Run checks in parallel with bounded concurrency
+/// Run checks in parallel, bounded by the run's pacing, reporting each as it finishes
 async fn run_parallel_checks(
-    config: &Config,
-    ping_client: Arc<PingClient>,
-    dns_resolver: Arc<hickory_resolver::TokioAsyncResolver>,
-    timeout: Duration,
-) -> Vec<CheckResult> {
-    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_CHECKS));
+    clients: &Arc<Clients>,
+    hosts: &[HostConfig],
+    pacing: &Pacing,
+    report: impl Fn(usize, &CheckResult),
+) -> Vec<(usize, CheckResult)> {
     let mut join_set = JoinSet::new();
+    let mut tasks = HashMap::new();
 
-    let hosts = config.hosts();
-
+    // Every host gets a task up front; the pacing decides when each one starts
     for (idx, host) in hosts.iter().enumerate() {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let ping_client = ping_client.clone();
-        let dns_resolver = dns_resolver.clone();
-        let host = host.clone();
-
-        join_set.spawn(async move {
-            let result = check_host(&host, &ping_client, &dns_resolver, timeout).await;
-            drop(permit);
-            (idx, result)
-        });
+        let check = pacing.check(host.clone(), clients.clone());
+        tasks.insert(join_set.spawn(check).id(), idx);
     }
 
-    // Collect results and sort by original order
-    let mut results: Vec<(usize, CheckResult)> = Vec::with_capacity(hosts.len());
-    while let Some(Ok((idx, result))) = join_set.join_next().await {
+    // Collect results and sort by original order; a panicked check fails its host
+    let mut results = Vec::with_capacity(hosts.len());
+    while let Some(joined) = join_set.join_next_with_id().await {
+        let (idx, result) = match joined {
+            Ok((id, result)) => (tasks[&id], result),
+            Err(e) => {
+                let idx = tasks[&e.id()];
+                (idx, CheckResult::failed(&hosts[idx], format!("check failed: {}", e)))
+            }
+        };
+        report(idx, &result);
         results.push((idx, result));
     }
     results.sort_by_key(|(idx, _)| *idx);
-    results.into_iter().map(|(_, r)| r).collect()
+    results
 }
 
-/// Run checks sequentially
+/// Run checks sequentially, reporting each as it finishes
 async fn run_sequential_checks(
-    config: &Config,
-    ping_client: Arc<PingClient>,
-    dns_resolver: Arc<hickory_resolver::TokioAsyncResolver>,
-    timeout: Duration,
-) -> Vec<CheckResult> {
-    let hosts = config.hosts();
+    clients: &Arc<Clients>,
+    hosts: &[HostConfig],
+    pacing: &Pacing,
+    report: impl Fn(usize, &CheckResult),
+) -> Vec<(usize, CheckResult)> {
     let mut results = Vec::with_capacity(hosts.len());
 
-    for host in &hosts {
-        let result = check_host(host, &ping_client, &dns_resolver, timeout).await;
-        results.push(result);
+    for (idx, host) in hosts.iter().enumerate() {
+        let result = pacing.check(host.clone(), clients.clone()).await;
+        report(idx, &result);
+        results.push((idx, result));
     }
 
     results
 }
 
-/// Check a single host
-async fn check_host(
+/// Check a single host, switching into its network namespace if it has one
+///
+/// The checker's clients live in the current namespace, so namespaced hosts
+/// get fresh ones created inside the namespace.
+async fn check_host_in_netns(
     host: &HostConfig,
-    ping_client: &PingClient,
-    dns_resolver: &hickory_resolver::TokioAsyncResolver,
-    timeout: Duration,
+    clients: &Clients,
+    registry: &Arc<Registry>,
+    rate: Option<Arc<RateLimiter>>,
 ) -> CheckResult {
-    let mut dns_result = None;
-    let mut ping_result = None;
-    let mut resolved_ip: Option<IpAddr> = None;
+    let netns = match host.netns.as_deref() {
+        None | Some(netns::CURRENT) => return check_host(host, clients, registry, rate.as_deref()).await,
+        Some(netns) => netns,
+    };
 
-    // Check if address is already an IP
-    if let Ok(ip) = host.address.parse::<IpAddr>() {
-        resolved_ip = Some(ip);
-    }
+    let owned = host.clone();
+    let registry = registry.clone();
+    let result = netns::run_in(netns, move || async move {
+        let clients = Clients::new(&owned.nameservers)?;
+        Ok::<_, eyre::Report>(check_host(&owned, &clients, &registry, rate.as_deref()).await)
+    })
+    .await;
 
-    // DNS check (only if enabled and address is a hostname)
-    if host.should_resolve_dns() {
-        let result = dns::resolve_dns(dns_resolver, &host.name, &host.address, true).await;
-        if result.success && resolved_ip.is_none() {
-            resolved_ip = result.addresses.first().copied();
-        }
-        dns_result = Some(result);
-    } else if resolved_ip.is_none() && host.ping {
-        // Need to resolve for ping even if dns check not requested
-        let result = dns::resolve_dns(dns_resolver, &host.name, &host.address, false).await;
-        if result.success {
-            resolved_ip = result.addresses.first().copied();
-        }
+    match result {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) | Err(e) => CheckResult::failed(host, format!("netns {}: {:#}", netns, e)),
     }
+}
 
-    // Ping check
-    if host.ping {
-        if let Some(ip) = resolved_ip {
-            let result = ping::ping_host(ping_client, &host.name, ip, timeout, 1).await;
-            ping_result = Some(result);
-        } else {
-            // Could not resolve hostname for ping
-            ping_result = Some(PingResult::failure(
-                host.name.clone(),
-                "0.0.0.0".parse().unwrap(),
-                "could not resolve hostname".to_string(),
-            ));
-        }
+/// Check a single host, running its checks one after another in registry order
+async fn check_host(
+    host: &HostConfig,
+    clients: &Clients,
+    registry: &Registry,
+    rate: Option<&RateLimiter>,
+) -> CheckResult {
+    let ctx = CheckContext::new(host, clients, rate);
+    let mut outcomes = Vec::new();
+    for check in registry.checks(host) {
+        let retries = if check.retryable() { host.retries } else { 0 };
+        let attempt = || async {
+            let mut outcome = check.run(&ctx).await;
+            outcome.judge(&host.thresholds);
+            outcome
+        };
+        outcomes.push(retry(retries, attempt, |o| o.status.passed()).await);
     }
 
     CheckResult {
         name: host.name.clone(),
         address: host.address.clone(),
-        dns: dns_result,
-        ping: ping_result,
+        group: host.group.clone(),
+        sweep: host.sweep.clone(),
+        outcomes,
+        error: None,
+        blocked_by: None,
+        not_checked: false,
+    }
+}
+
+/// Re-run a check until it succeeds or `retries` extra attempts are used up
+async fn retry<T, F, Fut>(retries: u32, attempt: F, ok: impl Fn(&T) -> bool) -> T
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    let mut result = attempt().await;
+    for _ in 0..retries {
+        if ok(&result) {
+            break;
+        }
+        result = attempt().await;
     }
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fake::{Answer, Echo, FakePinger, FakeResolver};
+    use std::time::Duration;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// A host with a ping check and a 1s timeout
+    fn pinged(name: &str, address: &str) -> HostConfig {
+        HostConfig {
+            name: name.to_string(),
+            address: address.to_string(),
+            ping: true,
+            timeout: Duration::from_secs(1),
+            ..Default::default()
+        }
+    }
+
+    fn fake_clients(pinger: &Arc<FakePinger>, resolver: &Arc<FakeResolver>) -> Arc<Clients> {
+        Arc::new(Clients::with(pinger.clone(), resolver.clone(), &[]))
+    }
 
     #[test]
     fn test_check_result_success() {
         let result = CheckResult {
             name: "Test".to_string(),
             address: "8.8.8.8".to_string(),
-            dns: None,
-            ping: Some(PingResult::success(
-                "Test".to_string(),
-                "8.8.8.8".parse().unwrap(),
-                Duration::from_millis(10),
-            )),
+            group: None,
+            sweep: None,
+            outcomes: vec![
+                PingResult::success(
+                    "Test".to_string(),
+                    "8.8.8.8".parse().unwrap(),
+                    Duration::from_millis(10),
+                )
+                .into(),
+            ],
+            error: None,
+            blocked_by: None,
+            not_checked: false,
         };
         assert!(result.is_success());
     }
@@ -182,12 +1026,14 @@ mod tests {
         let result = CheckResult {
             name: "Test".to_string(),
             address: "8.8.8.8".to_string(),
-            dns: None,
-            ping: Some(PingResult::failure(
-                "Test".to_string(),
-                "8.8.8.8".parse().unwrap(),
-                "timeout".to_string(),
-            )),
+            group: None,
+            sweep: None,
+            outcomes: vec![
+                PingResult::failure("Test".to_string(), "8.8.8.8".parse().unwrap(), "timeout".to_string()).into(),
+            ],
+            error: None,
+            blocked_by: None,
+            not_checked: false,
         };
         assert!(!result.is_success());
     }
@@ -197,12 +1043,19 @@ mod tests {
         let result = CheckResult {
             name: "Test".to_string(),
             address: "bad.invalid".to_string(),
-            dns: Some(DnsResult::failure(
-                "Test".to_string(),
-                "bad.invalid".to_string(),
-                "no such host".to_string(),
-            )),
-            ping: None,
+            group: None,
+            sweep: None,
+            outcomes: vec![
+                DnsResult::failure(
+                    "Test".to_string(),
+                    "bad.invalid".to_string(),
+                    "no such host".to_string(),
+                )
+                .into(),
+            ],
+            error: None,
+            blocked_by: None,
+            not_checked: false,
         };
         assert!(!result.is_success());
     }
@@ -212,10 +1065,454 @@ mod tests {
         let result = CheckResult {
             name: "Test".to_string(),
             address: "8.8.8.8".to_string(),
-            dns: None,
-            ping: None,
+            group: None,
+            sweep: None,
+            outcomes: Vec::new(),
+            error: None,
+            blocked_by: None,
+            not_checked: false,
         };
         // No checks means vacuously successful
         assert!(result.is_success());
     }
+
+    #[test]
+    fn test_check_result_host_error() {
+        let host = HostConfig {
+            name: "Test".to_string(),
+            address: "8.8.8.8".to_string(),
+            ping: true,
+            ..Default::default()
+        };
+        let result = CheckResult::failed(&host, "netns blue: not found".to_string());
+        assert!(!result.is_success());
+        assert!(result.format_error().unwrap().contains("netns blue"));
+    }
+
+    #[test]
+    fn test_check_result_outcomes_and_json() {
+        let host = HostConfig {
+            name: "Web".to_string(),
+            address: "10.0.0.1".to_string(),
+            ..Default::default()
+        };
+        let mut result = CheckResult::failed(&host, String::new());
+        result.error = None;
+        let ping = PingResult::success(
+            "Web".to_string(),
+            "10.0.0.1".parse().unwrap(),
+            Duration::from_millis(80),
+        );
+        result.outcomes.push(ping.into());
+        result.outcomes[0].status = crate::probe::Status::Warning;
+        result
+            .outcomes
+            .push(crate::tcp::TcpResult::failure(443, "refused".to_string()).into());
+
+        // Warnings still count as up; failures don't
+        assert!(!result.is_success());
+        result.outcomes.pop();
+        assert!(result.is_success());
+        assert_eq!(result.status(), "ok");
+
+        let json = result.to_json();
+        assert_eq!(json["checks"][0]["check"], "ping");
+        assert_eq!(json["checks"][0]["status"], "warning");
+        assert_eq!(json["checks"][0]["metrics"]["rtt_ms"], 80.0);
+        assert_eq!(result.outcomes_of("ping").count(), 1);
+
+        // Secrets are kept out of error text, summaries and details
+        let secret = interpolate::expand_with("${WEB_TOKEN}", &|_| Some("json-secret-1".to_string())).unwrap();
+        result.error = Some(format!("401 for token {}", secret));
+        result.outcomes[0].summary = format!("sent {}", secret);
+        result.outcomes[0].details = json!({ "request": { "headers": [format!("Bearer {}", secret)] } });
+        let json = result.to_json().to_string();
+        assert!(!json.contains(&secret), "{}", json);
+        assert!(json.contains("401 for token ****"), "{}", json);
+    }
+
+    #[test]
+    fn test_check_result_text_is_redacted() {
+        let secret = interpolate::expand_with("${API_TOKEN}", &|_| Some("text-secret-1".to_string())).unwrap();
+        let host = HostConfig {
+            name: "Web".to_string(),
+            address: "10.0.0.1".to_string(),
+            ..Default::default()
+        };
+        let mut result = CheckResult::failed(&host, format!("401 for token {}", secret));
+        let mut outcome: CheckOutcome = crate::tcp::TcpResult::failure(443, "refused".to_string()).into();
+        outcome.summary = format!("OK {}", secret);
+        result.outcomes.push(outcome);
+
+        let error = result.format_error().unwrap();
+        assert!(error.contains("401 for token ****"), "{}", error);
+        let line = result.outcomes[0].format();
+        assert!(line.contains("OK ****") && !line.contains(&secret), "{}", line);
+
+        let blocked = CheckResult::blocked(&host, format!("gw-{}", secret));
+        let notice = blocked.format_blocked().unwrap();
+        assert!(notice.contains("gw-****") && !notice.contains(&secret), "{}", notice);
+    }
+
+    #[test]
+    fn test_group_results_and_summary() {
+        let host = |name: &str, group: Option<&str>, ok: bool| CheckResult {
+            name: name.to_string(),
+            address: "10.0.0.1".to_string(),
+            group: group.map(String::from),
+            sweep: None,
+            outcomes: vec![
+                if ok {
+                    PingResult::success(name.to_string(), "10.0.0.1".parse().unwrap(), Duration::from_millis(1))
+                } else {
+                    PingResult::failure(name.to_string(), "10.0.0.1".parse().unwrap(), "timeout".to_string())
+                }
+                .into(),
+            ],
+            error: None,
+            blocked_by: None,
+            not_checked: false,
+        };
+        let results = vec![
+            host("Gateway", None, true),
+            host("Primary", Some("db"), true),
+            host("Replica", Some("db"), false),
+        ];
+        let groups: Vec<_> = group_results(&results).collect();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1][0].group.as_deref(), Some("db"));
+        assert_eq!(summarize(groups[1]), (1, 2));
+        assert_eq!(summarize(&results), (2, 3));
+    }
+
+    #[test]
+    fn test_dependency_levels_and_blocking() {
+        let host = |name: &str, depends_on: &[&str]| HostConfig {
+            name: name.to_string(),
+            address: "10.0.0.1".to_string(),
+            ping: true,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        };
+        let hosts = vec![
+            host("Wiki", &["VPN"]),
+            host("VPN", &["Router"]),
+            host("Router", &[]),
+            host("Filtered", &["Not selected"]),
+        ];
+        assert_eq!(dependency_levels(&hosts), vec![2, 1, 0, 0]);
+
+        let down = |h: &HostConfig| {
+            let mut result = CheckResult::failed(h, String::new());
+            result.error = None;
+            let ping = PingResult::failure(h.name.clone(), "10.0.0.1".parse().unwrap(), "timeout".to_string());
+            result.outcomes.push(ping.into());
+            result
+        };
+        let mut results = vec![None, None, Some(down(&hosts[2])), None];
+        assert_eq!(
+            down_parent(&hosts[1], &hosts, &results, &[]),
+            Some("Router".to_string())
+        );
+
+        // The root cause is passed down the chain
+        results[1] = Some(CheckResult::blocked(&hosts[1], "Router".to_string()));
+        assert_eq!(
+            down_parent(&hosts[0], &hosts, &results, &[]),
+            Some("Router".to_string())
+        );
+        assert_eq!(down_parent(&hosts[3], &hosts, &results, &[]), None);
+
+        results[0] = Some(CheckResult::blocked(&hosts[0], "Router".to_string()));
+        let results: Vec<_> = results.into_iter().flatten().collect();
+        assert_eq!(root_causes(&results), vec![("Router", 2)]);
+        assert!(!results[0].is_success());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spaces_out_starts() {
+        let limiter = Arc::new(RateLimiter::new(4));
+        let start = Instant::now();
+        let mut tasks = JoinSet::new();
+        for _ in 0..5 {
+            let limiter = limiter.clone();
+            tasks.spawn(async move {
+                limiter.wait().await;
+                start.elapsed()
+            });
+        }
+        let mut starts: Vec<Duration> = tasks.join_all().await;
+        starts.sort();
+        let millis: Vec<u128> = starts.iter().map(|d| d.as_millis()).collect();
+        assert_eq!(millis, vec![0, 250, 500, 750, 1000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_paces_each_probe() {
+        let pinger = Arc::new(FakePinger::new());
+        let resolver = Arc::new(FakeResolver::new());
+        let mut hosts = Vec::new();
+        for (i, name) in ["a.internal", "b.internal"].into_iter().enumerate() {
+            let address = ip(&format!("10.0.0.{}", i + 1));
+            pinger.reply(address, Echo::Reply(ms(5)));
+            resolver.answer(name, Answer::addresses(vec![address], Duration::from_secs(60)));
+            hosts.push(HostConfig {
+                dns: true,
+                ..pinged(name, name)
+            });
+        }
+        let checker = Checker::builder()
+            .pinger(pinger.clone())
+            .resolver(resolver.clone())
+            .rate_limit(2)
+            .build()
+            .unwrap();
+
+        // Two lookups and two pings at 2/s: the last ping goes out after 1.5s
+        let start = Instant::now();
+        let results = checker.run(&hosts).await;
+        assert!(results.iter().all(|r| r.is_success()));
+        assert_eq!(start.elapsed(), ms(1505));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parallel_checks_report_as_they_finish_and_return_in_order() {
+        let pinger = Arc::new(FakePinger::new());
+        let mut hosts = Vec::new();
+        for (i, (name, rtt)) in [("Slow", 300), ("Fast", 100), ("Medium", 200)].into_iter().enumerate() {
+            let address = format!("10.0.0.{}", i + 1);
+            pinger.reply(ip(&address), Echo::Reply(ms(rtt)));
+            hosts.push(pinged(name, &address));
+        }
+        let clients = fake_clients(&pinger, &Arc::new(FakeResolver::new()));
+        let registry = Arc::new(Registry::default());
+
+        let run = |limits: RunLimits| {
+            let (clients, hosts, registry) = (&clients, &hosts, &registry);
+            async move {
+                let start = Instant::now();
+                let finished = Mutex::new(Vec::new());
+                let pacing = Pacing::new(registry, &limits, true);
+                let report = |idx: usize, _: &CheckResult| finished.lock().unwrap().push((idx, start.elapsed()));
+                let results = run_parallel_checks(clients, hosts, &pacing, report).await;
+                let names: Vec<String> = results.into_iter().map(|(_, r)| r.name).collect();
+                (names, finished.into_inner().unwrap())
+            }
+        };
+
+        let (names, finished) = run(RunLimits::default()).await;
+        assert_eq!(names, ["Slow", "Fast", "Medium"]);
+        assert_eq!(finished, [(1, ms(100)), (2, ms(200)), (0, ms(300))]);
+
+        // One at a time, hosts start in order and their latencies add up
+        let one_at_a_time = RunLimits {
+            concurrency: 1,
+            ..Default::default()
+        };
+        let (names, finished) = run(one_at_a_time).await;
+        assert_eq!(names, ["Slow", "Fast", "Medium"]);
+        assert_eq!(finished, [(0, ms(300)), (1, ms(400)), (2, ms(600))]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_resolves_address_and_reuses_it_until_ttl() {
+        let pinger = Arc::new(FakePinger::new());
+        pinger.reply(ip("10.0.0.7"), Echo::Reply(ms(5)));
+        let resolver = Arc::new(FakeResolver::new());
+        let addresses = vec![ip("fd00::7"), ip("10.0.0.7")];
+        resolver.answer("wiki.internal", Answer::addresses(addresses, Duration::from_secs(60)));
+        let clients = fake_clients(&pinger, &resolver);
+        let registry = Registry::default();
+        let wiki = pinged("Wiki", "wiki.internal");
+
+        // Without a dns check the host is still resolved (IPv4 only) for the ping
+        let result = check_host(&wiki, &clients, &registry, None).await;
+        assert!(result.is_success());
+        assert_eq!(result.outcomes_of("dns").count(), 0);
+        assert_eq!(pinger.pinged("Wiki"), [ip("10.0.0.7")]);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        check_host(&wiki, &clients, &registry, None).await;
+        assert_eq!(resolver.lookups().len(), 1);
+
+        tokio::time::advance(Duration::from_secs(31)).await;
+        check_host(&wiki, &clients, &registry, None).await;
+        assert_eq!(resolver.lookups().len(), 2);
+        assert_eq!(pinger.pinged("Wiki").len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unresolvable_host_fails_without_pinging() {
+        let pinger = Arc::new(FakePinger::new());
+        let resolver = Arc::new(FakeResolver::new());
+        let clients = fake_clients(&pinger, &resolver);
+        let gone = HostConfig {
+            dns: true,
+            tcp: vec![443],
+            ..pinged("Gone", "gone.internal")
+        };
+
+        // The failed dns check's answer stands; the other checks don't look the name up again
+        let result = check_host(&gone, &clients, &Registry::default(), None).await;
+        let summaries: Vec<(&str, &str)> = result
+            .outcomes
+            .iter()
+            .map(|o| (o.check.as_str(), o.summary.as_str()))
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                ("dns", "no such host"),
+                ("ping", "could not resolve hostname"),
+                ("tcp", "443 could not resolve hostname"),
+            ]
+        );
+        assert_eq!(resolver.lookups(), ["gone.internal"]);
+        assert!(pinger.pings().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_ride_out_timeouts_and_loss() {
+        let pinger = Arc::new(FakePinger::new());
+        pinger.script(ip("10.0.0.9"), [Echo::Lost, Echo::Reply(ms(20))]);
+        let resolver = Arc::new(FakeResolver::new());
+        let answer = Answer::addresses(vec![ip("10.0.0.9")], Duration::from_secs(60));
+        resolver.script("vpn.internal", [Answer::Timeout, answer]);
+        let clients = fake_clients(&pinger, &resolver);
+        let vpn = HostConfig {
+            retries: 1,
+            ..pinged("VPN", "vpn.internal")
+        };
+
+        let start = Instant::now();
+        let result = check_host(&vpn, &clients, &Registry::default(), None).await;
+        assert!(result.is_success());
+        assert_eq!(resolver.lookups().len(), 2);
+        assert_eq!(pinger.pinged("VPN").len(), 2);
+        // The timed-out lookup and the lost ping each took the host's timeout
+        assert_eq!(start.elapsed(), Duration::from_secs(2) + ms(20));
+
+        // Out of retries, the loss shows
+        pinger.reply(ip("10.0.0.9"), Echo::Lost);
+        let result = check_host(&vpn, &clients, &Registry::default(), None).await;
+        assert_eq!(result.outcomes[0].summary, "timeout after 1000ms");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_skips_dependents_of_down_host() {
+        let pinger = Arc::new(FakePinger::new());
+        pinger.reply(ip("10.0.0.2"), Echo::Reply(ms(5)));
+        let checker = Checker::builder()
+            .pinger(pinger.clone())
+            .resolver(Arc::new(FakeResolver::new()))
+            .build()
+            .unwrap();
+        let hosts = vec![
+            HostConfig {
+                depends_on: vec!["Router".to_string()],
+                ..pinged("Wiki", "10.0.0.2")
+            },
+            pinged("Router", "10.0.0.1"),
+        ];
+
+        let results = checker.run(&hosts).await;
+        assert_eq!(results[0].blocked_by.as_deref(), Some("Router"));
+        assert_eq!(results[1].status(), "failed");
+        assert!(pinger.pinged("Wiki").is_empty());
+
+        // A parent not checked this time is judged by its previous result
+        let results = checker.run_after(&hosts[..1], &results[1..]).await;
+        assert_eq!(results[0].blocked_by.as_deref(), Some("Router"));
+        assert!(pinger.pinged("Wiki").is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_fails_only_the_host_whose_check_panicked() {
+        struct Panics;
+        impl crate::probe::Check for Panics {
+            fn name(&self) -> &str {
+                "panics"
+            }
+            fn run<'a>(&'a self, _: &'a CheckContext<'a>) -> crate::probe::BoxFuture<'a, CheckOutcome> {
+                Box::pin(async { panic!("probe bug") })
+            }
+        }
+        let factory: Factory = |host| match host.name.as_str() {
+            "Broken" => vec![Box::new(Panics)],
+            _ => vec![],
+        };
+
+        let pinger = Arc::new(FakePinger::new());
+        for address in ["10.0.0.1", "10.0.0.3"] {
+            pinger.reply(ip(address), Echo::Reply(ms(5)));
+        }
+        let checker = Checker::builder()
+            .pinger(pinger.clone())
+            .resolver(Arc::new(FakeResolver::new()))
+            .check_type("panics", factory)
+            .build()
+            .unwrap();
+        let hosts = vec![
+            pinged("Before", "10.0.0.1"),
+            pinged("Broken", "10.0.0.2"),
+            pinged("After", "10.0.0.3"),
+        ];
+
+        let results = checker.run(&hosts).await;
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Before", "Broken", "After"]);
+        assert!(results[0].is_success() && results[2].is_success());
+        assert!(
+            results[1].error.as_deref().unwrap().contains("panicked"),
+            "{:?}",
+            results[1].error
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_address_cache_honours_ttl() {
+        let mut host = HostConfig {
+            name: "Wiki".to_string(),
+            address: "wiki.internal".to_string(),
+            ..Default::default()
+        };
+        let ip: IpAddr = "10.0.0.7".parse().unwrap();
+        let cache = AddressCache::default();
+        cache.insert(&host, ip, Instant::now() + Duration::from_secs(60));
+        assert_eq!(cache.get(&host), Some(ip));
+
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert_eq!(cache.get(&host), Some(ip));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.get(&host), None);
+
+        // A changed address in a reloaded config doesn't reuse the old answer
+        cache.insert(&host, ip, Instant::now() + Duration::from_secs(60));
+        host.address = "wiki2.internal".to_string();
+        assert_eq!(cache.get(&host), None);
+
+        cache.retain(&["Other".to_string()]);
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_on_success() {
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = retry(
+            3,
+            || async { attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1 },
+            |&n| n >= 2,
+        )
+        .await;
+        assert_eq!(result, 2);
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = retry(
+            2,
+            || async { attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1 },
+            |_| false,
+        )
+        .await;
+        assert_eq!(result, 3);
+    }
 }
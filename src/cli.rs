@@ -1,4 +1,8 @@
 use clap::{Parser, Subcommand};
+use cxn::format::ConfigFormat;
+use cxn::import::ImportSource;
+use cxn::trace::TraceProtocol;
+use std::net::IpAddr;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -17,6 +21,27 @@ pub struct Cli {
     #[arg(short, long, global = true, help = "Enable verbose output")]
     pub verbose: bool,
 
+    /// Network interface to bind probe and DNS sockets to
+    #[arg(short = 'I', long, global = true, help = "Network interface to bind probes to")]
+    pub interface: Option<String>,
+
+    /// Local address to send probes and DNS queries from
+    #[arg(short = 'S', long, global = true, help = "Local source address for probes")]
+    pub source: Option<IpAddr>,
+
+    /// Network namespace(s) to run in; repeat to compare namespaces side by side (check only)
+    #[arg(
+        long = "netns",
+        value_name = "NAME|PATH",
+        global = true,
+        help = "Run inside a network namespace"
+    )]
+    pub netns: Vec<String>,
+
+    /// Config profile to use (default: CXN_PROFILE, else the first whose `match` fits this network)
+    #[arg(long, value_name = "NAME", global = true, help = "Use a named config profile")]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -36,6 +61,26 @@ pub enum Commands {
         /// Timeout in milliseconds
         #[arg(short, long, default_value = "1000")]
         timeout: u64,
+
+        /// Payload size in bytes (excluding the ICMP header)
+        #[arg(short = 's', long, default_value = "56")]
+        size: usize,
+
+        /// Payload fill pattern as hex bytes (e.g. "ff00")
+        #[arg(short = 'p', long)]
+        pattern: Option<String>,
+
+        /// IP TTL / IPv6 hop limit
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=255))]
+        ttl: Option<u32>,
+
+        /// Set the don't-fragment bit
+        #[arg(short = 'M', long = "df")]
+        dont_fragment: bool,
+
+        /// DSCP code point (0-63) for QoS marking
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=63))]
+        dscp: Option<u8>,
     },
 
     /// Resolve DNS for a hostname
@@ -49,6 +94,68 @@ pub enum Commands {
         ipv6: bool,
     },
 
+    /// Discover the path MTU to a host using DF-flagged pings
+    Mtu {
+        /// Host to probe (IP address or hostname)
+        #[arg(required = true)]
+        host: String,
+
+        /// Largest MTU to try in bytes
+        #[arg(short, long, default_value = "1500")]
+        max: usize,
+
+        /// Attempts per probe size before treating it as failed
+        #[arg(short = 'n', long, default_value = "2")]
+        attempts: u32,
+
+        /// Timeout in milliseconds
+        #[arg(short, long, default_value = "1000")]
+        timeout: u64,
+
+        /// Only probe IPv4
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+
+        /// Only probe IPv6
+        #[arg(short = '6', long)]
+        ipv6: bool,
+    },
+
+    /// Trace the network path to a host with per-hop loss and RTT statistics
+    Trace {
+        /// Host to trace (IP address or hostname)
+        #[arg(required = true)]
+        host: String,
+
+        /// Probe rounds to send (ignored in watch mode)
+        #[arg(short = 'n', long, default_value = "3")]
+        count: u32,
+
+        /// Maximum number of hops
+        #[arg(short, long, default_value = "30", value_parser = clap::value_parser!(u8).range(1..))]
+        max_hops: u8,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short, long, default_value = "1000")]
+        timeout: u64,
+
+        /// Probe type
+        #[arg(short = 'P', long, value_enum, default_value = "icmp")]
+        protocol: TraceProtocol,
+
+        /// Destination port for UDP (base port) and TCP probes
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Skip reverse DNS lookups of hop addresses
+        #[arg(long)]
+        no_dns: bool,
+
+        /// Watch mode: keep probing and redraw the table at interval (seconds)
+        #[arg(short, long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "1")]
+        watch: Option<u64>,
+    },
+
     /// Check connectivity for all configured hosts (default)
     Check {
         /// Run checks sequentially instead of in parallel
@@ -59,5 +166,160 @@ pub enum Commands {
         /// Uses config/env default if no value given
         #[arg(short, long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "0")]
         watch: Option<u64>,
+
+        /// Only check hosts with this tag or in this group (repeatable, any matches)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Skip hosts with this tag or in this group (repeatable)
+        #[arg(long = "exclude-tag", value_name = "TAG")]
+        exclude_tags: Vec<String>,
+
+        /// Only check hosts whose name matches this glob, e.g. 'GitHub*' (repeatable)
+        #[arg(long = "host", value_name = "PATTERN")]
+        hosts: Vec<String>,
+
+        /// Check hosts imported from this source instead of the configured ones
+        #[arg(long, value_enum, value_name = "SOURCE", conflicts_with = "targets")]
+        from: Option<ImportSource>,
+
+        /// Check these addresses instead of the configured hosts ("-" reads one per line from stdin)
+        #[arg(value_name = "ADDRESS")]
+        targets: Vec<String>,
+
+        /// Ping the given addresses (the default when no check is chosen)
+        #[arg(long, requires = "targets")]
+        ping: bool,
+
+        /// Resolve the given addresses
+        #[arg(long, requires = "targets")]
+        dns: bool,
+
+        /// Connect to this TCP port on the given addresses (repeatable)
+        #[arg(long, value_name = "PORT", requires = "targets")]
+        tcp: Vec<u16>,
+
+        /// Check at most this many hosts at once (default: config `concurrency`, else 20)
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        jobs: Option<u32>,
+
+        /// Send at most this many probes (pings, lookups, TCP connects) per second
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        rate: Option<u32>,
+
+        /// Give up after this many seconds, reporting remaining hosts as not checked
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        deadline: Option<u64>,
+
+        /// Show each host as soon as its check finishes (redrawn in place on a terminal)
+        #[arg(long, conflicts_with = "watch")]
+        stream: bool,
+
+        /// Print one JSON object per host as its check finishes, then a summary object
+        #[arg(long, conflicts_with_all = ["watch", "stream"])]
+        ndjson: bool,
+    },
+
+    /// Import hosts from an SSH config, hosts file or known_hosts file
+    Import {
+        /// Where to read hosts from
+        #[arg(value_enum, value_name = "SOURCE")]
+        from: ImportSource,
+
+        /// File to read instead of the source's default location
+        #[arg(short, long, value_name = "PATH")]
+        file: Option<PathBuf>,
+
+        /// Use ping checks instead of TCP to the SSH port
+        #[arg(long)]
+        ping: bool,
+
+        /// Put the hosts in this group
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// Add the hosts to the config file instead of printing them
+        #[arg(short, long)]
+        write: bool,
+    },
+
+    /// Inspect the loaded configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Check the configuration for errors and likely mistakes
+    Validate {
+        /// Exit non-zero on warnings as well as errors
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Print the merged configuration and the files it came from
+    Show {
+        /// Show the effective settings of every host after overrides are applied
+        #[arg(long)]
+        resolved: bool,
+    },
+
+    /// Print a JSON Schema for cxn.yml, for editor completion and linting
+    Schema,
+
+    /// Rewrite the config file in another format (includes are left as they are)
+    Convert {
+        /// Format to write
+        #[arg(long, value_enum)]
+        to: ConfigFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Write a commented starter config
+    Init {
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Add a host to the config file
+    Add {
+        /// Display name for the host
+        name: String,
+
+        /// IP address or hostname
+        #[arg(short, long)]
+        address: String,
+
+        /// Enable the ping check
+        #[arg(long)]
+        ping: bool,
+
+        /// Enable the DNS check
+        #[arg(long)]
+        dns: bool,
+
+        /// TCP port that must accept connections (repeatable)
+        #[arg(long, value_name = "PORT")]
+        tcp: Vec<u16>,
+
+        /// Add the host to this group (created if missing)
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// Tag for --tag filtering (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+
+    /// Remove a host from the file that defines it
+    Remove {
+        /// Name of the host to remove
+        name: String,
     },
 }
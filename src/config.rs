@@ -1,53 +1,569 @@
+use crate::check::{self, RunLimits};
+use crate::exec::ExecCommand;
+use crate::filter::HostFilter;
+use crate::format::{CONFIG_EXTENSIONS, ConfigFormat};
+use crate::interpolate;
+use crate::mtu;
+use crate::ping::{self, PingOptions};
+use crate::probe::{Registry, Threshold};
+use crate::profile::{NetworkState, Profile};
+use crate::source::SourceBinding;
+use crate::sweep::{self, Sweep};
 use eyre::{Context, Result};
 use indexmap::IndexMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     /// Timeout for ping/dns operations in milliseconds
     pub timeout: u64,
+    /// Number of ping packets sent per check
+    pub count: u32,
     /// Number of retry attempts
     pub retries: u32,
     /// Default watch interval in seconds for continuous monitoring
     pub interval: u64,
+    /// Default interface to bind probe sockets to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Default local address to send probes from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+    /// DNS servers to query instead of the resolver default
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nameservers: Vec<IpAddr>,
+    /// Number of hosts checked at once
+    pub concurrency: usize,
+    /// Most probes started per second (unlimited if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u32>,
     /// Map of host name to host configuration
     hosts: IndexMap<String, HostEntry>,
+    /// Named groups of hosts, checked after ungrouped hosts
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    groups: IndexMap<String, GroupEntry>,
+    /// Named network contexts, selected with --profile or by their `match` conditions
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    profiles: IndexMap<String, Profile>,
+    /// Profile in effect, if any
+    #[serde(skip)]
+    profile: Option<String>,
+    /// Source binding from the command line, overriding config values
+    #[serde(skip)]
+    source_override: SourceBinding,
+    /// Network namespaces from the command line; every host is checked in each
+    #[serde(skip)]
+    netns_override: Vec<String>,
+    /// Tag / name selection from the command line
+    #[serde(skip)]
+    filter: HostFilter,
+    /// Watch interval from the command line, overriding every host
+    #[serde(skip)]
+    interval_override: Option<u64>,
+    /// Concurrency from the command line, overriding config values
+    #[serde(skip)]
+    jobs_override: Option<usize>,
+    /// Rate limit from the command line, overriding config values
+    #[serde(skip)]
+    rate_override: Option<u32>,
+    /// Time limit for a run from the command line
+    #[serde(skip)]
+    deadline: Option<Duration>,
+    /// Files merged into this config, in load order
+    #[serde(skip)]
+    files: Vec<PathBuf>,
+    /// Fragment directories merged into this config, including ones that don't exist yet
+    #[serde(skip)]
+    fragment_dirs: Vec<PathBuf>,
+    /// File each host was defined in
+    #[serde(skip)]
+    origins: HashMap<String, PathBuf>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             timeout: 1000,
-            retries: 3,
+            count: 1,
+            retries: 0,
             interval: 5,
+            interface: None,
+            source: None,
+            nameservers: Vec::new(),
+            concurrency: check::DEFAULT_CONCURRENCY,
+            rate_limit: None,
             hosts: IndexMap::new(),
+            groups: IndexMap::new(),
+            profiles: IndexMap::new(),
+            profile: None,
+            source_override: SourceBinding::default(),
+            netns_override: Vec::new(),
+            filter: HostFilter::default(),
+            interval_override: None,
+            jobs_override: None,
+            rate_override: None,
+            deadline: None,
+            files: Vec::new(),
+            fragment_dirs: Vec::new(),
+            origins: HashMap::new(),
         }
     }
 }
 
 impl Config {
-    /// Get hosts as a Vec with names included
-    pub fn hosts(&self) -> Vec<HostConfig> {
-        self.hosts
+    /// Bind all probes to this source, taking precedence over config values
+    pub fn set_source_override(&mut self, source: SourceBinding) {
+        self.source_override = source;
+    }
+
+    /// Check every host in each of these namespaces, taking precedence over config values
+    ///
+    /// With more than one namespace, hosts are repeated per namespace and
+    /// named `"Name [ns]"` so results line up side by side.
+    pub fn set_netns_override(&mut self, netns: Vec<String>) {
+        self.netns_override = netns;
+    }
+
+    /// Only check hosts selected by this filter
+    pub fn set_filter(&mut self, filter: HostFilter) {
+        self.filter = filter;
+    }
+
+    /// Re-check every host at this interval in watch mode, ignoring config values
+    pub fn set_interval_override(&mut self, interval: Option<u64>) {
+        self.interval_override = interval;
+    }
+
+    /// Bound runs from the command line: hosts checked at once, checks started
+    /// per second, and total time before remaining hosts are given up on
+    pub fn set_run_overrides(&mut self, jobs: Option<usize>, rate_limit: Option<u32>, deadline: Option<Duration>) {
+        self.jobs_override = jobs;
+        self.rate_override = rate_limit;
+        self.deadline = deadline;
+    }
+
+    /// How hard and how long a run may probe, after command-line overrides
+    pub fn limits(&self) -> RunLimits {
+        RunLimits {
+            concurrency: self.jobs_override.unwrap_or(self.concurrency),
+            group_concurrency: self
+                .groups
+                .iter()
+                .filter_map(|(name, group)| group.concurrency.map(|limit| (name.clone(), limit)))
+                .collect(),
+            rate_limit: self.rate_override.or(self.rate_limit),
+            deadline: self.deadline,
+        }
+    }
+
+    /// Check these hosts instead of the configured hosts and groups; settings still apply
+    pub fn replace_hosts(&mut self, hosts: IndexMap<String, HostEntry>) -> Result<()> {
+        for (name, entry) in &hosts {
+            entry.validate().context(format!("Invalid host '{}'", name))?;
+        }
+        self.hosts = hosts;
+        self.groups.clear();
+        self.origins.clear();
+        Ok(())
+    }
+
+    /// Switch to a named profile: its settings replace the top-level ones and
+    /// only its groups (plus ungrouped hosts) are checked
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            let hint = match did_you_mean(name, &names) {
+                Some(close) => format!(" (did you mean '{}'?)", close),
+                None if names.is_empty() => " (no profiles are configured)".to_string(),
+                None => format!(" (available: {})", names.join(", ")),
+            };
+            eyre::bail!("Unknown profile '{}'{}", name, hint);
+        };
+        self.timeout = profile.timeout.unwrap_or(self.timeout);
+        self.count = profile.count.unwrap_or(self.count);
+        self.retries = profile.retries.unwrap_or(self.retries);
+        self.interval = profile.interval.unwrap_or(self.interval);
+        self.interface = profile.interface.or(self.interface.take());
+        self.source = profile.source.or(self.source);
+        if !profile.nameservers.is_empty() {
+            self.nameservers = profile.nameservers;
+        }
+        self.profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// First profile, in config order, whose `match` conditions hold on this network
+    pub fn detect_profile(&self, network: &NetworkState) -> Option<&str> {
+        self.profiles
+            .iter()
+            .find(|(_, profile)| profile.conditions.as_ref().is_some_and(|m| m.matches(network)))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Name of the profile in effect, if any
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Files merged into this config, in load order (empty when using defaults)
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Drop-in directories whose files are merged into this config, whether or not they exist yet
+    pub fn fragment_dirs(&self) -> &[PathBuf] {
+        &self.fragment_dirs
+    }
+
+    /// File a host was defined in
+    pub fn origin(&self, name: &str) -> Option<&Path> {
+        self.origins.get(name).map(PathBuf::as_path)
+    }
+
+    /// Host entries keyed by name with their group, in config order
+    pub fn host_entries(&self) -> impl Iterator<Item = (&String, &HostEntry, Option<&String>)> {
+        self.entries()
+            .map(|(name, entry, group)| (name, entry, group.map(|(group_name, _)| group_name)))
+    }
+
+    /// Check if a command-line filter narrows the host list
+    pub fn is_filtered(&self) -> bool {
+        !self.filter.is_empty()
+    }
+
+    /// All host entries with the group they belong to, ungrouped hosts first
+    fn entries(&self) -> impl Iterator<Item = (&String, &HostEntry, Option<(&String, &GroupEntry)>)> {
+        let ungrouped = self.hosts.iter().map(|(name, entry)| (name, entry, None));
+        let grouped = self
+            .groups
             .iter()
-            .map(|(name, entry)| HostConfig {
+            .flat_map(|(group, g)| g.hosts.iter().map(move |(name, entry)| (name, entry, Some((group, g)))));
+        ungrouped.chain(grouped)
+    }
+
+    /// Get hosts as a Vec with names included, after applying the filter
+    pub fn hosts(&self) -> Vec<HostConfig> {
+        let global = SourceBinding {
+            interface: self.interface.clone(),
+            address: self.source,
+        };
+        let mut hosts = Vec::with_capacity(self.hosts.len());
+        for (name, entry, group) in self.entries() {
+            // A host's tags include its group's tags and the group name itself
+            let mut tags = entry.tags.clone();
+            if let Some((group_name, g)) = group {
+                tags.extend(g.tags.iter().cloned());
+                tags.push(group_name.clone());
+            }
+            if !self.filter.matches(name, &tags) {
+                continue;
+            }
+            // A profile narrows the groups checked; ungrouped hosts always are
+            if let Some((group_name, _)) = group
+                && let Some(profile) = self.profile.as_ref().and_then(|p| self.profiles.get(p))
+                && !profile.groups.is_empty()
+                && !profile.groups.contains(group_name)
+            {
+                continue;
+            }
+
+            // Settings resolve host > group > global, with CLI overrides on top
+            let g = group.map(|(_, g)| g);
+            let group_source = g.map(GroupEntry::source_binding).unwrap_or_default();
+            let timeout = entry.timeout.or(g.and_then(|g| g.timeout)).unwrap_or(self.timeout);
+
+            let host = HostConfig {
                 name: name.clone(),
                 address: entry.address.clone(),
                 ping: entry.ping,
                 dns: entry.dns,
-            })
-            .collect()
+                ping_options: entry.ping_options(),
+                mtu_min: entry.mtu_min,
+                tcp: entry.tcp.clone(),
+                exec: entry.exec.clone(),
+                source: global
+                    .overridden_by(&group_source)
+                    .overridden_by(&entry.source_binding())
+                    .overridden_by(&self.source_override),
+                netns: entry.netns.clone(),
+                group: group.map(|(group_name, _)| group_name.clone()),
+                timeout: Duration::from_millis(timeout),
+                count: entry.count.or(g.and_then(|g| g.count)).unwrap_or(self.count),
+                retries: entry.retries.or(g.and_then(|g| g.retries)).unwrap_or(self.retries),
+                interval: self
+                    .interval_override
+                    .or(entry.interval)
+                    .or(g.and_then(|g| g.interval))
+                    .unwrap_or(self.interval),
+                nameservers: self.nameservers.clone(),
+                depends_on: self.dependencies(name, entry, g),
+                thresholds: g
+                    .iter()
+                    .flat_map(|g| &g.thresholds)
+                    .chain(&entry.thresholds)
+                    .map(|(key, threshold)| (key.clone(), threshold.clone()))
+                    .collect(),
+                sweep: None,
+            };
+            // Validation already expanded the address, so errors can't happen here
+            let addresses = sweep::expand(&entry.address).ok().flatten();
+            match self.netns_override.as_slice() {
+                [] => hosts.extend(expand_sweep(host, addresses.as_deref())),
+                [netns] => hosts.extend(expand_sweep(
+                    HostConfig {
+                        netns: Some(netns.clone()),
+                        ..host
+                    },
+                    addresses.as_deref(),
+                )),
+                many => {
+                    for netns in many {
+                        // Parents are checked in the same namespace as their dependents
+                        let host = HostConfig {
+                            name: format!("{} [{}]", name, netns),
+                            netns: Some(netns.clone()),
+                            depends_on: host.depends_on.iter().map(|d| format!("{} [{}]", d, netns)).collect(),
+                            ..host.clone()
+                        };
+                        hosts.extend(expand_sweep(host, addresses.as_deref()));
+                    }
+                }
+            }
+        }
+        hosts
+    }
+
+    /// A host's own dependencies followed by its group's, excluding itself
+    fn dependencies(&self, name: &str, entry: &HostEntry, group: Option<&GroupEntry>) -> Vec<String> {
+        let mut depends_on = entry.depends_on.clone();
+        for parent in group.iter().flat_map(|g| &g.depends_on) {
+            if parent != name && !depends_on.contains(parent) {
+                depends_on.push(parent.clone());
+            }
+        }
+        depends_on
+    }
+
+    /// Reject `depends_on` names that don't exist and dependency cycles
+    fn validate_dependencies(&self) -> Result<()> {
+        let names: Vec<&str> = self.entries().map(|(name, _, _)| name.as_str()).collect();
+        let graph: HashMap<&str, Vec<String>> = self
+            .entries()
+            .map(|(name, entry, group)| (name.as_str(), self.dependencies(name, entry, group.map(|(_, g)| g))))
+            .collect();
+
+        for (name, parents) in &graph {
+            for parent in parents {
+                if !graph.contains_key(parent.as_str()) {
+                    let hint = did_you_mean(parent, &names).map(|n| format!(" (did you mean '{}'?)", n));
+                    eyre::bail!(
+                        "Host '{}' depends on unknown host '{}'{}",
+                        name,
+                        parent,
+                        hint.unwrap_or_default()
+                    );
+                }
+            }
+        }
+
+        // Depth-first search; reaching a host already on the path closes a cycle
+        fn visit<'a>(
+            name: &'a str,
+            graph: &'a HashMap<&str, Vec<String>>,
+            path: &mut Vec<&'a str>,
+            done: &mut HashSet<&'a str>,
+        ) -> Result<()> {
+            if let Some(start) = path.iter().position(|n| *n == name) {
+                let cycle: Vec<&str> = path[start..].iter().copied().chain([name]).collect();
+                eyre::bail!("Dependency cycle: {}", cycle.join(" -> "));
+            }
+            if !done.insert(name) {
+                return Ok(());
+            }
+            path.push(name);
+            for parent in &graph[name] {
+                visit(parent, graph, path, done)?;
+            }
+            path.pop();
+            Ok(())
+        }
+        let mut done = HashSet::new();
+        for name in &names {
+            visit(name, &graph, &mut Vec::new(), &mut done)?;
+        }
+        Ok(())
+    }
+
+    /// Render the merged config as YAML, noting the file each host came from
+    pub fn to_annotated_yaml(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Settings<'a> {
+            timeout: u64,
+            count: u32,
+            retries: u32,
+            interval: u64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            interface: &'a Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            source: Option<IpAddr>,
+            #[serde(skip_serializing_if = "<[IpAddr]>::is_empty")]
+            nameservers: &'a [IpAddr],
+            concurrency: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            rate_limit: Option<u32>,
+        }
+
+        let mut out = String::new();
+        if !self.files.is_empty() {
+            out.push_str("# Merged from:\n");
+            for file in &self.files {
+                out.push_str(&format!("#   {}\n", file.display()));
+            }
+        }
+        out.push_str(&serde_yaml::to_string(&Settings {
+            timeout: self.timeout,
+            count: self.count,
+            retries: self.retries,
+            interval: self.interval,
+            interface: &self.interface,
+            source: self.source,
+            nameservers: &self.nameservers,
+            concurrency: self.concurrency,
+            rate_limit: self.rate_limit,
+        })?);
+
+        if !self.hosts.is_empty() {
+            out.push_str("hosts:\n");
+            for (name, entry) in &self.hosts {
+                self.write_entry(&mut out, 2, name, entry)?;
+            }
+        }
+        if !self.groups.is_empty() {
+            out.push_str("groups:\n");
+            for (group_name, group) in &self.groups {
+                out.push_str(&format!("  {}:\n", serde_yaml::to_string(group_name)?.trim_end()));
+                let settings = GroupEntry {
+                    hosts: IndexMap::new(),
+                    ..group.clone()
+                };
+                let settings = serde_yaml::to_string(&settings)?;
+                if settings.trim() != "{}" {
+                    out.push_str(&indent_yaml(&settings, 4));
+                }
+                if !group.hosts.is_empty() {
+                    out.push_str("    hosts:\n");
+                    for (name, entry) in &group.hosts {
+                        self.write_entry(&mut out, 6, name, entry)?;
+                    }
+                }
+            }
+        }
+        if !self.profiles.is_empty() {
+            out.push_str(&serde_yaml::to_string(&IndexMap::from([("profiles", &self.profiles)]))?);
+        }
+        Ok(out)
+    }
+
+    /// Append one `name: entry` block, preceded by its origin when several files were merged
+    fn write_entry(&self, out: &mut String, indent: usize, name: &str, entry: &HostEntry) -> Result<()> {
+        if self.files.len() > 1
+            && let Some(origin) = self.origin(name)
+        {
+            out.push_str(&format!("{}# from {}\n", " ".repeat(indent), origin.display()));
+        }
+        out.push_str(&indent_yaml(
+            &serde_yaml::to_string(&IndexMap::from([(name, entry)]))?,
+            indent,
+        ));
+        Ok(())
+    }
+
+    fn validate_profile(&self, profile: &Profile) -> Result<()> {
+        validate_knobs(profile.timeout, profile.count, profile.interval)?;
+        let groups: Vec<&str> = self.groups.keys().map(String::as_str).collect();
+        for group in &profile.groups {
+            if !self.groups.contains_key(group) {
+                let hint = did_you_mean(group, &groups).map(|g| format!(" (did you mean '{}'?)", g));
+                eyre::bail!("unknown group '{}'{}", group, hint.unwrap_or_default());
+            }
+        }
+        if profile.conditions.as_ref().is_some_and(|m| m.is_empty()) {
+            eyre::bail!("match needs at least one of gateway, interface or nameserver");
+        }
+        Ok(())
+    }
+
+    /// Validate values that serde cannot check on its own
+    pub fn validate(&self) -> Result<()> {
+        validate_knobs(Some(self.timeout), Some(self.count), Some(self.interval))?;
+        if self.concurrency == 0 {
+            eyre::bail!("concurrency must be greater than 0");
+        }
+        if self.rate_limit == Some(0) {
+            eyre::bail!("rate_limit must be greater than 0");
+        }
+        // Config files can only use the built-in check types
+        let registry = Registry::default();
+        for (name, group) in &self.groups {
+            validate_knobs(group.timeout, group.count, group.interval).context(format!("Invalid group '{}'", name))?;
+            if group.concurrency == Some(0) {
+                eyre::bail!("Invalid group '{}': concurrency must be greater than 0", name);
+            }
+            for (key, threshold) in &group.thresholds {
+                Threshold::validate(key, threshold, &registry).context(format!("Invalid group '{}'", name))?;
+            }
+        }
+        for (name, profile) in &self.profiles {
+            self.validate_profile(profile)
+                .context(format!("Invalid profile '{}'", name))?;
+        }
+        // Hosts expanded from sweeps share the namespace of named hosts, since
+        // results and watch state are keyed by name
+        let mut seen: HashMap<String, Option<&str>> = HashMap::new();
+        let clash = |name: &str, first: Option<&str>, second: Option<&str>| {
+            let sweeps: Vec<String> = [first, second]
+                .into_iter()
+                .flatten()
+                .map(|s| format!("'{}'", s))
+                .collect();
+            match sweeps.as_slice() {
+                [] => eyre::eyre!("Host '{}' is defined more than once", name),
+                _ => eyre::eyre!(
+                    "Host '{}' is defined more than once (expanded from {})",
+                    name,
+                    sweeps.join(" and ")
+                ),
+            }
+        };
+        for (name, entry, _) in self.entries() {
+            if let Some(first) = seen.insert(name.clone(), None) {
+                return Err(clash(name, first, None));
+            }
+            entry.validate().context(format!("Invalid host '{}'", name))?;
+            for address in sweep::expand(&entry.address)?.unwrap_or_default() {
+                let expanded = sweep_host_name(name, &address);
+                if let Some(first) = seen.insert(expanded.clone(), Some(name)) {
+                    return Err(clash(&expanded, first, Some(name)));
+                }
+            }
+        }
+        self.validate_dependencies()
     }
 }
 
 /// Host entry in config file (without name, which is the map key)
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct HostEntry {
-    /// IP address or hostname
+    /// IP address or hostname, or a pattern expanding to many: `10.0.4.0/28`,
+    /// `10.0.4.10-20`, `web-{01..12}.internal`
     pub address: String,
     /// Whether to perform ping check
     #[serde(default)]
@@ -55,10 +571,281 @@ pub struct HostEntry {
     /// Whether to perform DNS resolution (only valid for hostnames, not IPs)
     #[serde(default)]
     pub dns: bool,
+    /// ICMP payload size in bytes (default 56)
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub payload_size: Option<usize>,
+    /// ICMP payload fill pattern as hex bytes (e.g. "ff00")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_pattern: Option<String>,
+    /// IP TTL / IPv6 hop limit for ping packets
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(range(min = 1, max = 255))]
+    pub ttl: Option<u32>,
+    /// Set the don't-fragment bit on ping packets
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dont_fragment: bool,
+    /// DSCP code point (0-63) for ping packets
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(range(max = 63))]
+    pub dscp: Option<u8>,
+    /// Minimum path MTU that must pass with DF set
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mtu_min: Option<usize>,
+    /// TCP ports that must accept a connection
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tcp: Vec<u16>,
+    /// Commands run as checks, judged by exit code like Nagios plugins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exec: Vec<ExecCommand>,
+    /// Interface to bind this host's probe sockets to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Local address to send this host's probes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+    /// Network namespace (name under /var/run/netns or path) to probe from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub netns: Option<String>,
+    /// Labels used to select hosts with --tag / --exclude-tag
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Timeout in milliseconds, overriding the group and global value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<u64>,
+    /// Ping packets per check, overriding the group and global value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub count: Option<u32>,
+    /// Retry attempts, overriding the group and global value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retries: Option<u32>,
+    /// Watch interval in seconds, overriding the group and global value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub interval: Option<u64>,
+    /// Hosts that must be up for this one to be reachable; they are checked first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Limits on check metrics keyed `<check>.<metric>`, e.g. `ping.rtt_ms: { warn: 50, fail: 200 }`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub thresholds: IndexMap<String, Threshold>,
+}
+
+/// Named group of hosts in config file
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GroupEntry {
+    /// Tags applied to every host in the group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Timeout in milliseconds for hosts in the group
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<u64>,
+    /// Ping packets per check for hosts in the group
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub count: Option<u32>,
+    /// Retry attempts for hosts in the group
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retries: Option<u32>,
+    /// Watch interval in seconds for hosts in the group
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub interval: Option<u64>,
+    /// Interface to bind the group's probe sockets to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Local address to send the group's probes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+    /// Most hosts in the group checked at once, e.g. to go easy on a small subnet
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(range(min = 1))]
+    pub concurrency: Option<usize>,
+    /// Hosts every host in the group depends on, e.g. the VPN gateway in front of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Metric limits for hosts in the group; a host's own thresholds take precedence
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub thresholds: IndexMap<String, Threshold>,
+    /// Map of host name to host configuration
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub hosts: IndexMap<String, HostEntry>,
+}
+
+impl GroupEntry {
+    fn source_binding(&self) -> SourceBinding {
+        SourceBinding {
+            interface: self.interface.clone(),
+            address: self.source,
+        }
+    }
+}
+
+/// Prefix every line of a YAML snippet with `indent` spaces
+fn indent_yaml(yaml: &str, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    yaml.lines().map(|line| format!("{}{}\n", pad, line)).collect()
+}
+
+/// Reject zero values for timing knobs, which would make checks meaningless
+fn validate_knobs(timeout: Option<u64>, count: Option<u32>, interval: Option<u64>) -> Result<()> {
+    if timeout == Some(0) {
+        eyre::bail!("timeout must be greater than 0");
+    }
+    if count == Some(0) {
+        eyre::bail!("count must be greater than 0");
+    }
+    if interval == Some(0) {
+        eyre::bail!("interval must be greater than 0");
+    }
+    Ok(())
+}
+
+impl HostEntry {
+    fn source_binding(&self) -> SourceBinding {
+        SourceBinding {
+            interface: self.interface.clone(),
+            address: self.source,
+        }
+    }
+
+    /// Build ping packet options from this entry (assumes `validate` passed)
+    fn ping_options(&self) -> PingOptions {
+        PingOptions {
+            size: self.payload_size.unwrap_or(ping::DEFAULT_PAYLOAD_SIZE),
+            pattern: self
+                .payload_pattern
+                .as_deref()
+                .and_then(|p| ping::parse_pattern(p).ok())
+                .unwrap_or_default(),
+            ttl: self.ttl,
+            dont_fragment: self.dont_fragment,
+            dscp: self.dscp,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_knobs(self.timeout, self.count, self.interval)?;
+        sweep::expand(&self.address)?;
+        if let Some(size) = self.payload_size
+            && size > ping::MAX_PAYLOAD_SIZE
+        {
+            eyre::bail!("payload_size {} exceeds maximum of {}", size, ping::MAX_PAYLOAD_SIZE);
+        }
+        if let Some(ref pattern) = self.payload_pattern {
+            ping::parse_pattern(pattern)?;
+        }
+        if let Some(ttl) = self.ttl
+            && !(1..=255).contains(&ttl)
+        {
+            eyre::bail!("ttl must be between 1 and 255, got {}", ttl);
+        }
+        if let Some(dscp) = self.dscp
+            && dscp > 63
+        {
+            eyre::bail!("dscp must be between 0 and 63, got {}", dscp);
+        }
+        if let Some(mtu_min) = self.mtu_min
+            && !(mtu::IPV4_MIN_MTU..=mtu::MAX_MTU).contains(&mtu_min)
+        {
+            eyre::bail!(
+                "mtu_min must be between {} and {}, got {}",
+                mtu::IPV4_MIN_MTU,
+                mtu::MAX_MTU,
+                mtu_min
+            );
+        }
+        for (i, command) in self.exec.iter().enumerate() {
+            command.validate()?;
+            if let Some(name) = &command.name
+                && self.exec[..i].iter().any(|c| c.name.as_ref() == Some(name))
+            {
+                eyre::bail!("exec name '{}' is used more than once", name);
+            }
+        }
+        for (key, threshold) in &self.thresholds {
+            Threshold::validate(key, threshold, &Registry::default())?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of the host a sweep entry expands to for one address
+fn sweep_host_name(name: &str, address: &str) -> String {
+    format!("{} {}", name, address)
+}
+
+/// One host per expanded address, named `<entry name> <address>`, or the host itself
+fn expand_sweep(host: HostConfig, addresses: Option<&[String]>) -> Vec<HostConfig> {
+    let Some(addresses) = addresses else {
+        return vec![host];
+    };
+    let origin = Sweep {
+        name: host.name.clone(),
+        pattern: host.address.clone(),
+    };
+    addresses
+        .iter()
+        .map(|address| HostConfig {
+            name: sweep_host_name(&host.name, address),
+            address: address.clone(),
+            sweep: Some(origin.clone()),
+            ..host.clone()
+        })
+        .collect()
 }
 
 /// Host configuration with name (used internally after loading)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct HostConfig {
     /// Display name for the host
     pub name: String,
@@ -68,6 +855,36 @@ pub struct HostConfig {
     pub ping: bool,
     /// Whether to perform DNS resolution (only valid for hostnames, not IPs)
     pub dns: bool,
+    /// ICMP packet parameters for the ping check
+    pub ping_options: PingOptions,
+    /// Minimum path MTU that must pass with DF set
+    pub mtu_min: Option<usize>,
+    /// TCP ports that must accept a connection
+    pub tcp: Vec<u16>,
+    /// Commands run as checks
+    pub exec: Vec<ExecCommand>,
+    /// Interface / source address probes are bound to
+    pub source: SourceBinding,
+    /// Network namespace to run this host's probes in
+    pub netns: Option<String>,
+    /// Group the host was declared in, if any
+    pub group: Option<String>,
+    /// Timeout for each ping/DNS attempt
+    pub timeout: Duration,
+    /// Ping packets sent per check
+    pub count: u32,
+    /// Extra attempts for a failed ping or DNS check
+    pub retries: u32,
+    /// Watch interval in seconds
+    pub interval: u64,
+    /// DNS servers to query (resolver default if empty)
+    pub nameservers: Vec<IpAddr>,
+    /// Names of hosts that must be up before this one is checked
+    pub depends_on: Vec<String>,
+    /// Limits on check metrics, keyed `<check>.<metric>`
+    pub thresholds: IndexMap<String, Threshold>,
+    /// Entry this host was generated from, if its address was a CIDR / range / brace pattern
+    pub sweep: Option<Sweep>,
 }
 
 impl HostConfig {
@@ -78,7 +895,7 @@ impl HostConfig {
 
     /// Check if this host has any checks enabled
     pub fn has_checks(&self) -> bool {
-        self.ping || self.dns
+        self.ping || self.dns || self.mtu_min.is_some() || !self.tcp.is_empty() || !self.exec.is_empty()
     }
 
     /// Check if DNS resolution should be performed
@@ -89,54 +906,541 @@ impl HostConfig {
 }
 
 impl Config {
+    /// Primary config location: ~/.config/cxn/cxn.yml
+    pub fn default_path() -> Option<PathBuf> {
+        let project_name = env!("CARGO_PKG_NAME");
+        dirs::config_dir().map(|dir| dir.join(project_name).join(format!("{}.yml", project_name)))
+    }
+
+    /// JSON Schema describing the config file format, for editor completion and linting
+    pub fn json_schema() -> Result<String> {
+        Ok(serde_json::to_string_pretty(&schemars::schema_for!(ConfigFile))?)
+    }
+
     /// Load configuration with fallback chain
+    ///
+    /// Files may be YAML, TOML or JSON, told apart by extension or content.
+    /// Without an explicit path, fragments in `~/.config/cxn/conf.d/` are
+    /// merged into whichever main file was found (or into the defaults).
     pub fn load(config_path: Option<&PathBuf>) -> Result<Self> {
+        Self::load_with(config_path, true)
+    }
+
+    /// Load configuration like `load`, but fail instead of falling back when a
+    /// main file exists and is broken
+    pub fn load_strict(config_path: Option<&PathBuf>) -> Result<Self> {
+        Self::load_with(config_path, false)
+    }
+
+    fn load_with(config_path: Option<&PathBuf>, lenient: bool) -> Result<Self> {
         // If explicit config path provided, try to load it
         if let Some(path) = config_path {
             return Self::load_from_file(path).context(format!("Failed to load config from {}", path.display()));
         }
 
-        // Try primary location: ~/.config/cxn/cxn.yml
-        if let Some(config_dir) = dirs::config_dir() {
-            let project_name = env!("CARGO_PKG_NAME");
-            let primary_config = config_dir.join(project_name).join(format!("{}.yml", project_name));
-            if primary_config.exists() {
-                match Self::load_from_file(&primary_config) {
-                    Ok(config) => return Ok(config),
-                    Err(e) => {
-                        log::warn!("Failed to load config from {}: {}", primary_config.display(), e);
-                    }
+        let mut candidates = Vec::new();
+
+        // Try primary location: ~/.config/cxn/cxn.yml (or .yaml, .toml, .json)
+        let primary = Self::default_path();
+        let config_dir = primary.as_ref().and_then(|p| p.parent()).map(Path::to_path_buf);
+        candidates.extend(primary.iter().flat_map(|p| with_config_extensions(p)));
+
+        // Try fallback location: ./cxn.yml (or .yaml, .toml, .json)
+        candidates.extend(with_config_extensions(Path::new(env!("CARGO_PKG_NAME"))));
+
+        let mut loader = None;
+        for candidate in candidates.iter().filter(|c| c.exists()) {
+            let mut attempt = Loader::default();
+            match attempt.load_file(candidate) {
+                Ok(()) => {
+                    loader = Some(attempt);
+                    break;
                 }
+                Err(e) if lenient => log::warn!("Failed to load config from {}: {:#}", candidate.display(), e),
+                Err(e) => return Err(e.wrap_err(format!("Failed to load config from {}", candidate.display()))),
             }
         }
+        let mut loader = loader.unwrap_or_else(|| {
+            log::info!("No config file found, using defaults");
+            Loader::default()
+        });
 
-        // Try fallback location: ./cxn.yml
-        let project_name = env!("CARGO_PKG_NAME");
-        let fallback_config = PathBuf::from(format!("{}.yml", project_name));
-        if fallback_config.exists() {
-            match Self::load_from_file(&fallback_config) {
-                Ok(config) => return Ok(config),
-                Err(e) => {
-                    log::warn!("Failed to load config from {}: {}", fallback_config.display(), e);
-                }
+        // Drop-in fragments are merged after the main file, so its settings win
+        if let Some(conf_d) = config_dir.map(|dir| dir.join(CONF_D)) {
+            if conf_d.is_dir() {
+                loader.load_dir(&conf_d)?;
             }
+            loader.fragment_dirs.push(conf_d);
         }
 
-        // No config file found, use defaults
-        log::info!("No config file found, using defaults");
-        Ok(Self::default())
+        loader.finish()
     }
 
-    fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(&path).context("Failed to read config file")?;
+    /// Load a single config file and its includes, without the fallback chain or conf.d
+    ///
+    /// ```no_run
+    /// let config = cxn::Config::load_from_file("cxn.yml")?;
+    /// for host in config.hosts() {
+    ///     println!("{} ({})", host.name, host.address);
+    /// }
+    /// # Ok::<(), eyre::Report>(())
+    /// ```
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut loader = Loader::default();
+        loader.load_file(path.as_ref())?;
+        loader.finish()
+    }
+}
+
+/// A config path with each supported extension in turn, e.g. `cxn.yml` .. `cxn.json`
+fn with_config_extensions(path: &Path) -> Vec<PathBuf> {
+    CONFIG_EXTENSIONS.iter().map(|ext| path.with_extension(ext)).collect()
+}
+
+/// Drop-in directory beside the primary config file
+const CONF_D: &str = "conf.d";
+
+/// A single config file as written, before includes and fragments are merged
+///
+/// Top-level settings are optional here so the loader can tell which file set them.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+#[schemars(title = "cxn.yml", description = "cxn connectivity check configuration")]
+struct ConfigFile {
+    /// Timeout for each ping / DNS attempt in milliseconds (default 1000)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(range(min = 1), extend("default" = 1000))]
+    timeout: Option<u64>,
+    /// Ping packets sent per check; passes if any reply (default 1)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(range(min = 1), extend("default" = 1))]
+    count: Option<u32>,
+    /// Extra attempts for a failed ping or DNS check (default 0)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(extend("default" = 0))]
+    retries: Option<u32>,
+    /// Watch-mode interval in seconds (default 5)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(range(min = 1), extend("default" = 5))]
+    interval: Option<u64>,
+    /// Default interface to bind probe sockets to
+    interface: Option<String>,
+    /// Default local address to send probes from
+    source: Option<IpAddr>,
+    /// DNS servers to query instead of the resolver default
+    nameservers: Option<Vec<IpAddr>>,
+    /// Number of hosts checked at once (default 20)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(range(min = 1), extend("default" = 20))]
+    concurrency: Option<usize>,
+    /// Most host checks started per second (default unlimited)
+    #[serde(deserialize_with = "interpolate::number")]
+    #[schemars(range(min = 1))]
+    rate_limit: Option<u32>,
+    /// Glob patterns of further files to merge, relative to this file
+    include: Vec<String>,
+    /// Map of host name to host configuration
+    hosts: IndexMap<String, HostEntry>,
+    /// Named groups of hosts, checked after ungrouped hosts
+    groups: IndexMap<String, GroupEntry>,
+    /// Named network contexts, selected with --profile or by their `match` conditions
+    profiles: IndexMap<String, Profile>,
+}
+
+/// Merges a main config file, its includes and conf.d fragments into one Config
+///
+/// Files are merged in load order: a file's own contents, then its includes
+/// depth-first. The first file to set a top-level setting wins, so the main
+/// file takes precedence over everything it pulls in. Host names must be
+/// unique across all files.
+#[derive(Default)]
+struct Loader {
+    merged: ConfigFile,
+    /// File each top-level setting came from, for conflict warnings
+    settings: HashMap<&'static str, PathBuf>,
+    /// File each host was defined in, for duplicate errors
+    origins: HashMap<String, PathBuf>,
+    /// Canonical paths already loaded, to break include cycles
+    visited: HashSet<PathBuf>,
+    /// Files loaded, in order
+    files: Vec<PathBuf>,
+    /// Fragment directories merged, or to be merged once they exist
+    fragment_dirs: Vec<PathBuf>,
+    /// Variable lookup for `${VAR}` references; the process environment unless set
+    lookup: Option<fn(&str) -> Option<String>>,
+}
+
+impl Loader {
+    /// Load one file and everything it includes
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        let canonical = fs::canonicalize(path).context(format!("Failed to read config file {}", path.display()))?;
+        if !self.visited.insert(canonical) {
+            log::debug!("Skipping already loaded config file {}", path.display());
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).context(format!("Failed to read config file {}", path.display()))?;
+        let format = ConfigFormat::detect(path, &content);
+        let mut value: serde_yaml::Value = format
+            .parse(&content)
+            .context(format!("Failed to parse config file {}", path.display()))?;
+        // Checked before deserializing so the error names the full path and a likely fix
+        let unknown = unknown_keys(&value);
+        if !unknown.is_empty() {
+            eyre::bail!("Unknown key(s) in {}: {}", path.display(), unknown.join(", "));
+        }
+
+        let file: ConfigFile = if content.contains('$') {
+            interpolate::expand_value_with(&mut value, &self.lookup.unwrap_or(interpolate::env))
+                .context(format!("Failed to expand variables in {}", path.display()))?;
+            // Expanded values may hold secrets, so errors past this point are redacted
+            serde_yaml::from_value(value)
+                .map_err(|e| interpolate::redact_report(e.into()))
+                .context(format!("Failed to parse config file {}", path.display()))?
+        } else {
+            // Parsing the text directly keeps line and column numbers in errors
+            format
+                .parse(&content)
+                .context(format!("Failed to parse config file {}", path.display()))?
+        };
+        self.files.push(path.to_path_buf());
+        let includes = self.merge(file, path).map_err(interpolate::redact_report)?;
+        log::info!("Loaded config from: {}", path.display());
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        for pattern in includes {
+            for included in expand_include(base, &pattern).context(format!("Bad include in {}", path.display()))? {
+                self.load_file(&included)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load every YAML, TOML and JSON file in a directory, in name order
+    fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .context(format!("Failed to read {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && ConfigFormat::from_extension(p).is_some())
+            .collect();
+        files.sort();
+        for file in files {
+            self.load_file(&file)?;
+        }
+        Ok(())
+    }
+
+    /// Fold one parsed file into the merged config, returning its includes
+    fn merge(&mut self, file: ConfigFile, path: &Path) -> Result<Vec<String>> {
+        let merged = &mut self.merged;
+        let settings = &mut self.settings;
+        first_wins(&mut merged.timeout, file.timeout, "timeout", path, settings);
+        first_wins(&mut merged.count, file.count, "count", path, settings);
+        first_wins(&mut merged.retries, file.retries, "retries", path, settings);
+        first_wins(&mut merged.interval, file.interval, "interval", path, settings);
+        first_wins(&mut merged.interface, file.interface, "interface", path, settings);
+        first_wins(&mut merged.source, file.source, "source", path, settings);
+        first_wins(&mut merged.nameservers, file.nameservers, "nameservers", path, settings);
+        first_wins(&mut merged.concurrency, file.concurrency, "concurrency", path, settings);
+        first_wins(&mut merged.rate_limit, file.rate_limit, "rate_limit", path, settings);
+
+        // Like top-level settings, the first file to define a profile wins
+        for (name, profile) in file.profiles {
+            if self.merged.profiles.contains_key(&name) {
+                log::warn!(
+                    "Profile '{}' in {} is already defined; ignoring it",
+                    name,
+                    path.display()
+                );
+            } else {
+                self.merged.profiles.insert(name, profile);
+            }
+        }
+
+        for (name, entry) in file.hosts {
+            self.claim(&name, &entry, path)?;
+            self.merged.hosts.insert(name, entry);
+        }
+
+        // Groups with the same name in several files are combined
+        for (group_name, group) in file.groups {
+            for (name, entry) in &group.hosts {
+                self.claim(name, entry, path)?;
+            }
+            let target = self.merged.groups.entry(group_name).or_default();
+            for tag in group.tags {
+                if !target.tags.contains(&tag) {
+                    target.tags.push(tag);
+                }
+            }
+            for parent in group.depends_on {
+                if !target.depends_on.contains(&parent) {
+                    target.depends_on.push(parent);
+                }
+            }
+            for (key, threshold) in group.thresholds {
+                target.thresholds.entry(key).or_insert(threshold);
+            }
+            // Group settings follow the same first-file-wins rule as top-level ones
+            target.timeout = target.timeout.or(group.timeout);
+            target.count = target.count.or(group.count);
+            target.retries = target.retries.or(group.retries);
+            target.interval = target.interval.or(group.interval);
+            target.interface = target.interface.take().or(group.interface);
+            target.source = target.source.or(group.source);
+            target.concurrency = target.concurrency.or(group.concurrency);
+            target.hosts.extend(group.hosts);
+        }
+
+        Ok(file.include)
+    }
 
-        let config: Self = serde_yaml::from_str(&content).context("Failed to parse config file")?;
+    /// Validate a host and record which file defines it
+    fn claim(&mut self, name: &str, entry: &HostEntry, path: &Path) -> Result<()> {
+        entry
+            .validate()
+            .context(format!("Invalid host '{}' in {}", name, path.display()))?;
+        if let Some(first) = self.origins.get(name) {
+            eyre::bail!(
+                "Host '{}' in {} is already defined in {}",
+                name,
+                path.display(),
+                first.display()
+            );
+        }
+        self.origins.insert(name.to_string(), path.to_path_buf());
+        Ok(())
+    }
 
-        log::info!("Loaded config from: {}", path.as_ref().display());
+    /// Build the final config, filling unset settings with defaults
+    fn finish(self) -> Result<Config> {
+        let defaults = Config::default();
+        let file = self.merged;
+        let config = Config {
+            timeout: file.timeout.unwrap_or(defaults.timeout),
+            count: file.count.unwrap_or(defaults.count),
+            retries: file.retries.unwrap_or(defaults.retries),
+            interval: file.interval.unwrap_or(defaults.interval),
+            interface: file.interface,
+            source: file.source,
+            nameservers: file.nameservers.unwrap_or_default(),
+            concurrency: file.concurrency.unwrap_or(defaults.concurrency),
+            rate_limit: file.rate_limit,
+            hosts: file.hosts,
+            groups: file.groups,
+            profiles: file.profiles,
+            files: self.files,
+            fragment_dirs: self.fragment_dirs,
+            origins: self.origins,
+            ..defaults
+        };
+        config.validate()?;
         Ok(config)
     }
 }
 
+/// Keys accepted at the top level of a config file
+const TOP_LEVEL_KEYS: &[&str] = &[
+    "timeout",
+    "count",
+    "retries",
+    "interval",
+    "interface",
+    "source",
+    "nameservers",
+    "concurrency",
+    "rate_limit",
+    "include",
+    "hosts",
+    "groups",
+    "profiles",
+];
+
+/// Keys accepted in a group
+const GROUP_KEYS: &[&str] = &[
+    "tags",
+    "timeout",
+    "count",
+    "retries",
+    "interval",
+    "interface",
+    "source",
+    "concurrency",
+    "depends_on",
+    "thresholds",
+    "hosts",
+];
+
+/// Keys accepted in a profile
+const PROFILE_KEYS: &[&str] = &[
+    "groups",
+    "timeout",
+    "count",
+    "retries",
+    "interval",
+    "interface",
+    "source",
+    "nameservers",
+    "match",
+];
+
+/// Keys accepted in a profile's `match`
+const MATCH_KEYS: &[&str] = &["gateway", "interface", "nameserver"];
+
+/// Keys accepted in a host entry
+const HOST_KEYS: &[&str] = &[
+    "address",
+    "ping",
+    "dns",
+    "payload_size",
+    "payload_pattern",
+    "ttl",
+    "dont_fragment",
+    "dscp",
+    "mtu_min",
+    "tcp",
+    "exec",
+    "interface",
+    "source",
+    "netns",
+    "tags",
+    "timeout",
+    "count",
+    "retries",
+    "interval",
+    "depends_on",
+    "thresholds",
+];
+
+/// List unrecognised keys as dotted paths like `hosts.Web.pnig`, with suggestions
+fn unknown_keys(document: &serde_yaml::Value) -> Vec<String> {
+    fn check(map: &serde_yaml::Value, known: &[&str], prefix: &str, found: &mut Vec<String>) {
+        if let Some(map) = map.as_mapping() {
+            for key in map.keys().filter_map(|k| k.as_str()) {
+                if !known.contains(&key) {
+                    let hint = did_you_mean(key, known).map(|s| format!(" (did you mean '{}'?)", s));
+                    found.push(format!("{}{}{}", prefix, key, hint.unwrap_or_default()));
+                }
+            }
+        }
+    }
+    fn check_hosts(hosts: Option<&serde_yaml::Value>, prefix: &str, found: &mut Vec<String>) {
+        if let Some(hosts) = hosts.and_then(|h| h.as_mapping()) {
+            for (name, entry) in hosts {
+                let name = name.as_str().unwrap_or_default();
+                check(entry, HOST_KEYS, &format!("{}hosts.{}.", prefix, name), found);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    check(document, TOP_LEVEL_KEYS, "", &mut found);
+    check_hosts(document.get("hosts"), "", &mut found);
+    if let Some(groups) = document.get("groups").and_then(|g| g.as_mapping()) {
+        for (name, group) in groups {
+            let prefix = format!("groups.{}.", name.as_str().unwrap_or_default());
+            check(group, GROUP_KEYS, &prefix, &mut found);
+            check_hosts(group.get("hosts"), &prefix, &mut found);
+        }
+    }
+    if let Some(profiles) = document.get("profiles").and_then(|p| p.as_mapping()) {
+        for (name, profile) in profiles {
+            let prefix = format!("profiles.{}.", name.as_str().unwrap_or_default());
+            check(profile, PROFILE_KEYS, &prefix, &mut found);
+            if let Some(conditions) = profile.get("match") {
+                check(conditions, MATCH_KEYS, &format!("{}match.", prefix), &mut found);
+            }
+        }
+    }
+    found
+}
+
+/// The known key closest to a misspelt one, if it is close enough to be a typo
+pub(crate) fn did_you_mean<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    let key = key.to_ascii_lowercase();
+    // Allow one edit per three characters, so short keys only match near-identical ones
+    let max_distance = (key.len() / 3).max(1);
+    known
+        .iter()
+        .map(|candidate| {
+            (
+                strsim::damerau_levenshtein(&key, &candidate.to_ascii_lowercase()),
+                *candidate,
+            )
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+        // A truncated key like `mtu` for `mtu_min`
+        .or_else(|| {
+            known
+                .iter()
+                .copied()
+                .find(|candidate| key.len() >= 3 && candidate.to_ascii_lowercase().starts_with(&key))
+        })
+}
+
+/// Keep the first file's value for a top-level setting, warning if a later file disagrees
+fn first_wins<T: PartialEq>(
+    slot: &mut Option<T>,
+    value: Option<T>,
+    key: &'static str,
+    path: &Path,
+    settings: &mut HashMap<&'static str, PathBuf>,
+) {
+    let Some(value) = value else { return };
+    match settings.get(key) {
+        None => {
+            settings.insert(key, path.to_path_buf());
+            *slot = Some(value);
+        }
+        Some(first) if slot.as_ref() != Some(&value) => {
+            log::warn!(
+                "Ignoring {} from {}: already set in {}",
+                key,
+                path.display(),
+                first.display()
+            );
+        }
+        Some(_) => {}
+    }
+}
+
+/// Expand an include pattern relative to the including file's directory
+///
+/// Patterns may start with `~/`. A pattern without glob characters must name
+/// an existing file; a glob that matches nothing is not an error.
+fn expand_include(base: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = match pattern.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .ok_or_else(|| eyre::eyre!("Cannot expand ~ without a home directory"))?
+            .join(rest),
+        None => PathBuf::from(pattern),
+    };
+    let full = if path.is_absolute() {
+        path.to_string_lossy().into_owned()
+    } else {
+        format!(
+            "{}/{}",
+            glob::Pattern::escape(&base.to_string_lossy()),
+            path.to_string_lossy()
+        )
+    };
+
+    let mut matches: Vec<PathBuf> = glob::glob(&full)
+        .context(format!("Invalid include pattern '{}'", pattern))?
+        .filter_map(|entry| entry.ok())
+        .filter(|p| p.is_file())
+        .collect();
+    matches.sort();
+
+    let is_glob = pattern.contains(['*', '?', '[']);
+    if matches.is_empty() && !is_glob {
+        eyre::bail!("Included file not found: {}", pattern);
+    }
+    Ok(matches)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,7 +1449,7 @@ mod tests {
     fn test_config_default() {
         let config = Config::default();
         assert_eq!(config.timeout, 1000);
-        assert_eq!(config.retries, 3);
+        assert_eq!(config.retries, 0);
         assert_eq!(config.interval, 5);
         assert!(config.hosts().is_empty());
     }
@@ -157,6 +1461,7 @@ mod tests {
             address: "8.8.8.8".to_string(),
             ping: true,
             dns: false,
+            ..Default::default()
         };
         assert!(ip_host.is_ip_address());
 
@@ -165,6 +1470,7 @@ mod tests {
             address: "google.com".to_string(),
             ping: true,
             dns: true,
+            ..Default::default()
         };
         assert!(!hostname_host.is_ip_address());
     }
@@ -177,6 +1483,7 @@ mod tests {
             address: "8.8.8.8".to_string(),
             ping: true,
             dns: true,
+            ..Default::default()
         };
         assert!(!ip_host.should_resolve_dns());
 
@@ -186,6 +1493,7 @@ mod tests {
             address: "google.com".to_string(),
             ping: true,
             dns: true,
+            ..Default::default()
         };
         assert!(hostname_host.should_resolve_dns());
 
@@ -195,6 +1503,7 @@ mod tests {
             address: "google.com".to_string(),
             ping: true,
             dns: false,
+            ..Default::default()
         };
         assert!(!hostname_no_dns.should_resolve_dns());
     }
@@ -223,4 +1532,656 @@ hosts:
         assert!(hosts[0].ping);
         assert!(!hosts[0].dns);
     }
+
+    #[test]
+    fn test_config_parse_ping_options() {
+        let yaml = r#"
+hosts:
+  VPN MTU:
+    address: "10.0.0.1"
+    ping: true
+    payload_size: 1472
+    payload_pattern: "ff00"
+    ttl: 32
+    dont_fragment: true
+    dscp: 46
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let options = &config.hosts()[0].ping_options;
+        assert_eq!(options.size, 1472);
+        assert_eq!(options.pattern, vec![0xff, 0x00]);
+        assert_eq!(options.ttl, Some(32));
+        assert!(options.dont_fragment);
+        assert_eq!(options.dscp, Some(46));
+    }
+
+    #[test]
+    fn test_config_validate_rejects_bad_ping_options() {
+        let yaml = r#"
+hosts:
+  Bad:
+    address: "10.0.0.1"
+    ping: true
+    dscp: 64
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validate_bounds_mtu_min() {
+        let config = |mtu_min: usize| -> Config {
+            serde_yaml::from_str(&format!(
+                "hosts:\n  Link:\n    address: \"10.0.0.1\"\n    mtu_min: {}\n",
+                mtu_min
+            ))
+            .unwrap()
+        };
+        assert!(config(68).validate().is_ok());
+        assert!(config(65535).validate().is_ok());
+        let err = format!("{:#}", config(65536).validate().unwrap_err());
+        assert!(err.contains("between 68 and 65535"), "{}", err);
+        assert!(config(67).validate().is_err());
+    }
+
+    #[test]
+    fn test_config_source_precedence() {
+        let yaml = r#"
+interface: eth0
+hosts:
+  Default:
+    address: "10.0.0.1"
+    ping: true
+  Wifi:
+    address: "10.0.0.2"
+    ping: true
+    interface: wlan0
+    source: "192.168.1.20"
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let hosts = config.hosts();
+        assert_eq!(hosts[0].source.interface.as_deref(), Some("eth0"));
+        assert_eq!(hosts[1].source.interface.as_deref(), Some("wlan0"));
+        assert_eq!(hosts[1].source.address, Some("192.168.1.20".parse().unwrap()));
+
+        config.set_source_override(SourceBinding {
+            interface: Some("wg0".to_string()),
+            address: None,
+        });
+        let hosts = config.hosts();
+        assert_eq!(hosts[0].source.interface.as_deref(), Some("wg0"));
+        assert_eq!(hosts[1].source.interface.as_deref(), Some("wg0"));
+        assert_eq!(hosts[1].source.address, Some("192.168.1.20".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_config_netns_override_expands_hosts() {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+    ping: true
+    netns: blue
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.hosts()[0].netns.as_deref(), Some("blue"));
+
+        config.set_netns_override(vec!["current".to_string(), "red".to_string()]);
+        let hosts = config.hosts();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].name, "Gateway [current]");
+        assert_eq!(hosts[1].name, "Gateway [red]");
+        assert_eq!(hosts[1].netns.as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn test_config_groups_and_tags() {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+    ping: true
+    tags: [core]
+groups:
+  database:
+    tags: [db]
+    hosts:
+      Primary:
+        address: "10.0.1.1"
+        ping: true
+      Replica:
+        address: "10.0.1.2"
+        ping: true
+        tags: [flaky]
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let hosts = config.hosts();
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts[0].group, None);
+        assert_eq!(hosts[1].group.as_deref(), Some("database"));
+
+        config.set_filter(HostFilter {
+            tags: vec!["db".to_string()],
+            exclude_tags: vec!["flaky".to_string()],
+            ..Default::default()
+        });
+        let names: Vec<_> = config.hosts().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["Primary"]);
+
+        // Group names act as tags too
+        config.set_filter(HostFilter {
+            tags: vec!["database".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(config.hosts().len(), 2);
+
+        config.set_filter(HostFilter {
+            hosts: vec!["*a*y".to_string()],
+            ..Default::default()
+        });
+        let names: Vec<_> = config.hosts().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["Gateway", "Primary"]);
+    }
+
+    #[test]
+    fn test_config_validate_rejects_duplicate_names() {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+groups:
+  core:
+    hosts:
+      Gateway:
+        address: "10.0.0.2"
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_validate_rejects_sweep_name_clashes() {
+        let yaml = r#"
+hosts:
+  Lab:
+    address: "10.0.0.0-3"
+    ping: true
+  Lab 10.0.0.2:
+    address: "10.0.0.9"
+    ping: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("'Lab 10.0.0.2'") && err.contains("expanded from 'Lab'"),
+            "{}",
+            err
+        );
+
+        let yaml = "hosts:\n  Lab:\n    address: 10.0.0.0-3\n  Lab 10.0.0.9:\n    address: 10.0.0.9\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.hosts().len(), 5);
+    }
+
+    #[test]
+    fn test_config_includes_merge() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::create_dir_all(dir.join("teams")).unwrap();
+        fs::write(
+            dir.join("cxn.yml"),
+            "timeout: 2000\ninclude: [\"teams/*.yml\"]\nhosts:\n  Gateway:\n    address: 10.0.0.1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("teams/db.yml"),
+            "timeout: 5000\nretries: 1\ngroups:\n  db:\n    hosts:\n      Primary:\n        address: 10.0.1.1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("teams/web.yml"),
+            "groups:\n  db:\n    tags: [sql]\n    hosts:\n      Replica:\n        address: 10.0.1.2\n",
+        )
+        .unwrap();
+
+        let config = Config::load_from_file(dir.join("cxn.yml")).unwrap();
+        // The including file wins; includes fill in what it leaves unset
+        assert_eq!(config.timeout, 2000);
+        assert_eq!(config.retries, 1);
+        let names: Vec<_> = config.hosts().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["Gateway", "Primary", "Replica"]);
+        assert_eq!(config.groups["db"].tags, vec!["sql"]);
+    }
+
+    #[test]
+    fn test_config_toml_and_json_files() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let toml = r#"
+timeout = 2000
+include = ["hosts.json"]
+
+[hosts.Zeta]
+address = "10.0.0.9"
+tcp = [22, 443]
+
+[hosts.Alpha]
+address = "10.0.0.1"
+ping = true
+"#;
+        fs::write(dir.join("cxn.toml"), toml).unwrap();
+        let json = r#"{"groups": {"db": {"tags": ["sql"], "hosts": {"Primary": {"address": "db1", "dns": true}}}}}"#;
+        fs::write(dir.join("hosts.json"), json).unwrap();
+
+        let config = Config::load_from_file(dir.join("cxn.toml")).unwrap();
+        assert_eq!(config.timeout, 2000);
+        let names: Vec<_> = config.hosts().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["Zeta", "Alpha", "Primary"]);
+        assert_eq!(config.hosts()[0].tcp, vec![22, 443]);
+
+        // Unknown keys are caught in every format
+        fs::write(dir.join("hosts.json"), r#"{"hosts": {"B": {"adress": "x"}}}"#).unwrap();
+        let err = format!("{:#}", Config::load_from_file(dir.join("cxn.toml")).unwrap_err());
+        assert!(err.contains("hosts.B.adress (did you mean 'address'?)"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_includes_duplicate_host_cites_files() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::write(
+            dir.join("cxn.yml"),
+            "include: [other.yml]\nhosts:\n  Gateway:\n    address: 10.0.0.1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("other.yml"), "hosts:\n  Gateway:\n    address: 10.0.0.2\n").unwrap();
+
+        let err = format!("{:#}", Config::load_from_file(dir.join("cxn.yml")).unwrap_err());
+        assert!(err.contains("other.yml"), "{}", err);
+        assert!(err.contains("cxn.yml"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_conf_d_and_missing_include() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::create_dir_all(dir.join(CONF_D)).unwrap();
+        fs::write(dir.join(CONF_D).join("b.yml"), "hosts:\n  B:\n    address: 10.0.0.2\n").unwrap();
+        fs::write(dir.join(CONF_D).join("a.yaml"), "hosts:\n  A:\n    address: 10.0.0.1\n").unwrap();
+        fs::write(dir.join(CONF_D).join("notes.txt"), "not config").unwrap();
+
+        let mut loader = Loader::default();
+        loader.load_dir(&dir.join(CONF_D)).unwrap();
+        let names: Vec<_> = loader.finish().unwrap().hosts().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["A", "B"]);
+
+        fs::write(dir.join("cxn.yml"), "include: [missing.yml]\n").unwrap();
+        assert!(Config::load_from_file(dir.join("cxn.yml")).is_err());
+    }
+
+    #[test]
+    fn test_config_interpolates_variables() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let load = |path: &Path| {
+            let mut loader = Loader {
+                lookup: Some(|name| match name {
+                    "DB_HOST" => Some("db.staging.internal".to_string()),
+                    "DB_IP" => Some("10".to_string()),
+                    "TIMEOUT" => Some("2500".to_string()),
+                    _ => None,
+                }),
+                ..Loader::default()
+            };
+            loader.load_file(path)?;
+            loader.finish()
+        };
+        fs::write(
+            dir.join("cxn.yml"),
+            "timeout: ${TIMEOUT}\nhosts:\n  Database:\n    address: ${DB_HOST}\n    retries: \"${TIMEOUT}\"\n\
+             \x20 Fallback:\n    address: ${NOPE:-10.0.0.9}\n  Legacy:\n    address: \"${DB_IP}\"\n",
+        )
+        .unwrap();
+        let config = load(&dir.join("cxn.yml")).unwrap();
+        assert_eq!(config.timeout, 2500);
+        let hosts = config.hosts();
+        assert_eq!(hosts[0].address, "db.staging.internal");
+        assert_eq!(hosts[0].retries, 2500);
+        assert_eq!(hosts[1].address, "10.0.0.9");
+        // A value that looks like a number stays text where the field is text
+        assert_eq!(hosts[2].address, "10");
+
+        fs::write(dir.join("cxn.yml"), "hosts:\n  Database:\n    address: ${NOPE}\n").unwrap();
+        let err = format!("{:#}", load(&dir.join("cxn.yml")).unwrap_err());
+        assert!(err.contains("NOPE") && err.contains("cxn.yml"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_per_host_and_group_overrides() {
+        let yaml = r#"
+timeout: 1000
+count: 2
+interval: 10
+hosts:
+  Local:
+    address: "10.0.0.1"
+groups:
+  satellite:
+    timeout: 3000
+    retries: 1
+    hosts:
+      Uplink:
+        address: "10.9.0.1"
+      Modem:
+        address: "10.9.0.2"
+        timeout: 5000
+        count: 5
+        interval: 60
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let hosts = config.hosts();
+        assert_eq!(hosts[0].timeout, Duration::from_millis(1000));
+        assert_eq!((hosts[0].count, hosts[0].retries, hosts[0].interval), (2, 0, 10));
+        assert_eq!(hosts[1].timeout, Duration::from_millis(3000));
+        assert_eq!((hosts[1].count, hosts[1].retries, hosts[1].interval), (2, 1, 10));
+        assert_eq!(hosts[2].timeout, Duration::from_millis(5000));
+        assert_eq!((hosts[2].count, hosts[2].retries, hosts[2].interval), (5, 1, 60));
+
+        config.set_interval_override(Some(2));
+        assert!(config.hosts().iter().all(|h| h.interval == 2));
+    }
+
+    #[test]
+    fn test_config_validate_rejects_zero_count() {
+        let yaml = r#"
+hosts:
+  Bad:
+    address: "10.0.0.1"
+    count: 0
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_run_limits() {
+        let yaml = r#"
+concurrency: 50
+rate_limit: 100
+groups:
+  branch:
+    concurrency: 2
+    hosts:
+      Printer:
+        address: "10.9.0.5"
+        ping: true
+  core:
+    hosts:
+      Router:
+        address: "10.0.0.1"
+        ping: true
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let limits = config.limits();
+        assert_eq!(limits.concurrency, 50);
+        assert_eq!(limits.group_concurrency, HashMap::from([("branch".to_string(), 2)]));
+        assert_eq!(limits.rate_limit, Some(100));
+        assert_eq!(limits.deadline, None);
+
+        config.set_run_overrides(Some(4), None, Some(Duration::from_secs(30)));
+        let limits = config.limits();
+        assert_eq!((limits.concurrency, limits.rate_limit), (4, Some(100)));
+        assert_eq!(limits.deadline, Some(Duration::from_secs(30)));
+
+        config.groups["branch"].concurrency = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_thresholds_host_over_group() {
+        let yaml = r#"
+groups:
+  branch:
+    thresholds:
+      ping.rtt_ms: { warn: 50, fail: 200 }
+      tcp.rtt_ms: { fail: 500 }
+    hosts:
+      Printer:
+        address: "10.9.0.5"
+        ping: true
+        thresholds:
+          ping.rtt_ms: { warn: 100 }
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let thresholds = &config.hosts()[0].thresholds;
+        let printer = Threshold {
+            warn: Some(100.0),
+            fail: None,
+        };
+        assert_eq!(thresholds["ping.rtt_ms"], printer);
+        assert_eq!(thresholds["tcp.rtt_ms"].fail, Some(500.0));
+
+        config.groups["branch"].hosts["Printer"]
+            .thresholds
+            .insert("rtt_ms".to_string(), Threshold::default());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_exec_names() {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+    exec:
+      - name: vpn
+        command: check_vpn
+      - command: check_load
+    thresholds:
+      exec.vpn.rtt: { warn: 50 }
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.hosts()[0].exec[0].name.as_deref(), Some("vpn"));
+
+        config.hosts["Gateway"].exec[1].name = Some("vpn".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("exec name 'vpn' is used more than once"),
+            "{:#}",
+            err
+        );
+        config.hosts["Gateway"].exec[1].name = Some("vpn.load".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_keys_with_suggestion() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::write(
+            dir.join("cxn.yml"),
+            "timout: 500
+hosts:
+  Web:
+    address: 10.0.0.1
+    pnig: true
+groups:
+  db:
+    colour: red
+",
+        )
+        .unwrap();
+
+        let err = format!("{:#}", Config::load_from_file(dir.join("cxn.yml")).unwrap_err());
+        assert!(err.contains("timout (did you mean 'timeout'?)"), "{}", err);
+        assert!(err.contains("hosts.Web.pnig (did you mean 'ping'?)"), "{}", err);
+        assert!(
+            err.contains("groups.db.colour,") || err.ends_with("groups.db.colour"),
+            "{}",
+            err
+        );
+
+        assert!(serde_yaml::from_str::<Config>("hosts:\n  Web:\n    address: a\n    pnig: true\n").is_err());
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(did_you_mean("adress", HOST_KEYS), Some("address"));
+        assert_eq!(did_you_mean("DNS", HOST_KEYS), Some("dns"));
+        assert_eq!(did_you_mean("mtu", HOST_KEYS), Some("mtu_min"));
+        assert_eq!(did_you_mean("tll", HOST_KEYS), Some("ttl"));
+        assert_eq!(did_you_mean("hostname", HOST_KEYS), None);
+    }
+
+    #[test]
+    fn test_json_schema_matches_known_keys() {
+        let schema: serde_json::Value = serde_json::from_str(&Config::json_schema().unwrap()).unwrap();
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            value["properties"].as_object().unwrap().keys().cloned().collect()
+        };
+        assert_eq!(keys(&schema), TOP_LEVEL_KEYS);
+        assert_eq!(keys(&schema["$defs"]["GroupEntry"]), GROUP_KEYS);
+        assert_eq!(keys(&schema["$defs"]["HostEntry"]), HOST_KEYS);
+        assert_eq!(keys(&schema["$defs"]["Profile"]), PROFILE_KEYS);
+        assert_eq!(keys(&schema["$defs"]["ProfileMatch"]), MATCH_KEYS);
+        assert_eq!(schema["$defs"]["HostEntry"]["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_config_expands_address_patterns() {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.4.1"
+    ping: true
+  LAN:
+    address: "10.0.4.0/30"
+    ping: true
+  Web:
+    address: "web-{01..02}.internal"
+    dns: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let hosts = config.hosts();
+        let names: Vec<_> = hosts.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Gateway",
+                "LAN 10.0.4.0",
+                "LAN 10.0.4.1",
+                "LAN 10.0.4.2",
+                "LAN 10.0.4.3",
+                "Web web-01.internal",
+                "Web web-02.internal"
+            ]
+        );
+        assert_eq!(hosts[0].sweep, None);
+        let sweep = hosts[1].sweep.as_ref().unwrap();
+        assert_eq!((sweep.name.as_str(), sweep.pattern.as_str()), ("LAN", "10.0.4.0/30"));
+        assert!(hosts[5].should_resolve_dns());
+
+        let config: Config = serde_yaml::from_str("hosts:\n  Big:\n    address: 10.0.0.0/8\n").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let yaml = r#"
+timeout: 500
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+    ping: true
+groups:
+  office:
+    hosts:
+      Printer:
+        address: "10.1.0.5"
+        ping: true
+  home:
+    hosts:
+      NAS:
+        address: "192.168.1.10"
+        ping: true
+profiles:
+  home:
+    groups: [home]
+    match:
+      gateway: "192.168.1.1"
+  office:
+    groups: [office]
+    timeout: 2000
+    nameservers: ["10.1.1.53"]
+    match:
+      interface: wg0
+      nameserver: "10.1.1.53"
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        let network = NetworkState {
+            interfaces: vec!["wg0".to_string()],
+            nameservers: vec!["10.1.1.53".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(config.detect_profile(&network), Some("office"));
+        assert_eq!(config.detect_profile(&NetworkState::default()), None);
+
+        config.apply_profile("office").unwrap();
+        let hosts = config.hosts();
+        let names: Vec<_> = hosts.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(names, vec!["Gateway", "Printer"]);
+        assert_eq!(hosts[0].timeout, Duration::from_millis(2000));
+        assert_eq!(hosts[0].nameservers, vec!["10.1.1.53".parse::<IpAddr>().unwrap()]);
+
+        let err = config.apply_profile("hom").unwrap_err().to_string();
+        assert!(err.contains("did you mean 'home'"), "{}", err);
+
+        let bad: Config = serde_yaml::from_str("profiles:\n  vpn:\n    groups: [missing]\n").unwrap();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_dependencies() {
+        let yaml = r#"
+hosts:
+  VPN:
+    address: "10.9.0.1"
+    ping: true
+groups:
+  internal:
+    depends_on: [VPN]
+    hosts:
+      Wiki:
+        address: "10.9.1.5"
+        ping: true
+        depends_on: [Proxy]
+      Proxy:
+        address: "10.9.1.1"
+        ping: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let hosts = config.hosts();
+        assert_eq!(hosts[0].depends_on, Vec::<String>::new());
+        assert_eq!(hosts[1].depends_on, vec!["Proxy", "VPN"]);
+
+        let config: Config = serde_yaml::from_str(&yaml.replace("[Proxy]", "[Proxi]")).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("unknown host 'Proxi' (did you mean 'Proxy'?)"), "{}", err);
+
+        let cycle = yaml.replace("depends_on: [VPN]", "depends_on: [Wiki]");
+        let config: Config = serde_yaml::from_str(&cycle).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("Dependency cycle: Wiki -> Proxy -> Wiki"), "{}", err);
+    }
 }
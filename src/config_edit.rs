@@ -0,0 +1,387 @@
+use cxn::config::HostEntry;
+use cxn::format::ConfigFormat;
+use eyre::{Context, Result};
+use indexmap::IndexMap;
+use std::fs;
+use std::path::Path;
+
+/// Commented starter config written by `cxn config init`
+pub const STARTER_CONFIG: &str = r#"# cxn configuration
+#
+# Run `cxn config validate` after editing and `cxn config show --resolved`
+# to see the effective settings of every host.
+
+# Timeout for each ping / DNS attempt in milliseconds
+timeout: 1000
+# Ping packets sent per check (passes if any reply)
+count: 1
+# Extra attempts for a failed check; each can take up to another timeout
+retries: 0
+# Watch-mode interval in seconds (cxn check --watch)
+interval: 5
+
+# Merge more host lists; globs are relative to this file
+# include:
+#   - teams/*.yml
+
+hosts:
+  Google DNS:
+    address: 8.8.8.8
+    ping: true
+  Cloudflare DNS:
+    address: 1.1.1.1
+    ping: true
+  GitHub:
+    address: github.com
+    ping: true
+    dns: true
+
+# Groups share tags and settings; select them with `cxn check --tag db`
+# groups:
+#   database:
+#     tags: [db]
+#     timeout: 3000
+#     hosts:
+#       Primary:
+#         address: db1.internal
+#         ping: true
+"#;
+
+/// Write the starter config to `path`, creating parent directories
+pub fn init(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        eyre::bail!("{} already exists (use --force to overwrite)", path.display());
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, STARTER_CONFIG).context(format!("Failed to write {}", path.display()))
+}
+
+/// Add a host to a config file, under `hosts:` or a group's `hosts:`
+///
+/// The file is edited as text so comments and ordering are kept; the new
+/// entry is appended at the end of the target map. `check` runs against the
+/// edited file, which is put back as it was if that fails.
+pub fn add_host(
+    path: &Path,
+    group: Option<&str>,
+    name: &str,
+    entry: &HostEntry,
+    check: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    add_hosts(path, group, &[(name, entry)], check)
+}
+
+/// Add several hosts like [`add_host`], writing the file once with all of them
+pub fn add_hosts(
+    path: &Path,
+    group: Option<&str>,
+    entries: &[(&str, &HostEntry)],
+    check: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let original = if path.exists() {
+        Some(fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?)
+    } else {
+        None
+    };
+    let mut edited = original.clone().unwrap_or_default();
+    ensure_yaml(path, &edited)?;
+    let keys: Vec<&str> = match group {
+        Some(group) => vec!["groups", group, "hosts"],
+        None => vec!["hosts"],
+    };
+    for (name, entry) in entries {
+        let block = serde_yaml::to_string(&IndexMap::from([(name, entry)]))?;
+        edited = insert_entry(&edited, &keys, &block)?;
+    }
+    write_checked(path, original.as_deref(), &edited, check)
+}
+
+/// Remove a host (top-level or in any group) from a config file, keeping the
+/// edit only if `check` passes afterwards
+pub fn remove_host(path: &Path, name: &str, check: impl FnOnce() -> Result<()>) -> Result<()> {
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    ensure_yaml(path, &content)?;
+    let edited =
+        remove_entry(&content, name).ok_or_else(|| eyre::eyre!("Host '{}' not found in {}", name, path.display()))?;
+    write_checked(path, Some(&content), &edited, check)
+}
+
+/// Refuse to edit TOML and JSON files, which the text edits here would corrupt
+fn ensure_yaml(path: &Path, content: &str) -> Result<()> {
+    match ConfigFormat::detect(path, content) {
+        ConfigFormat::Yaml => Ok(()),
+        format => eyre::bail!(
+            "{} is {}; only YAML config files can be edited (convert with `cxn config convert --to yaml`)",
+            path.display(),
+            format
+        ),
+    }
+}
+
+/// Write the edited text if it is still valid YAML, then keep it only if
+/// `check` passes; otherwise restore `original` (or remove a file that was new)
+///
+/// The check typically loads the whole config, since an edit that parses can
+/// still clash with includes and conf.d fragments.
+fn write_checked(path: &Path, original: Option<&str>, content: &str, check: impl FnOnce() -> Result<()>) -> Result<()> {
+    serde_yaml::from_str::<serde_yaml::Value>(content).context(format!(
+        "Refusing to write {}: edit produced invalid YAML",
+        path.display()
+    ))?;
+    replace(path, content)?;
+    if let Err(e) = check() {
+        match original {
+            Some(original) => replace(path, original)?,
+            None => fs::remove_file(path).context(format!("Failed to remove {}", path.display()))?,
+        }
+        return Err(e.wrap_err(format!(
+            "{} would no longer load after the edit; left unchanged",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Atomically replace a file's content
+fn replace(path: &Path, content: &str) -> Result<()> {
+    let tmp = path.with_extension("yml.tmp");
+    fs::write(&tmp, content).context(format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).context(format!("Failed to replace {}", path.display()))
+}
+
+/// Indentation of a line in spaces
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Check if a line carries YAML content (not blank, not only a comment)
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// The mapping key a line defines, unquoted, if it starts with `key:`
+fn key_of(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('-') || !is_content(line) {
+        return None;
+    }
+    let raw = if trimmed.starts_with(['"', '\'']) {
+        let quote = trimmed.chars().next()?;
+        let close = trimmed[1..].find(quote)? + 1;
+        &trimmed[..=close]
+    } else {
+        let end = trimmed.find(": ").or_else(|| trimmed.strip_suffix(':').map(str::len))?;
+        &trimmed[..end]
+    };
+    serde_yaml::from_str::<String>(raw).ok()
+}
+
+/// One past the last content line belonging to the block that starts at `start`
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indent_of(lines[start]);
+    let mut end = start + 1;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if is_content(line) {
+            if indent_of(line) <= indent {
+                break;
+            }
+            end = i + 1;
+        }
+    }
+    end
+}
+
+/// Lines spanned by the children of `parent` (the whole file for `None`)
+fn children(lines: &[&str], parent: Option<usize>) -> (usize, usize) {
+    match parent {
+        Some(p) => (p + 1, block_end(lines, p)),
+        None => (0, lines.len()),
+    }
+}
+
+/// Find the line defining `key` directly under `parent`
+fn find_child(lines: &[&str], parent: Option<usize>, key: &str) -> Option<usize> {
+    let (start, end) = children(lines, parent);
+    let child_indent = lines[start..end].iter().find(|l| is_content(l)).map(|l| indent_of(l))?;
+    (start..end).find(|&i| indent_of(lines[i]) == child_indent && key_of(lines[i]).as_deref() == Some(key))
+}
+
+/// Append `block` (a one-entry YAML map) under the nested keys, creating them as needed
+fn insert_entry(content: &str, keys: &[&str], block: &str) -> Result<String> {
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    let mut parent: Option<usize> = None;
+
+    for key in keys {
+        let view: Vec<&str> = lines.iter().map(String::as_str).collect();
+        parent = match find_child(&view, parent, key) {
+            Some(line) => {
+                // `hosts: {}` and similar empty flow maps become block maps
+                if let Some(stripped) = lines[line].trim_end().strip_suffix("{}") {
+                    lines[line] = stripped.trim_end().to_string();
+                }
+                Some(line)
+            }
+            None => {
+                let indent = parent.map(|p| child_indent(&view, p)).unwrap_or(0);
+                let at = parent.map(|p| block_end(&view, p)).unwrap_or(lines.len());
+                lines.insert(at, format!("{}{}:", " ".repeat(indent), yaml_key(key)?));
+                Some(at)
+            }
+        };
+    }
+
+    let view: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let parent = parent.expect("at least one key");
+    let indent = child_indent(&view, parent);
+    let at = block_end(&view, parent);
+    let new_lines: Vec<String> = block.lines().map(|l| format!("{}{}", " ".repeat(indent), l)).collect();
+    lines.splice(at..at, new_lines);
+
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Indentation for children of `parent`: existing children's, or two more than the parent
+fn child_indent(lines: &[&str], parent: usize) -> usize {
+    let (start, end) = children(lines, Some(parent));
+    lines[start..end]
+        .iter()
+        .find(|l| is_content(l))
+        .map(|l| indent_of(l))
+        .unwrap_or(indent_of(lines[parent]) + 2)
+}
+
+/// Quote a key only when YAML needs it
+fn yaml_key(key: &str) -> Result<String> {
+    Ok(serde_yaml::to_string(key)?.trim_end().to_string())
+}
+
+/// Remove the entry for host `name` from `hosts:` or any `groups.*.hosts:`
+fn remove_entry(content: &str, name: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    let mut maps = Vec::new();
+    if let Some(hosts) = find_child(&lines, None, "hosts") {
+        maps.push(hosts);
+    }
+    if let Some(groups) = find_child(&lines, None, "groups") {
+        let (start, end) = children(&lines, Some(groups));
+        let group_indent = child_indent(&lines, groups);
+        for i in start..end {
+            if indent_of(lines[i]) == group_indent
+                && key_of(lines[i]).is_some()
+                && let Some(hosts) = find_child(&lines, Some(i), "hosts")
+            {
+                maps.push(hosts);
+            }
+        }
+    }
+
+    for map in maps {
+        if let Some(entry) = find_child(&lines, Some(map), name) {
+            let end = block_end(&lines, entry);
+            let mut kept: Vec<String> = lines[..entry]
+                .iter()
+                .chain(&lines[end..])
+                .map(|l| l.to_string())
+                .collect();
+            // An emptied map must stay a map, not become null
+            let view: Vec<&str> = kept.iter().map(String::as_str).collect();
+            if block_end(&view, map) == map + 1 {
+                kept[map] = format!("{} {{}}", kept[map].trim_end());
+            }
+            return Some(kept.join("\n") + "\n");
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cxn::config::Config;
+
+    fn entry(address: &str) -> HostEntry {
+        serde_yaml::from_str(&format!("address: {}\nping: true\n", address)).unwrap()
+    }
+
+    #[test]
+    fn test_starter_config_is_valid() {
+        let config: Config = serde_yaml::from_str(STARTER_CONFIG).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.hosts().len(), 3);
+    }
+
+    #[test]
+    fn test_insert_entry_keeps_comments_and_order() {
+        let content = "# top\ntimeout: 500\nhosts:\n  # first\n  A:\n    address: 10.0.0.1\n\n# trailing\n";
+        let block = serde_yaml::to_string(&IndexMap::from([("New Host", entry("10.0.0.9"))])).unwrap();
+        let edited = insert_entry(content, &["hosts"], &block).unwrap();
+        assert_eq!(
+            edited,
+            "# top\ntimeout: 500\nhosts:\n  # first\n  A:\n    address: 10.0.0.1\n  New Host:\n    address: 10.0.0.9\n    ping: true\n    dns: false\n\n# trailing\n"
+        );
+
+        let edited = insert_entry("hosts: {}\n", &["groups", "db", "hosts"], &block).unwrap();
+        let config: Config = serde_yaml::from_str(&edited).unwrap();
+        assert_eq!(config.hosts()[0].group.as_deref(), Some("db"));
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let content = "hosts:\n  A:\n    address: 10.0.0.1\n  \"B: two\":\n    address: 10.0.0.2\ngroups:\n  db:\n    hosts:\n      C:\n        address: 10.0.0.3\n";
+        let edited = remove_entry(content, "B: two").unwrap();
+        assert_eq!(
+            edited,
+            "hosts:\n  A:\n    address: 10.0.0.1\ngroups:\n  db:\n    hosts:\n      C:\n        address: 10.0.0.3\n"
+        );
+        let edited = remove_entry(&edited, "C").unwrap();
+        assert!(edited.ends_with("    hosts: {}\n"), "{}", edited);
+        serde_yaml::from_str::<Config>(&edited).unwrap();
+        assert!(remove_entry(&edited, "missing").is_none());
+    }
+
+    #[test]
+    fn test_failed_check_restores_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxn.yml");
+        let content = "# keep me\nhosts:\n  A:\n    address: 10.0.0.1\n";
+        fs::write(&path, content).unwrap();
+        let reject = || -> Result<()> { eyre::bail!("duplicate host") };
+
+        let err = add_host(&path, None, "B", &entry("10.0.0.2"), reject).unwrap_err();
+        assert!(format!("{:#}", err).contains("duplicate host"), "{:#}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert!(remove_host(&path, "A", reject).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+        let new = dir.path().join("new.yml");
+        assert!(add_host(&new, None, "B", &entry("10.0.0.2"), reject).is_err());
+        assert!(!new.exists());
+
+        add_host(&path, None, "B", &entry("10.0.0.2"), || Ok(())).unwrap();
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .contains("  B:\n    address: 10.0.0.2\n")
+        );
+
+        // A batch is checked once, and rejected as a whole
+        let (c, d) = (entry("10.0.0.3"), entry("10.0.0.4"));
+        let before = fs::read_to_string(&path).unwrap();
+        assert!(add_hosts(&path, Some("db"), &[("C", &c), ("D", &d)], reject).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        add_hosts(&path, Some("db"), &[("C", &c), ("D", &d)], || Ok(())).unwrap();
+        let config = Config::load_strict(Some(&path)).unwrap();
+        assert_eq!(
+            config
+                .hosts()
+                .iter()
+                .filter(|h| h.group.as_deref() == Some("db"))
+                .count(),
+            2
+        );
+    }
+}
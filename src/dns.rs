@@ -1,11 +1,18 @@
+use crate::source::SourceBinding;
 use colored::*;
-use hickory_resolver::TokioAsyncResolver;
-use hickory_resolver::config::{ResolverConfig, ResolverOpts};
-use std::net::IpAddr;
+use eyre::Result;
+use hickory_resolver::AsyncResolver;
+use hickory_resolver::config::{NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::name_server::{GenericConnector, RuntimeProvider, TokioRuntimeProvider};
+use hickory_resolver::proto::iocompat::AsyncIoTokioAsStd;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::time::Instant;
 
 /// Result of a DNS resolution operation
 #[derive(Debug, Clone)]
-#[allow(dead_code)] // Used in later phases
 pub struct DnsResult {
     /// Display name from config
     pub name: String,
@@ -19,7 +26,6 @@ pub struct DnsResult {
     pub error: Option<String>,
 }
 
-#[allow(dead_code)] // Used in later phases
 impl DnsResult {
     /// Create a successful DNS result
     pub fn success(name: String, hostname: String, addresses: Vec<IpAddr>) -> Self {
@@ -63,42 +69,197 @@ impl DnsResult {
     }
 }
 
+/// DNS resolver whose sockets honour a source binding
+pub type DnsResolver = AsyncResolver<GenericConnector<BoundRuntime>>;
+
+/// Tokio runtime for hickory that binds its query sockets like the other probes
+///
+/// hickory only binds query sockets to a local address (and only UDP ones),
+/// so the interface binding (SO_BINDTODEVICE) and the TCP fallback's source
+/// address are applied here.
+#[derive(Clone, Default)]
+pub struct BoundRuntime {
+    tokio: TokioRuntimeProvider,
+    source: SourceBinding,
+}
+
+impl RuntimeProvider for BoundRuntime {
+    type Handle = <TokioRuntimeProvider as RuntimeProvider>::Handle;
+    type Timer = <TokioRuntimeProvider as RuntimeProvider>::Timer;
+    type Udp = <TokioRuntimeProvider as RuntimeProvider>::Udp;
+    type Tcp = <TokioRuntimeProvider as RuntimeProvider>::Tcp;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.tokio.create_handle()
+    }
+
+    fn connect_tcp(&self, server_addr: SocketAddr) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        if self.source.is_default() {
+            return self.tokio.connect_tcp(server_addr);
+        }
+        let source = self.source.clone();
+        Box::pin(async move {
+            let socket = if server_addr.is_ipv6() {
+                tokio::net::TcpSocket::new_v6()?
+            } else {
+                tokio::net::TcpSocket::new_v4()?
+            };
+            bind_socket(&source, &socket, server_addr.is_ipv6())?;
+            Ok(AsyncIoTokioAsStd(socket.connect(server_addr).await?))
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        if self.source.interface.is_none() {
+            return self.tokio.bind_udp(local_addr, server_addr);
+        }
+        // hickory picks the local port, so only the interface is bound here
+        let interface = SourceBinding {
+            interface: self.source.interface.clone(),
+            address: None,
+        };
+        Box::pin(async move {
+            let socket = socket2::Socket::new(socket2::Domain::for_address(local_addr), socket2::Type::DGRAM, None)?;
+            bind_socket(&interface, &socket, local_addr.is_ipv6())?;
+            socket.set_nonblocking(true)?;
+            socket.bind(&local_addr.into())?;
+            tokio::net::UdpSocket::from_std(socket.into())
+        })
+    }
+}
+
+/// Apply a source binding to a resolver socket
+#[cfg(target_os = "linux")]
+fn bind_socket(source: &SourceBinding, socket: &impl std::os::fd::AsRawFd, v6: bool) -> io::Result<()> {
+    source
+        .bind_fd(socket.as_raw_fd(), v6)
+        .map_err(|e| io::Error::other(format!("{:#}", e)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_socket<S>(source: &SourceBinding, _socket: &S, _v6: bool) -> io::Result<()> {
+    Err(io::Error::other(format!(
+        "binding DNS queries {} is only supported on Linux",
+        source.describe()
+    )))
+}
+
 /// Create a new DNS resolver using system configuration
-#[allow(dead_code)] // Used in later phases
-pub fn create_resolver() -> TokioAsyncResolver {
-    TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+pub fn create_resolver() -> DnsResolver {
+    resolver(ResolverConfig::default(), SourceBinding::default())
+}
+
+fn resolver(config: ResolverConfig, source: SourceBinding) -> DnsResolver {
+    let runtime = BoundRuntime {
+        tokio: TokioRuntimeProvider::default(),
+        source,
+    };
+    DnsResolver::new(config, ResolverOpts::default(), GenericConnector::new(runtime))
+}
+
+/// Create a DNS resolver whose queries leave from the given source binding
+///
+/// Name servers of a family the binding cannot reach are dropped.
+pub fn create_resolver_with(source: &SourceBinding) -> Result<DnsResolver> {
+    create_resolver_for(source, &[])
+}
+
+/// Create a DNS resolver querying `nameservers` (the default servers if empty)
+/// from the given source binding
+pub fn create_resolver_for(source: &SourceBinding, nameservers: &[IpAddr]) -> Result<DnsResolver> {
+    let base = if nameservers.is_empty() {
+        ResolverConfig::default()
+    } else {
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(nameservers, 53, true),
+        )
+    };
+    if source.is_default() {
+        return Ok(resolver(base, SourceBinding::default()));
+    }
+
+    let mut name_servers: Vec<NameServerConfig> = Vec::new();
+    for server in base.name_servers() {
+        let v6 = server.socket_addr.is_ipv6();
+        let local = match source.local_address(v6) {
+            Ok(Some(local)) => local,
+            Ok(None) | Err(_) => continue,
+        };
+        let mut server = server.clone();
+        server.bind_addr = Some(SocketAddr::new(local, 0));
+        name_servers.push(server);
+    }
+    if name_servers.is_empty() {
+        eyre::bail!("no name server reachable {}", source.describe());
+    }
+
+    let config = ResolverConfig::from_parts(base.domain().cloned(), base.search().to_vec(), name_servers);
+    Ok(resolver(config, source.clone()))
 }
 
 /// Resolve DNS for a hostname
 ///
 /// Performs A and optionally AAAA lookups for the given hostname.
-#[allow(dead_code)] // Used in later phases
-pub async fn resolve_dns(resolver: &TokioAsyncResolver, name: &str, hostname: &str, include_ipv6: bool) -> DnsResult {
+pub async fn resolve_dns(resolver: &DnsResolver, name: &str, hostname: &str, include_ipv6: bool) -> DnsResult {
+    resolve_dns_expiring(resolver, name, hostname, include_ipv6).await.0
+}
+
+/// Resolve a hostname like [`resolve_dns`], also returning when the answer's TTL runs out
+pub async fn resolve_dns_expiring(
+    resolver: &DnsResolver,
+    name: &str,
+    hostname: &str,
+    include_ipv6: bool,
+) -> (DnsResult, Option<Instant>) {
     let mut addresses = Vec::new();
 
     // Try IPv4 lookup
-    match resolver.lookup_ip(hostname).await {
+    let valid_until = match resolver.lookup_ip(hostname).await {
         Ok(lookup) => {
             for ip in lookup.iter() {
                 if ip.is_ipv4() || include_ipv6 {
                     addresses.push(ip);
                 }
             }
+            lookup.valid_until()
         }
         Err(e) => {
-            return DnsResult::failure(name.to_string(), hostname.to_string(), format_dns_error(&e));
+            return (
+                DnsResult::failure(name.to_string(), hostname.to_string(), format_dns_error(&e)),
+                None,
+            );
         }
-    }
+    };
 
     if addresses.is_empty() {
-        DnsResult::failure(name.to_string(), hostname.to_string(), "no addresses found".to_string())
+        (
+            DnsResult::failure(name.to_string(), hostname.to_string(), "no addresses found".to_string()),
+            None,
+        )
     } else {
-        DnsResult::success(name.to_string(), hostname.to_string(), addresses)
+        (
+            DnsResult::success(name.to_string(), hostname.to_string(), addresses),
+            Some(valid_until),
+        )
     }
 }
 
+/// Reverse-resolve an IP address to its PTR name (without the trailing dot)
+pub async fn reverse_lookup(resolver: &DnsResolver, address: IpAddr) -> Option<String> {
+    let lookup = resolver.reverse_lookup(address).await.ok()?;
+    lookup
+        .iter()
+        .next()
+        .map(|name| name.to_string().trim_end_matches('.').to_string())
+}
+
 /// Format a DNS error into a user-friendly message
-#[allow(dead_code)] // Used in later phases
 fn format_dns_error(error: &hickory_resolver::error::ResolveError) -> String {
     use hickory_resolver::error::ResolveErrorKind;
 
@@ -111,7 +272,6 @@ fn format_dns_error(error: &hickory_resolver::error::ResolveError) -> String {
 }
 
 /// Detailed DNS result for the `cxn dns` subcommand
-#[allow(dead_code)] // Used in later phases
 pub struct DetailedDnsResult {
     pub hostname: String,
     pub ipv4_addresses: Vec<IpAddr>,
@@ -119,7 +279,6 @@ pub struct DetailedDnsResult {
     pub error: Option<String>,
 }
 
-#[allow(dead_code)] // Used in later phases
 impl DetailedDnsResult {
     /// Format detailed output for the dns subcommand
     pub fn format(&self) -> String {
@@ -161,12 +320,7 @@ impl DetailedDnsResult {
 }
 
 /// Run detailed DNS resolution for the dns subcommand
-#[allow(dead_code)] // Used in later phases
-pub async fn resolve_dns_detailed(
-    resolver: &TokioAsyncResolver,
-    hostname: &str,
-    include_ipv6: bool,
-) -> DetailedDnsResult {
+pub async fn resolve_dns_detailed(resolver: &DnsResolver, hostname: &str, include_ipv6: bool) -> DetailedDnsResult {
     let mut ipv4_addresses = Vec::new();
     let mut ipv6_addresses = Vec::new();
 
@@ -253,4 +407,52 @@ mod tests {
         assert!(output.contains("bad.invalid"));
         assert!(output.contains("no such host"));
     }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_bound_runtime_binds_query_sockets_to_the_interface() {
+        use std::os::fd::AsRawFd;
+        let device = |fd: std::os::fd::RawFd| {
+            let mut name = [0u8; libc::IFNAMSIZ];
+            let mut len = name.len() as libc::socklen_t;
+            // SAFETY: fd is a live socket and the buffer and length outlive the call
+            let rc = unsafe {
+                libc::getsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_BINDTODEVICE,
+                    name.as_mut_ptr().cast(),
+                    &mut len,
+                )
+            };
+            assert_eq!(rc, 0);
+            String::from_utf8_lossy(&name[..len as usize])
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        let runtime = |interface: &str| BoundRuntime {
+            tokio: TokioRuntimeProvider::default(),
+            source: SourceBinding {
+                interface: Some(interface.to_string()),
+                address: None,
+            },
+        };
+        let server: SocketAddr = "127.0.0.1:53".parse().unwrap();
+
+        let udp = runtime("lo")
+            .bind_udp("127.0.0.1:0".parse().unwrap(), server)
+            .await
+            .unwrap();
+        assert_eq!(device(udp.as_raw_fd()), "lo");
+        assert!(
+            runtime("does-not-exist0")
+                .bind_udp("0.0.0.0:0".parse().unwrap(), server)
+                .await
+                .is_err()
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp = runtime("lo").connect_tcp(listener.local_addr().unwrap()).await.unwrap();
+        assert_eq!(device(tcp.0.as_raw_fd()), "lo");
+    }
 }
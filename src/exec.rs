@@ -0,0 +1,516 @@
+//! External command checks, following the Nagios plugin conventions
+//!
+//! A command's exit code is its status: 0 OK, 1 WARNING, 2 CRITICAL and 3 (or
+//! anything else) UNKNOWN. The first line of its output is the summary;
+//! performance data after a `|` becomes the check's metrics, so thresholds
+//! can judge it like any built-in check.
+
+use crate::check::CheckContext;
+use crate::interpolate;
+use crate::probe::{BoxFuture, Check, CheckOutcome, Status};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Time a command may run unless configured otherwise, as in Nagios
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command run as a host's check, e.g. a Nagios plugin
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExecCommand {
+    /// Name for the check; its metrics are then `<name>.<label>`, so thresholds can tell commands apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Shell command line; CXN_HOST and CXN_ADDRESS hold the host's name and address
+    pub command: String,
+    /// Milliseconds before the command is killed and the check fails (default 10000)
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(range(min = 1))]
+    pub timeout: Option<u64>,
+}
+
+impl ExecCommand {
+    /// Reject commands that can't run
+    pub fn validate(&self) -> eyre::Result<()> {
+        if let Some(name) = &self.name
+            && (name.is_empty() || name.contains('.'))
+        {
+            eyre::bail!("exec name '{}' must be non-empty and without dots", name);
+        }
+        if self.command.trim().is_empty() {
+            eyre::bail!("exec command must not be empty");
+        }
+        if self.timeout == Some(0) {
+            eyre::bail!("exec timeout must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// One value from a plugin's performance data, e.g. `rta=12.3ms;100;500;0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerfData {
+    pub label: String,
+    pub value: f64,
+    /// Unit of measure: `s`, `ms`, `%`, `B`, `c` or empty
+    pub unit: String,
+    /// Warning and critical ranges as the plugin wrote them
+    pub warn: Option<String>,
+    pub crit: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// A plugin's output split into summary, further lines and performance data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginOutput {
+    /// First line, before any `|`
+    pub text: String,
+    /// Lines after the first, without performance data
+    pub long_text: Vec<String>,
+    pub perfdata: Vec<PerfData>,
+}
+
+/// Split plugin output per the Nagios format
+///
+/// Performance data follows a `|` on the first line, and on a later line all
+/// lines after that one are performance data too.
+pub fn parse_output(output: &str) -> PluginOutput {
+    let mut lines = output.lines();
+    let (text, mut perf) = match lines.next() {
+        Some(first) => split_perf(first),
+        None => (String::new(), String::new()),
+    };
+
+    let mut long_text = Vec::new();
+    let mut in_perf = false;
+    for line in lines {
+        if in_perf {
+            perf.push(' ');
+            perf.push_str(line);
+            continue;
+        }
+        let (line_text, line_perf) = split_perf(line);
+        if line.contains('|') {
+            in_perf = true;
+            perf.push(' ');
+            perf.push_str(&line_perf);
+        }
+        if !line_text.is_empty() {
+            long_text.push(line_text);
+        }
+    }
+
+    PluginOutput {
+        text,
+        long_text,
+        perfdata: parse_perfdata(&perf),
+    }
+}
+
+/// Text before the first `|` and the performance data after it, both trimmed
+fn split_perf(line: &str) -> (String, String) {
+    match line.split_once('|') {
+        Some((text, perf)) => (text.trim().to_string(), perf.trim().to_string()),
+        None => (line.trim().to_string(), String::new()),
+    }
+}
+
+/// Parse space-separated `'label'=value[unit];[warn];[crit];[min];[max]` items,
+/// skipping malformed ones and values the plugin couldn't determine (`U`)
+pub fn parse_perfdata(perf: &str) -> Vec<PerfData> {
+    let mut items = Vec::new();
+    let mut rest = perf.trim_start();
+    while !rest.is_empty() {
+        // Labels may be quoted to hold spaces, with '' for a literal quote
+        let label_end = if let Some(quoted) = rest.strip_prefix('\'') {
+            let mut end = None;
+            let mut chars = quoted.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                if c == '\'' {
+                    if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                        chars.next();
+                        continue;
+                    }
+                    end = Some(i + 2);
+                    break;
+                }
+            }
+            end
+        } else {
+            rest.find('=')
+        };
+        let item_end = label_end
+            .and_then(|start| rest[start..].find(char::is_whitespace).map(|i| start + i))
+            .unwrap_or(rest.len());
+        if let Some(item) = parse_item(&rest[..item_end]) {
+            items.push(item);
+        }
+        rest = rest[item_end..].trim_start();
+    }
+    items
+}
+
+/// One `label=value[unit];warn;crit;min;max` item
+fn parse_item(item: &str) -> Option<PerfData> {
+    let (label, data) = item.rsplit_once('=')?;
+    let label = match label.strip_prefix('\'').and_then(|l| l.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => label.to_string(),
+    };
+    let mut fields = data.split(';');
+    let value = fields.next()?;
+    let number_end = value
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(value.len());
+    let field = |f: Option<&str>| f.filter(|f| !f.is_empty()).map(str::to_string);
+    let warn = field(fields.next());
+    let crit = field(fields.next());
+    let min = field(fields.next()).and_then(|f| f.parse().ok());
+    let max = field(fields.next()).and_then(|f| f.parse().ok());
+    Some(PerfData {
+        label: label.trim().to_string(),
+        value: value[..number_end].parse().ok()?,
+        unit: value[number_end..].to_string(),
+        warn,
+        crit,
+        min,
+        max,
+    })
+}
+
+/// Status and Nagios state name for a plugin's exit code (`None` if killed by a signal)
+pub fn status_for(code: Option<i32>) -> (Status, &'static str) {
+    match code {
+        Some(0) => (Status::Ok, "ok"),
+        Some(1) => (Status::Warning, "warning"),
+        Some(2) => (Status::Failed, "critical"),
+        _ => (Status::Unknown, "unknown"),
+    }
+}
+
+/// Runs a command and reports its exit code and output
+pub struct ExecCheck {
+    pub command: ExecCommand,
+}
+
+impl ExecCheck {
+    /// The outcome for a command that ran to completion
+    fn outcome(&self, code: Option<i32>, stdout: &str, stderr: &str) -> CheckOutcome {
+        let (status, state) = status_for(code);
+        let mut output = parse_output(stdout);
+        if output.text.is_empty() {
+            // Like Nagios, say so rather than show nothing; stderr often explains a crash
+            output.text = stderr.lines().next().unwrap_or("(no output)").trim().to_string();
+        }
+        let perfdata: Vec<serde_json::Value> = output
+            .perfdata
+            .iter()
+            .map(|p| {
+                json!({
+                    "label": p.label,
+                    "value": p.value,
+                    "unit": p.unit,
+                    "warn": p.warn,
+                    "crit": p.crit,
+                    "min": p.min,
+                    "max": p.max,
+                })
+            })
+            .collect();
+        let details = json!({
+            "name": self.command.name,
+            "command": interpolate::redact(&self.command.command),
+            "exit_code": code,
+            "long_output": output.long_text,
+            "perfdata": perfdata,
+        });
+        // Like ping's round-trip time, a passing plugin's first value says the most
+        let brief = match output.perfdata.first() {
+            Some(p) if status == Status::Ok => format!("{}{}", p.value, p.unit),
+            _ => state.to_string(),
+        };
+        let mut outcome = CheckOutcome::new("exec", status, self.named(output.text))
+            .brief(brief)
+            .details(details);
+        for p in &output.perfdata {
+            let metric = match &self.command.name {
+                Some(name) => format!("{}.{}", name, p.label),
+                None => p.label.clone(),
+            };
+            outcome = outcome.metric(metric, p.value);
+        }
+        outcome
+    }
+
+    /// The outcome for a command that couldn't be run or didn't finish
+    fn error(&self, status: Status, brief: &str, error: String) -> CheckOutcome {
+        let details = json!({
+            "name": self.command.name,
+            "command": interpolate::redact(&self.command.command),
+            "error": error,
+        });
+        CheckOutcome::new("exec", status, self.named(error))
+            .brief(brief)
+            .details(details)
+    }
+
+    /// Summary text, led by the command's name if it has one
+    fn named(&self, text: String) -> String {
+        match &self.command.name {
+            Some(name) => format!("{}: {}", name, text),
+            None => text,
+        }
+    }
+}
+
+impl Check for ExecCheck {
+    fn name(&self) -> &str {
+        "exec"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            let host = ctx.host();
+            let timeout = self
+                .command
+                .timeout
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_TIMEOUT);
+            let child = Command::new("sh")
+                .arg("-c")
+                .arg(&self.command.command)
+                .env("CXN_HOST", &host.name)
+                .env("CXN_ADDRESS", &host.address)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                // Its own process group, so a timeout kills what the shell started too
+                .process_group(0)
+                .kill_on_drop(true)
+                .spawn();
+            let child = match child {
+                Ok(child) => child,
+                Err(e) => return self.error(Status::Unknown, "unknown", format!("could not run command: {}", e)),
+            };
+            let group = child.id();
+            let finished = tokio::time::timeout(timeout, child.wait_with_output()).await;
+            if finished.is_err()
+                && let Some(group) = group
+            {
+                // SAFETY: killpg only sends a signal; the group is the one the command leads
+                unsafe { libc::killpg(group as libc::pid_t, libc::SIGKILL) };
+            }
+            match finished {
+                Ok(Ok(output)) => self.outcome(
+                    output.status.code(),
+                    &String::from_utf8_lossy(&output.stdout),
+                    &String::from_utf8_lossy(&output.stderr),
+                ),
+                Ok(Err(e)) => self.error(Status::Unknown, "unknown", format!("command failed: {}", e)),
+                Err(_) => self.error(
+                    Status::Failed,
+                    "timeout",
+                    format!("timed out after {}ms", timeout.as_millis()),
+                ),
+            }
+        })
+    }
+
+    /// Commands may have side effects, and plugins do their own retrying
+    fn retryable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::Checker;
+    use crate::config::HostConfig;
+    use crate::fake::{FakePinger, FakeResolver};
+    use crate::probe::Threshold;
+    use std::sync::Arc;
+
+    /// Run a host's exec checks with no network behind them
+    async fn run(exec: Vec<ExecCommand>, thresholds: &[(&str, Threshold)]) -> Vec<CheckOutcome> {
+        let checker = Checker::builder()
+            .pinger(Arc::new(FakePinger::new()))
+            .resolver(Arc::new(FakeResolver::new()))
+            .build()
+            .unwrap();
+        let host = HostConfig {
+            name: "Router".to_string(),
+            address: "10.0.0.1".to_string(),
+            exec,
+            thresholds: thresholds.iter().map(|(key, t)| (key.to_string(), t.clone())).collect(),
+            timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        checker.check_host(&host).await.outcomes
+    }
+
+    fn command(command: &str) -> ExecCommand {
+        ExecCommand {
+            name: None,
+            command: command.to_string(),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_output_single_line() {
+        let output = parse_output("PING OK - rta 0.80ms | rta=0.8ms;100;500;0 pl=0%;20;60\n");
+        assert_eq!(output.text, "PING OK - rta 0.80ms");
+        assert_eq!(output.perfdata[0].label, "rta");
+        assert_eq!(output.perfdata[1].value, 0.0);
+    }
+
+    #[test]
+    fn test_parse_output_with_long_text_and_perfdata() {
+        let output = parse_output(
+            "DISK OK - free space: / 3326 MB (56%); | /=2643MB;5948;5958;0;5968\n\
+             / 15272 MB (77%);\n\
+             /boot 68 MB (69%); | /boot=68MB;88;93;0;98\n\
+             /home=69357MB;253404;253409;0;253414\n",
+        );
+        assert_eq!(output.text, "DISK OK - free space: / 3326 MB (56%);");
+        assert_eq!(output.long_text, ["/ 15272 MB (77%);", "/boot 68 MB (69%);"]);
+        let labels: Vec<&str> = output.perfdata.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels, ["/", "/boot", "/home"]);
+        assert_eq!(
+            output.perfdata[0],
+            PerfData {
+                label: "/".to_string(),
+                value: 2643.0,
+                unit: "MB".to_string(),
+                warn: Some("5948".to_string()),
+                crit: Some("5958".to_string()),
+                min: Some(0.0),
+                max: Some(5968.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_perfdata_quoted_labels_and_undetermined_values() {
+        let perf = parse_perfdata("'tunnel ''a'' rtt'=12.5ms;;;0 time=0.01s loss=U%;20;60 junk");
+        assert_eq!(perf.len(), 2);
+        assert_eq!(perf[0].label, "tunnel 'a' rtt");
+        assert_eq!((perf[0].value, perf[0].unit.as_str()), (12.5, "ms"));
+        assert_eq!((perf[0].warn.as_deref(), perf[0].min), (None, Some(0.0)));
+        assert_eq!((perf[1].label.as_str(), perf[1].value), ("time", 0.01));
+    }
+
+    #[test]
+    fn test_exit_codes_follow_nagios() {
+        assert_eq!(status_for(Some(0)), (Status::Ok, "ok"));
+        assert_eq!(status_for(Some(1)), (Status::Warning, "warning"));
+        assert_eq!(status_for(Some(2)), (Status::Failed, "critical"));
+        assert_eq!(status_for(Some(3)), (Status::Unknown, "unknown"));
+        assert_eq!(status_for(Some(127)).0, Status::Unknown);
+        assert_eq!(status_for(None).0, Status::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_exec_status_summary_and_metrics() {
+        let outcomes = run(
+            vec![
+                command(r#"echo "OK - $CXN_HOST at $CXN_ADDRESS | rtt=12ms;50;100;0""#),
+                command(r#"echo "LOAD WARNING - 4.2 | load1=4.2;4;8"; exit 1"#),
+                command("echo 'DISK CRITICAL - / full'; exit 2"),
+                command("echo 'UNKNOWN - no such sensor'; exit 3"),
+                command("echo oops >&2; exit 42"),
+            ],
+            &[],
+        )
+        .await;
+        let statuses: Vec<Status> = outcomes.iter().map(|o| o.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                Status::Ok,
+                Status::Warning,
+                Status::Failed,
+                Status::Unknown,
+                Status::Unknown
+            ]
+        );
+
+        assert_eq!(outcomes[0].summary, "OK - Router at 10.0.0.1");
+        assert_eq!(outcomes[0].brief, "12ms");
+        assert_eq!(outcomes[0].metric_value("rtt"), Some(12.0));
+        assert_eq!(outcomes[0].details["perfdata"][0]["crit"], "100");
+        assert_eq!(outcomes[1].brief, "warning");
+        assert_eq!(outcomes[1].metric_value("load1"), Some(4.2));
+        assert_eq!(outcomes[2].brief, "critical");
+        assert_eq!(outcomes[4].summary, "oops");
+        assert_eq!(outcomes[4].details["exit_code"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_exec_metrics_meet_thresholds() {
+        let threshold = Threshold {
+            warn: Some(10.0),
+            fail: None,
+        };
+        let outcomes = run(
+            vec![command("echo 'OK | rtt=12ms'")],
+            &[("exec.rtt", threshold.clone())],
+        )
+        .await;
+        assert_eq!(outcomes[0].status, Status::Warning);
+        assert_eq!(outcomes[0].summary, "OK (rtt 12 > 10)");
+
+        // Named commands have thresholds of their own
+        let named = |name: &str| ExecCommand {
+            name: Some(name.to_string()),
+            ..command("echo 'OK | rtt=12ms'")
+        };
+        let outcomes = run(vec![named("vpn"), named("dns")], &[("exec.vpn.rtt", threshold)]).await;
+        assert_eq!(outcomes[0].status, Status::Warning);
+        assert_eq!(outcomes[0].summary, "vpn: OK (vpn.rtt 12 > 10)");
+        assert_eq!(outcomes[1].status, Status::Ok);
+        assert_eq!(outcomes[1].metric_value("dns.rtt"), Some(12.0));
+    }
+
+    /// Check if a process has exited (a zombie awaiting its reaper counts as gone)
+    fn exited(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid)).map_or(true, |stat| {
+            stat.rsplit_once(") ").is_some_and(|(_, rest)| rest.starts_with('Z'))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_exec_timeout_kills_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("pid");
+        let slow = ExecCommand {
+            name: None,
+            command: format!("sleep 5 & echo $! > {}; wait; echo done", pidfile.display()),
+            timeout: Some(100),
+        };
+        let outcomes = run(vec![slow], &[]).await;
+        assert_eq!(outcomes[0].status, Status::Failed);
+        assert_eq!(outcomes[0].summary, "timed out after 100ms");
+
+        // The shell's own children are killed with it
+        let pid = std::fs::read_to_string(&pidfile).unwrap();
+        let pid = pid.trim();
+        for _ in 0..50 {
+            if exited(pid) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("sleep {} outlived the timed out command", pid);
+    }
+}
@@ -0,0 +1,237 @@
+//! Scriptable stand-ins for the network, for deterministic tests
+//!
+//! [`FakePinger`] and [`FakeResolver`] answer from a script instead of the
+//! network: replies with a given latency, lost packets, NXDOMAIN and timeouts.
+//! Latency and timeouts are tokio sleeps, so under a paused clock a test runs
+//! instantly and the same way every time. Both record what they were asked.
+//!
+//! ```
+//! use std::net::IpAddr;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use cxn::fake::{Answer, Echo, FakePinger, FakeResolver};
+//! use cxn::{Checker, HostConfig};
+//!
+//! # let runtime = tokio::runtime::Builder::new_current_thread().enable_time().start_paused(true).build().unwrap();
+//! # runtime.block_on(async {
+//! let ip: IpAddr = "10.0.0.7".parse().unwrap();
+//! let pinger = Arc::new(FakePinger::new());
+//! pinger.reply(ip, Echo::Reply(Duration::from_millis(12)));
+//! let resolver = Arc::new(FakeResolver::new());
+//! resolver.answer("wiki.internal", Answer::addresses(vec![ip], Duration::from_secs(60)));
+//!
+//! let checker = Checker::builder().pinger(pinger.clone()).resolver(resolver).build().unwrap();
+//! let wiki = HostConfig {
+//!     name: "Wiki".to_string(),
+//!     address: "wiki.internal".to_string(),
+//!     ping: true,
+//!     timeout: Duration::from_secs(1),
+//!     ..Default::default()
+//! };
+//! let result = checker.check_host(&wiki).await;
+//! assert_eq!(result.status(), "ok");
+//! assert_eq!(pinger.pinged("Wiki"), vec![ip]);
+//! # });
+//! ```
+
+use crate::config::HostConfig;
+use crate::dns::DnsResult;
+use crate::network::{Pinger, Resolver};
+use crate::ping::PingResult;
+use crate::probe::BoxFuture;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How a [`FakePinger`] answers one ping check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Echo {
+    /// A reply after this round-trip time (lost if longer than the host's timeout)
+    Reply(Duration),
+    /// No reply; the check fails once the host's timeout passes
+    Lost,
+    /// Fails right away with this error, e.g. "network unreachable"
+    Error(String),
+}
+
+/// How a [`FakeResolver`] answers one lookup
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    /// These addresses, valid for `ttl`, after `latency`
+    Addresses {
+        addresses: Vec<IpAddr>,
+        ttl: Duration,
+        latency: Duration,
+    },
+    /// The name doesn't exist
+    NxDomain,
+    /// No answer; the lookup fails once the host's timeout passes
+    Timeout,
+}
+
+impl Answer {
+    /// These addresses, valid for `ttl`, answered right away
+    pub fn addresses(addresses: Vec<IpAddr>, ttl: Duration) -> Self {
+        Answer::Addresses {
+            addresses,
+            ttl,
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+/// Replies given in turn, the last one repeating
+#[derive(Debug)]
+struct Script<T>(VecDeque<T>);
+
+impl<T: Clone> Script<T> {
+    fn new(replies: impl IntoIterator<Item = T>) -> Self {
+        Self(replies.into_iter().collect())
+    }
+
+    fn next(&mut self) -> Option<T> {
+        match self.0.len() {
+            0 | 1 => self.0.front().cloned(),
+            _ => self.0.pop_front(),
+        }
+    }
+}
+
+/// A [`Pinger`] answering from per-address scripts; unscripted addresses never reply
+#[derive(Debug, Default)]
+pub struct FakePinger {
+    scripts: Mutex<HashMap<IpAddr, Script<Echo>>>,
+    /// Host name and address of every ping check, in order
+    pings: Mutex<Vec<(String, IpAddr)>>,
+}
+
+impl FakePinger {
+    /// A pinger with nothing scripted yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every ping to `ip` the same way from now on
+    pub fn reply(&self, ip: IpAddr, echo: Echo) -> &Self {
+        self.script(ip, [echo])
+    }
+
+    /// Answer pings to `ip` with these in turn, repeating the last one
+    pub fn script(&self, ip: IpAddr, echoes: impl IntoIterator<Item = Echo>) -> &Self {
+        self.scripts.lock().unwrap().insert(ip, Script::new(echoes));
+        self
+    }
+
+    /// Host names and addresses pinged so far, in order
+    pub fn pings(&self) -> Vec<(String, IpAddr)> {
+        self.pings.lock().unwrap().clone()
+    }
+
+    /// Addresses the named host was pinged at, in order
+    pub fn pinged(&self, host: &str) -> Vec<IpAddr> {
+        self.pings()
+            .into_iter()
+            .filter(|(name, _)| name == host)
+            .map(|(_, ip)| ip)
+            .collect()
+    }
+}
+
+impl Pinger for FakePinger {
+    fn ping<'a>(&'a self, host: &'a HostConfig, ip: IpAddr) -> BoxFuture<'a, PingResult> {
+        self.pings.lock().unwrap().push((host.name.clone(), ip));
+        let echo = self
+            .scripts
+            .lock()
+            .unwrap()
+            .get_mut(&ip)
+            .and_then(Script::next)
+            .unwrap_or(Echo::Lost);
+        Box::pin(async move {
+            match echo {
+                Echo::Reply(rtt) if rtt <= host.timeout => {
+                    tokio::time::sleep(rtt).await;
+                    PingResult::success(host.name.clone(), ip, rtt)
+                }
+                Echo::Reply(_) | Echo::Lost => {
+                    tokio::time::sleep(host.timeout).await;
+                    let error = format!("timeout after {}ms", host.timeout.as_millis());
+                    PingResult::failure(host.name.clone(), ip, error)
+                }
+                Echo::Error(error) => PingResult::failure(host.name.clone(), ip, error),
+            }
+        })
+    }
+}
+
+/// A [`Resolver`] answering from per-name scripts; unscripted names don't exist
+#[derive(Debug, Default)]
+pub struct FakeResolver {
+    scripts: Mutex<HashMap<String, Script<Answer>>>,
+    /// Every name looked up, in order
+    lookups: Mutex<Vec<String>>,
+}
+
+impl FakeResolver {
+    /// A resolver with nothing scripted yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every lookup of `name` the same way from now on
+    pub fn answer(&self, name: &str, answer: Answer) -> &Self {
+        self.script(name, [answer])
+    }
+
+    /// Answer lookups of `name` with these in turn, repeating the last one
+    pub fn script(&self, name: &str, answers: impl IntoIterator<Item = Answer>) -> &Self {
+        self.scripts
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Script::new(answers));
+        self
+    }
+
+    /// Names looked up so far, in order
+    pub fn lookups(&self) -> Vec<String> {
+        self.lookups.lock().unwrap().clone()
+    }
+}
+
+impl Resolver for FakeResolver {
+    fn resolve<'a>(&'a self, host: &'a HostConfig, include_ipv6: bool) -> BoxFuture<'a, (DnsResult, Option<Instant>)> {
+        self.lookups.lock().unwrap().push(host.address.clone());
+        let answer = self
+            .scripts
+            .lock()
+            .unwrap()
+            .get_mut(&host.address)
+            .and_then(Script::next);
+        let failure = |error: &str| DnsResult::failure(host.name.clone(), host.address.clone(), error.to_string());
+        Box::pin(async move {
+            match answer.unwrap_or(Answer::NxDomain) {
+                Answer::Addresses {
+                    addresses,
+                    ttl,
+                    latency,
+                } => {
+                    tokio::time::sleep(latency).await;
+                    let addresses: Vec<IpAddr> =
+                        addresses.into_iter().filter(|a| a.is_ipv4() || include_ipv6).collect();
+                    if addresses.is_empty() {
+                        return (failure("no addresses found"), None);
+                    }
+                    let result = DnsResult::success(host.name.clone(), host.address.clone(), addresses);
+                    (result, Some(Instant::now() + ttl))
+                }
+                Answer::NxDomain => (failure("no such host"), None),
+                Answer::Timeout => {
+                    tokio::time::sleep(host.timeout).await;
+                    (failure("timeout"), None)
+                }
+            }
+        })
+    }
+}
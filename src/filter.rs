@@ -0,0 +1,108 @@
+/// Command-line selection of which configured hosts to check
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostFilter {
+    /// Keep hosts carrying any of these tags (all hosts if empty)
+    pub tags: Vec<String>,
+    /// Drop hosts carrying any of these tags
+    pub exclude_tags: Vec<String>,
+    /// Keep hosts whose name matches any of these glob patterns (all hosts if empty)
+    pub hosts: Vec<String>,
+}
+
+impl HostFilter {
+    /// Check if no filtering is requested
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.exclude_tags.is_empty() && self.hosts.is_empty()
+    }
+
+    /// Decide whether a host with this name and these tags is selected
+    pub fn matches(&self, name: &str, tags: &[String]) -> bool {
+        let has_tag = |wanted: &String| tags.iter().any(|t| t == wanted);
+
+        if !self.tags.is_empty() && !self.tags.iter().any(has_tag) {
+            return false;
+        }
+        if self.exclude_tags.iter().any(has_tag) {
+            return false;
+        }
+        self.hosts.is_empty() || self.hosts.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// Match a name against a shell-style glob (`*` any run, `?` any one character)
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Greedy match with backtracking to the most recent `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("GitHub*", "GitHub"));
+        assert!(glob_match("GitHub*", "GitHub API"));
+        assert!(glob_match("*DNS", "Google DNS"));
+        assert!(glob_match("db-?", "db-1"));
+        assert!(glob_match("*a*b*", "xaybz"));
+        assert!(!glob_match("GitHub*", "Gitlab"));
+        assert!(!glob_match("db-?", "db-10"));
+    }
+
+    #[test]
+    fn test_host_filter_tags() {
+        let tags = vec!["db".to_string(), "flaky".to_string()];
+        let filter = HostFilter {
+            tags: vec!["db".to_string()],
+            ..Default::default()
+        };
+        assert!(filter.matches("Primary", &tags));
+        assert!(!filter.matches("Web", &["web".to_string()]));
+
+        let filter = HostFilter {
+            tags: vec!["db".to_string()],
+            exclude_tags: vec!["flaky".to_string()],
+            ..Default::default()
+        };
+        assert!(!filter.matches("Primary", &tags));
+        assert!(filter.matches("Replica", &["db".to_string()]));
+    }
+
+    #[test]
+    fn test_host_filter_names() {
+        let filter = HostFilter {
+            hosts: vec!["GitHub*".to_string(), "Google DNS".to_string()],
+            ..Default::default()
+        };
+        assert!(filter.matches("GitHub API", &[]));
+        assert!(filter.matches("Google DNS", &[]));
+        assert!(!filter.matches("Cloudflare DNS", &[]));
+        assert!(HostFilter::default().matches("anything", &[]));
+    }
+}
@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+use eyre::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use std::fs;
+use std::path::Path;
+
+/// File extensions recognised as config files, in search order
+pub const CONFIG_EXTENSIONS: &[&str] = &["yml", "yaml", "toml", "json"];
+
+/// Syntax a config file is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format implied by a file's extension
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yml" | "yaml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Format of a file: its extension if known, else a guess from the content
+    pub fn detect(path: &Path, content: &str) -> Self {
+        Self::from_extension(path).unwrap_or_else(|| Self::sniff(content))
+    }
+
+    /// Guess the format of a file from its first meaningful line
+    ///
+    /// JSON starts with `{`; TOML with a `[table]` header or a `key = value`
+    /// line. Anything else is read as YAML.
+    fn sniff(content: &str) -> Self {
+        let first = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        if first.starts_with('{') {
+            ConfigFormat::Json
+        } else if first.starts_with('[') && first.ends_with(']') {
+            ConfigFormat::Toml
+        } else if let Some((key, _)) = first.split_once('=')
+            && !key.contains(':')
+        {
+            ConfigFormat::Toml
+        } else {
+            ConfigFormat::Yaml
+        }
+    }
+
+    /// Parse text in this format; errors carry the parser's line and column
+    pub fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+        })
+    }
+
+    /// Write a document in this format, keeping its key order
+    pub fn to_string(self, document: &Value) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(document)?,
+            // TOML has no null; an empty `hosts:` simply disappears
+            ConfigFormat::Toml => toml::to_string(&without_nulls(document.clone()))?,
+            ConfigFormat::Json => serde_json::to_string_pretty(document)? + "\n",
+        })
+    }
+}
+
+impl std::fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+        })
+    }
+}
+
+/// Rewrite a config file in another format
+///
+/// Includes and `${VAR}` references are kept as written; comments are lost.
+pub fn convert(path: &Path, to: ConfigFormat) -> Result<String> {
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let document: Value = ConfigFormat::detect(path, &content)
+        .parse(&content)
+        .context(format!("Failed to parse {}", path.display()))?;
+    to.to_string(&document)
+        .context(format!("Failed to write {} as {}", path.display(), to))
+}
+
+/// Drop null values from every mapping and sequence
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Sequence(items) => {
+            Value::Sequence(items.into_iter().filter(|v| !v.is_null()).map(without_nulls).collect())
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        let path = Path::new("cxn.yml");
+        assert_eq!(ConfigFormat::detect(path, "{}"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::detect(Path::new("hosts.json"), ""), ConfigFormat::Json);
+
+        let unknown = Path::new("cxn.conf");
+        assert_eq!(ConfigFormat::detect(unknown, "\n  {\"hosts\": {}}"), ConfigFormat::Json);
+        assert_eq!(
+            ConfigFormat::detect(unknown, "# hosts\n[hosts.Gateway]\n"),
+            ConfigFormat::Toml
+        );
+        assert_eq!(ConfigFormat::detect(unknown, "timeout = 500\n"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::detect(unknown, "timeout: 500\n"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::detect(unknown, "hosts:\n  A=B:\n"), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_order() {
+        let yaml = "timeout: 500\nhosts:\n  Zeta:\n    address: 10.0.0.2\n    tcp: [22]\n\
+                    \x20 Alpha:\n    address: 10.0.0.1\n";
+        let document: Value = serde_yaml::from_str(yaml).unwrap();
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let text = format.to_string(&document).unwrap();
+            let back: Value = format.parse(&text).unwrap();
+            // Mapping equality ignores order, so compare as text
+            assert_eq!(
+                serde_yaml::to_string(&back).unwrap(),
+                serde_yaml::to_string(&document).unwrap(),
+                "{}",
+                text
+            );
+        }
+        let toml = ConfigFormat::Toml
+            .to_string(&serde_yaml::from_str("hosts:\ntimeout: 5\n").unwrap())
+            .unwrap();
+        assert_eq!(toml, "timeout = 5\n");
+    }
+
+    #[test]
+    fn test_convert_keeps_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxn.yml");
+        fs::write(
+            &path,
+            "include: [teams/*.yml]\nhosts:\n  DB:\n    address: ${DB_HOST}\n    ping: true\n",
+        )
+        .unwrap();
+
+        let toml = convert(&path, ConfigFormat::Toml).unwrap();
+        assert!(toml.contains("address = \"${DB_HOST}\""), "{}", toml);
+        let json = convert(&path, ConfigFormat::Json).unwrap();
+        assert!(
+            json.starts_with("{\n  \"include\": [\n    \"teams/*.yml\"\n  ],"),
+            "{}",
+            json
+        );
+    }
+}
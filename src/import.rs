@@ -0,0 +1,426 @@
+use crate::config::HostEntry;
+use crate::filter::glob_match;
+use clap::ValueEnum;
+use eyre::{Context, Result};
+use indexmap::IndexMap;
+use std::fs;
+use std::io::BufRead;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// Default SSH port, checked for hosts imported from SSH sources
+const SSH_PORT: u16 = 22;
+
+/// Deepest chain of ssh_config `Include`s followed
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A file of hosts that `cxn import` and `cxn check --from` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// ~/.ssh/config Host entries, checked with TCP to their SSH port
+    SshConfig,
+    /// /etc/hosts entries, checked with ping
+    HostsFile,
+    /// ~/.ssh/known_hosts entries, checked with TCP to their SSH port
+    KnownHosts,
+}
+
+impl ImportSource {
+    /// Where this source is read from when no path is given
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            ImportSource::SshConfig => dirs::home_dir().map(|home| home.join(".ssh").join("config")),
+            ImportSource::HostsFile => Some(PathBuf::from("/etc/hosts")),
+            ImportSource::KnownHosts => dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts")),
+        }
+    }
+}
+
+/// A host found in an import source, before it becomes a `HostEntry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Imported {
+    name: String,
+    address: String,
+    /// SSH port, for sources that have one
+    port: Option<u16>,
+}
+
+/// Read hosts from `source` (at `path`, or its default location)
+///
+/// SSH sources get a TCP check on the SSH port, or a ping check with `ping`;
+/// hosts-file entries are always pinged.
+pub fn import(source: ImportSource, path: Option<&Path>, ping: bool) -> Result<IndexMap<String, HostEntry>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => source
+            .default_path()
+            .ok_or_else(|| eyre::eyre!("No home directory; pass the file to read"))?,
+    };
+    let content = fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+
+    let imported = match source {
+        ImportSource::SshConfig => {
+            let base = path.parent().unwrap_or(Path::new("."));
+            let mut blocks = Vec::new();
+            parse_ssh_config(&content, base, vec!["*".to_string()], &mut blocks, 0)?;
+            resolve_ssh_hosts(&blocks)
+        }
+        ImportSource::HostsFile => parse_hosts_file(&content),
+        ImportSource::KnownHosts => parse_known_hosts(&content),
+    };
+    log::info!("Imported {} hosts from {}", imported.len(), path.display());
+
+    let mut hosts = IndexMap::new();
+    for host in imported {
+        let entry = match host.port {
+            Some(port) if !ping => HostEntry {
+                address: host.address,
+                tcp: vec![port],
+                ..Default::default()
+            },
+            _ => HostEntry {
+                address: host.address,
+                ping: true,
+                ..Default::default()
+            },
+        };
+        // Sources may list a host twice; the first entry wins
+        hosts.entry(host.name).or_insert(entry);
+    }
+    Ok(hosts)
+}
+
+/// Hosts for addresses given on the command line, each with the checks in `template`
+///
+/// An address of `-` reads more from `stdin`: the first word of each line,
+/// skipping blank lines and `#` comments.
+pub fn adhoc_hosts(
+    addresses: &[String],
+    template: &HostEntry,
+    stdin: impl BufRead,
+) -> Result<IndexMap<String, HostEntry>> {
+    let mut all = Vec::new();
+    let mut stdin = Some(stdin);
+    for address in addresses {
+        match (address.as_str(), stdin.take()) {
+            ("-", Some(stdin)) => {
+                for line in stdin.lines() {
+                    let line = line.context("Failed to read addresses from stdin")?;
+                    if let Some(word) = line.split('#').next().and_then(|l| l.split_whitespace().next()) {
+                        all.push(word.to_string());
+                    }
+                }
+            }
+            ("-", None) => eyre::bail!("stdin ('-') can only be given once"),
+            (_, stdin_left) => {
+                stdin = stdin_left;
+                all.push(address.clone());
+            }
+        }
+    }
+    if all.is_empty() {
+        eyre::bail!("No addresses to check");
+    }
+
+    let mut hosts = IndexMap::new();
+    for address in all {
+        let entry = HostEntry {
+            address: address.clone(),
+            ..template.clone()
+        };
+        hosts.entry(address).or_insert(entry);
+    }
+    Ok(hosts)
+}
+
+/// One `Host` section of an ssh_config, with its options in file order
+#[derive(Debug, Default)]
+struct SshBlock {
+    patterns: Vec<String>,
+    /// Lowercased keyword and value
+    options: Vec<(String, String)>,
+}
+
+impl SshBlock {
+    /// ssh's rule: some pattern matches and no negated (`!`) pattern does
+    fn matches(&self, alias: &str) -> bool {
+        let mut matched = false;
+        for pattern in &self.patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if glob_match(negated, alias) => return false,
+                Some(_) => {}
+                None => matched |= glob_match(pattern, alias),
+            }
+        }
+        matched
+    }
+}
+
+/// Split an ssh_config line into its keyword and value (`Key value` or `Key=value`)
+fn ssh_keyword(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let value = line[end..]
+        .trim_start()
+        .strip_prefix('=')
+        .unwrap_or(&line[end..])
+        .trim();
+    Some((line[..end].to_ascii_lowercase(), value.trim_matches('"').to_string()))
+}
+
+/// Collect the Host sections of an ssh_config, following `Include`s
+///
+/// Lines before the first `Host` belong to `current` (`*` at the top level).
+fn parse_ssh_config(
+    content: &str,
+    base: &Path,
+    current: Vec<String>,
+    blocks: &mut Vec<SshBlock>,
+    depth: usize,
+) -> Result<()> {
+    blocks.push(SshBlock {
+        patterns: current,
+        options: Vec::new(),
+    });
+    for (key, value) in content.lines().filter_map(ssh_keyword) {
+        match key.as_str() {
+            "host" => blocks.push(SshBlock {
+                patterns: value.split_whitespace().map(String::from).collect(),
+                options: Vec::new(),
+            }),
+            // Match conditions can't be evaluated offline; their options are skipped
+            "match" => blocks.push(SshBlock::default()),
+            "include" if depth < MAX_INCLUDE_DEPTH => {
+                let patterns = blocks.last().map(|b| b.patterns.clone()).unwrap_or_default();
+                for pattern in value.split_whitespace() {
+                    let pattern = match pattern.strip_prefix("~/") {
+                        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+                        None => base.join(pattern),
+                    };
+                    let matches = glob::glob(&pattern.to_string_lossy())
+                        .context(format!("Bad Include pattern {}", pattern.display()))?;
+                    for included in matches.filter_map(|m| m.ok()) {
+                        let content =
+                            fs::read_to_string(&included).context(format!("Failed to read {}", included.display()))?;
+                        parse_ssh_config(&content, base, patterns.clone(), blocks, depth + 1)?;
+                    }
+                }
+                // Options after the Include still belong to the enclosing block
+                blocks.push(SshBlock {
+                    patterns,
+                    options: Vec::new(),
+                });
+            }
+            _ => {
+                if let Some(block) = blocks.last_mut() {
+                    block.options.push((key, value));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Turn every concrete `Host` alias into a host, applying HostName and Port from
+/// all matching sections the way ssh does (first value wins)
+fn resolve_ssh_hosts(blocks: &[SshBlock]) -> Vec<Imported> {
+    let mut aliases: Vec<&str> = Vec::new();
+    for pattern in blocks.iter().flat_map(|b| &b.patterns) {
+        if !pattern.contains(['*', '?', '!']) && !aliases.contains(&pattern.as_str()) {
+            aliases.push(pattern);
+        }
+    }
+
+    aliases
+        .into_iter()
+        .map(|alias| {
+            let option = |key: &str| {
+                blocks
+                    .iter()
+                    .filter(|b| b.matches(alias))
+                    .flat_map(|b| &b.options)
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.as_str())
+            };
+            let address = option("hostname")
+                .map(|h| h.replace("%h", alias).replace("%%", "%"))
+                .unwrap_or_else(|| alias.to_string());
+            let port = option("port").and_then(|p| p.parse().ok()).unwrap_or(SSH_PORT);
+            Imported {
+                name: alias.to_string(),
+                address,
+                port: Some(port),
+            }
+        })
+        .collect()
+}
+
+/// Entries of an /etc/hosts file, skipping loopback and other local-only names
+fn parse_hosts_file(content: &str) -> Vec<Imported> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split('#').next()?.split_whitespace();
+            let ip: IpAddr = words.next()?.parse().ok()?;
+            let name = words.next()?;
+            let local_only = ip.is_loopback() || ip.is_unspecified() || ip.is_multicast();
+            if local_only || name == "localhost" || name == "broadcasthost" || name.starts_with("ip6-") {
+                return None;
+            }
+            Some(Imported {
+                name: name.to_string(),
+                address: ip.to_string(),
+                port: None,
+            })
+        })
+        .collect()
+}
+
+/// Hosts of a known_hosts file; hashed and wildcard entries can't be recovered and are skipped
+fn parse_known_hosts(content: &str) -> Vec<Imported> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            // @cert-authority / @revoked lines describe keys, not hosts
+            if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+                return None;
+            }
+            let hostnames = line.split_whitespace().next()?;
+            let host = hostnames
+                .split(',')
+                .find(|h| !h.starts_with('|') && !h.contains(['*', '?', '!']))?;
+            let (address, port) = match host.strip_prefix('[').and_then(|h| h.split_once("]:")) {
+                Some((address, port)) => (address, port.parse().ok()?),
+                None => (host, SSH_PORT),
+            };
+            let name = if port == SSH_PORT {
+                address.to_string()
+            } else {
+                format!("{}:{}", address, port)
+            };
+            Some(Imported {
+                name,
+                address: address.to_string(),
+                port: Some(port),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imported(name: &str, address: &str, port: Option<u16>) -> Imported {
+        Imported {
+            name: name.to_string(),
+            address: address.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_ssh_config_patterns_hostname_and_port() {
+        let content = "\
+User me
+
+Host bastion
+    HostName 203.0.113.10
+    Port 2222
+
+Host web-1 web-2 !web-3
+  Hostname=%h.prod.example.com
+
+Host *.internal db
+    Port 2200
+
+Match host foo
+    Port 9999
+
+Host *
+    Port 22
+";
+        let mut blocks = Vec::new();
+        parse_ssh_config(content, Path::new("."), vec!["*".to_string()], &mut blocks, 0).unwrap();
+        assert_eq!(
+            resolve_ssh_hosts(&blocks),
+            vec![
+                imported("bastion", "203.0.113.10", Some(2222)),
+                imported("web-1", "web-1.prod.example.com", Some(22)),
+                imported("web-2", "web-2.prod.example.com", Some(22)),
+                imported("db", "db", Some(2200)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ssh_config_include() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::create_dir_all(dir.join("config.d")).unwrap();
+        fs::write(dir.join("config.d/work"), "Host build\n  HostName 10.0.0.7\n").unwrap();
+        fs::write(
+            dir.join("config"),
+            "Include config.d/*\nHost home\n  HostName 192.168.1.2\n",
+        )
+        .unwrap();
+
+        let hosts = import(ImportSource::SshConfig, Some(&dir.join("config")), false).unwrap();
+        let names: Vec<_> = hosts.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["build", "home"]);
+        assert_eq!(hosts["build"].address, "10.0.0.7");
+        assert_eq!(hosts["build"].tcp, vec![22]);
+
+        let hosts = import(ImportSource::SshConfig, Some(&dir.join("config")), true).unwrap();
+        assert!(hosts["home"].ping && hosts["home"].tcp.is_empty());
+    }
+
+    #[test]
+    fn test_adhoc_hosts() {
+        let template = HostEntry {
+            tcp: vec![443],
+            ..Default::default()
+        };
+        let args = ["web1".to_string(), "-".to_string(), "web1".to_string()];
+        let stdin = "# inventory\n10.0.0.5 app\n\n  db.internal\n".as_bytes();
+        let hosts = adhoc_hosts(&args, &template, stdin).unwrap();
+        let names: Vec<_> = hosts.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["web1", "10.0.0.5", "db.internal"]);
+        assert_eq!(hosts["db.internal"].address, "db.internal");
+        assert_eq!(hosts["db.internal"].tcp, vec![443]);
+
+        let twice = ["-".to_string(), "-".to_string()];
+        assert!(adhoc_hosts(&twice, &template, "a\n".as_bytes()).is_err());
+        assert!(adhoc_hosts(&["-".to_string()], &template, "".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_hosts_file_and_known_hosts() {
+        let hosts = "127.0.0.1 localhost\n::1 ip6-localhost ip6-loopback\nff02::1 ip6-allnodes\n\
+                     10.0.0.5  nas nas.lan  # storage\n192.168.1.1 router\n";
+        assert_eq!(
+            parse_hosts_file(hosts),
+            vec![
+                imported("nas", "10.0.0.5", None),
+                imported("router", "192.168.1.1", None)
+            ]
+        );
+
+        let known = "github.com,140.82.121.4 ssh-ed25519 AAAA\n\
+                     [git.example.com]:2222 ssh-rsa AAAA\n\
+                     |1|abc=|def= ssh-ed25519 AAAA\n\
+                     *.corp.example ssh-rsa AAAA\n\
+                     @cert-authority *.example.com ssh-rsa AAAA\n";
+        assert_eq!(
+            parse_known_hosts(known),
+            vec![
+                imported("github.com", "github.com", Some(22)),
+                imported("git.example.com:2222", "git.example.com", Some(2222)),
+            ]
+        );
+    }
+}
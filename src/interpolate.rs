@@ -0,0 +1,323 @@
+use eyre::{Context, Result};
+use serde::de::{self, Deserializer, Unexpected, Visitor};
+use serde_yaml::Value;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// Replacement text for secret values in logs and output
+pub const REDACTED: &str = "****";
+
+/// Shorter secrets are too likely to match unrelated text to be redacted
+const MIN_SECRET_LEN: usize = 4;
+
+/// Environment variable names containing any of these are treated as secrets
+const SECRET_MARKERS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL", "AUTH"];
+
+/// Values expanded from secrets so far, redacted wherever they are displayed
+fn secrets() -> &'static Mutex<Vec<String>> {
+    static SECRETS: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+    SECRETS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn register_secret(value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    if value.chars().count() < MIN_SECRET_LEN {
+        log::warn!(
+            "A secret value is shorter than {} characters and will not be redacted",
+            MIN_SECRET_LEN
+        );
+        return;
+    }
+    let mut secrets = secrets().lock().unwrap();
+    if !secrets.iter().any(|s| s == value) {
+        secrets.push(value.to_string());
+        // Longest first so a secret containing another is replaced whole
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Replace every known secret value in `text` with `****`
+pub fn redact(text: &str) -> String {
+    let secrets = secrets().lock().unwrap();
+    secrets
+        .iter()
+        .fold(text.to_string(), |acc, secret| acc.replace(secret.as_str(), REDACTED))
+}
+
+/// Copy a JSON value with known secret values replaced in every string in it
+pub fn redact_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(text) => serde_json::Value::String(redact(text)),
+        serde_json::Value::Array(items) => items.iter().map(redact_json).collect(),
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| (key.clone(), redact_json(value)))
+            .collect(),
+        other => other.clone(),
+    }
+}
+
+/// Rebuild an error with secrets removed from its message chain
+pub fn redact_report(report: eyre::Report) -> eyre::Report {
+    eyre::eyre!(redact(&format!("{:#}", report)))
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| upper.contains(marker))
+}
+
+/// Value of a variable in the process environment, if set
+pub fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// Expand `${VAR}`, `${VAR:-default}` and `${file:PATH}` references in a string
+///
+/// `$$` produces a literal `$`. Values read from files, and from variables whose
+/// names look secret (TOKEN, PASSWORD, KEY, ...), are registered for redaction.
+pub fn expand(input: &str) -> Result<String> {
+    expand_with(input, &env)
+}
+
+/// [`expand`], looking variables up with `lookup` instead of the environment
+pub fn expand_with(input: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        output.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(tail) = after.strip_prefix('$') {
+            output.push('$');
+            rest = tail;
+        } else if let Some(body) = after.strip_prefix('{') {
+            let end = body
+                .find('}')
+                .ok_or_else(|| eyre::eyre!("unterminated ${{...}} in '{}'", input))?;
+            output.push_str(&expand_reference(&body[..end], lookup)?);
+            rest = &body[end + 1..];
+        } else {
+            output.push('$');
+            rest = after;
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Resolve the inside of a single `${...}` reference
+fn expand_reference(reference: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    if let Some(path) = reference.strip_prefix("file:") {
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()
+                .ok_or_else(|| eyre::eyre!("Cannot expand ~ without a home directory"))?
+                .join(rest),
+            None => path.into(),
+        };
+        let content =
+            std::fs::read_to_string(&path).context(format!("Failed to read secret file {}", path.display()))?;
+        // Secret files usually end with a newline that is not part of the value
+        let value = content.trim_end_matches(['\r', '\n']).to_string();
+        register_secret(&value);
+        return Ok(value);
+    }
+
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        eyre::bail!("invalid variable reference '${{{}}}'", reference);
+    }
+
+    let value = match (lookup(name), default) {
+        (Some(value), _) if !value.is_empty() => value,
+        (_, Some(default)) => default.to_string(),
+        (Some(value), None) => value,
+        (None, None) => eyre::bail!("environment variable {} is not set", name),
+    };
+    if is_secret_name(name) {
+        register_secret(&value);
+    }
+    Ok(value)
+}
+
+/// Expand references in every string value of a parsed YAML document
+///
+/// Values stay strings; numeric fields read them with [`number`]. Errors name
+/// the offending field, e.g. `hosts.Database.address`.
+pub fn expand_value(value: &mut Value) -> Result<()> {
+    expand_value_with(value, &env)
+}
+
+/// [`expand_value`], looking variables up with `lookup` instead of the environment
+pub fn expand_value_with(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+    expand_at(value, &mut Vec::new(), lookup)
+}
+
+fn expand_at(value: &mut Value, path: &mut Vec<String>, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains('$') => {
+            *s = expand_with(s, lookup).context(format!("in {}", path.join(".")))?;
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(index.to_string());
+                expand_at(item, path, lookup)?;
+                path.pop();
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                path.push(key.as_str().map(String::from).unwrap_or_else(|| format!("{:?}", key)));
+                expand_at(item, path, lookup)?;
+                path.pop();
+            }
+        }
+        Value::Tagged(tagged) => expand_at(&mut tagged.value, path, lookup)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Deserialize an optional count or duration written as a number or as text,
+/// so `timeout: ${CXN_TIMEOUT}` works once the reference is expanded
+///
+/// For use as `#[serde(default, deserialize_with = "interpolate::number")]`.
+pub fn number<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64> + FromStr,
+{
+    deserializer.deserialize_option(NumberVisitor(PhantomData))
+}
+
+struct NumberVisitor<T>(PhantomData<T>);
+
+impl<'de, T: TryFrom<u64> + FromStr> Visitor<'de> for NumberVisitor<T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative integer")
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+        T::try_from(v)
+            .map(Some)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &"a smaller integer"))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Self::Value, E> {
+        match u64::try_from(v) {
+            Ok(v) => self.visit_u64(v),
+            Err(_) => Err(E::invalid_value(Unexpected::Signed(v), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+        v.trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// Variables for expansion in tests, instead of the shared process environment
+    fn vars(pairs: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| pairs.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let lookup = vars(&[("HOST", "db.staging"), ("EMPTY", "")]);
+        let expand = |input: &str| expand_with(input, &lookup);
+        assert_eq!(expand("${HOST}").unwrap(), "db.staging");
+        assert_eq!(expand("https://${HOST}:8443/").unwrap(), "https://db.staging:8443/");
+        assert_eq!(expand("${UNSET:-10.0.0.1}").unwrap(), "10.0.0.1");
+        assert_eq!(expand("${EMPTY:-10.0.0.1}").unwrap(), "10.0.0.1");
+        assert_eq!(expand("cost $5, literal $${X}").unwrap(), "cost $5, literal ${X}");
+
+        let err = expand("${UNSET}").unwrap_err().to_string();
+        assert!(err.contains("UNSET"), "{}", err);
+        assert!(expand("${HOST").is_err());
+        assert!(expand("${not valid}").is_err());
+    }
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let lookup = vars(&[("API_TOKEN", "s3cr3t-value"), ("PIN_KEY", "1")]);
+        let header = expand_with("Bearer ${API_TOKEN}", &lookup).unwrap();
+        assert_eq!(header, "Bearer s3cr3t-value");
+        assert_eq!(redact(&header), "Bearer ****");
+        let json = serde_json::json!({ "headers": ["Authorization: Bearer s3cr3t-value"], "status": 401 });
+        assert_eq!(
+            redact_json(&json),
+            serde_json::json!({ "headers": ["Authorization: Bearer ****"], "status": 401 })
+        );
+
+        // Redacting a one-character secret would mangle every other "1" in the output
+        assert_eq!(expand_with("${PIN_KEY}", &lookup).unwrap(), "1");
+        assert_eq!(redact("10.0.0.1"), "10.0.0.1");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret");
+        std::fs::write(&path, "from-file-pw\n").unwrap();
+        let value = expand(&format!("${{file:{}}}", path.display())).unwrap();
+        assert_eq!(value, "from-file-pw");
+        assert_eq!(redact("password=from-file-pw"), "password=****");
+    }
+
+    #[test]
+    fn test_expand_value_names_field() {
+        let lookup = vars(&[("TIMEOUT", "2500"), ("DB_IP", "10")]);
+        let mut value: Value = serde_yaml::from_str("hosts:\n  Database:\n    address: ${MISSING_DB}\n").unwrap();
+        let err = format!("{:#}", expand_value_with(&mut value, &lookup).unwrap_err());
+        assert!(err.contains("hosts.Database.address"), "{}", err);
+
+        let mut value: Value = serde_yaml::from_str("timeout: ${TIMEOUT}\naddress: \"${DB_IP}\"\n").unwrap();
+        expand_value_with(&mut value, &lookup).unwrap();
+        assert_eq!(value["timeout"], Value::from("2500"));
+        assert_eq!(value["address"], Value::from("10"));
+    }
+
+    #[test]
+    fn test_number_reads_integers_and_text() {
+        #[derive(Deserialize)]
+        struct Settings {
+            #[serde(default, deserialize_with = "number")]
+            timeout: Option<u64>,
+            #[serde(default, deserialize_with = "number")]
+            count: Option<u8>,
+        }
+        let parse = |yaml: &str| serde_yaml::from_str::<Settings>(yaml).map(|s| (s.timeout, s.count));
+        assert_eq!(parse("timeout: 2500\ncount: 3").unwrap(), (Some(2500), Some(3)));
+        assert_eq!(parse("timeout: '2500'\ncount: ~").unwrap(), (Some(2500), None));
+        assert_eq!(parse("{}").unwrap(), (None, None));
+        assert!(parse("timeout: soon").is_err());
+        assert!(parse("timeout: -1").is_err());
+        assert!(parse("count: 300").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Connectivity checks from cxn, for embedding in other tools
+//!
+//! The `cxn` command line tool is a thin consumer of this crate: it loads a
+//! [`Config`], builds a [`Checker`] from it and prints the [`CheckResult`]s.
+//! Other programs can do the same, or check hosts they describe themselves.
+//!
+//! ```no_run
+//! use cxn::{Checker, Config};
+//!
+//! # async fn example() -> eyre::Result<()> {
+//! let config = Config::load_from_file("/etc/cxn/cxn.yml")?;
+//! let checker = Checker::builder().config(&config).build()?;
+//! let results = checker.run(&config.hosts()).await;
+//! let tally = cxn::Tally::of(&results);
+//! println!("{}/{} hosts OK", tally.ok, tally.checked());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The items re-exported here are the supported API: the [`Checker`] and its
+//! builder, the config types, the result types and the [`Check`] trait for
+//! new probe types. The `testing` feature adds the network fakes in `fake`.
+//! The other public modules exist for the `cxn` binary and are hidden from
+//! the docs; they may change in any release.
+
+#[doc(hidden)]
+pub mod check;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod dns;
+mod exec;
+#[cfg(any(test, feature = "testing"))]
+pub mod fake;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod interpolate;
+#[doc(hidden)]
+pub mod mtu;
+#[doc(hidden)]
+pub mod netns;
+mod network;
+#[doc(hidden)]
+pub mod ping;
+mod probe;
+#[doc(hidden)]
+pub mod profile;
+#[doc(hidden)]
+pub mod source;
+mod sweep;
+mod tcp;
+#[doc(hidden)]
+pub mod trace;
+
+pub use check::{CheckContext, CheckResult, Checker, CheckerBuilder, RunLimits, Tally};
+pub use config::{Config, GroupEntry, HostConfig, HostEntry};
+pub use dns::DnsResult;
+pub use exec::ExecCommand;
+pub use mtu::MtuCheckResult;
+pub use network::{Pinger, Resolver};
+pub use ping::PingResult;
+pub use probe::{BoxFuture, Check, CheckOutcome, Factory, Metric, Status, Threshold};
+pub use source::SourceBinding;
+pub use sweep::Sweep;
+pub use tcp::TcpResult;
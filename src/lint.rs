@@ -0,0 +1,99 @@
+use colored::*;
+use cxn::config::Config;
+use std::collections::HashMap;
+
+/// Something `cxn config validate` wants the user to look at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Host the finding is about, if any
+    pub host: Option<String>,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Finding {
+    /// Format the finding for display, citing the file the host came from
+    pub fn format(&self, config: &Config) -> String {
+        let origin = self
+            .host
+            .as_deref()
+            .and_then(|h| config.origin(h))
+            .map(|p| format!(" ({})", p.display()))
+            .unwrap_or_default();
+        format!("  {} {}{}", "⚠".yellow(), self.message, origin.dimmed())
+    }
+}
+
+/// Find config mistakes that load fine but probably don't do what was meant
+pub fn lint(config: &Config) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let mut by_address: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, entry, _) in config.host_entries() {
+        let host = |message: String| Finding {
+            host: Some(name.clone()),
+            message,
+        };
+
+        if !entry.ping && !entry.dns && entry.mtu_min.is_none() && entry.tcp.is_empty() && entry.exec.is_empty() {
+            findings.push(host(format!(
+                "host '{}' has no checks enabled (set ping, dns, tcp, mtu_min or exec)",
+                name
+            )));
+        }
+        if entry.dns && entry.address.parse::<std::net::IpAddr>().is_ok() {
+            findings.push(host(format!(
+                "host '{}' has dns: true but {} is an IP address; the DNS check is skipped",
+                name, entry.address
+            )));
+        }
+        by_address.entry(entry.address.as_str()).or_default().push(name);
+    }
+
+    // Report duplicates in config order, once per address
+    for (name, entry, _) in config.host_entries() {
+        let names = &by_address[entry.address.as_str()];
+        if names.len() > 1 && names[0] == name.as_str() {
+            findings.push(Finding {
+                host: None,
+                message: format!("hosts {} share address {}", quote_list(names), entry.address),
+            });
+        }
+    }
+
+    findings
+}
+
+fn quote_list(names: &[&str]) -> String {
+    names.iter().map(|n| format!("'{}'", n)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_findings() {
+        let yaml = r#"
+hosts:
+  Quiet:
+    address: "10.0.0.1"
+  Redundant:
+    address: "8.8.8.8"
+    ping: true
+    dns: true
+  Same:
+    address: "8.8.8.8"
+    ping: true
+  Fine:
+    address: "example.com"
+    dns: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let messages: Vec<_> = lint(&config).into_iter().map(|f| f.message).collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].contains("'Quiet' has no checks"));
+        assert!(messages[1].contains("'Redundant' has dns: true"));
+        assert!(messages[2].contains("'Redundant', 'Same' share address 8.8.8.8"));
+    }
+}
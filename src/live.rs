@@ -0,0 +1,372 @@
+use colored::*;
+use cxn::Status;
+use cxn::check::{self, CheckResult};
+use cxn::config::HostConfig;
+use cxn::interpolate;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+
+/// Spinner frames shown on rows still being checked
+const SPINNER: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// One line of the live view
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    /// Header before the hosts of a group
+    Group(String),
+    /// A single host, by index
+    Host(usize),
+    /// Hosts expanded from one sweep entry, shown as one line
+    Sweep(Range<usize>),
+}
+
+/// Results of a streaming check, shown in config order as they come in
+///
+/// On a terminal tall enough for every row, all rows are drawn up front and
+/// redrawn in place, with a spinner on those still pending. Otherwise each
+/// row is printed once, as soon as it and every row above it are finished.
+pub struct LiveView {
+    hosts: Vec<HostConfig>,
+    rows: Vec<Row>,
+    results: Vec<Option<CheckResult>>,
+    /// Terminal width when redrawing in place, else `None`
+    width: Option<usize>,
+    /// Rows written so far: all of them once drawn in place, else the finished prefix
+    written: usize,
+    frame: usize,
+}
+
+impl LiveView {
+    /// Start a view of `hosts`, redrawing in place if stdout is a terminal with room for it
+    pub fn new(hosts: &[HostConfig]) -> Self {
+        Self::with_terminal(hosts, terminal_size())
+    }
+
+    /// Start a view for a terminal of the given (columns, rows), or a plain stream if `None`
+    fn with_terminal(hosts: &[HostConfig], terminal: Option<(usize, usize)>) -> Self {
+        let rows = layout(hosts);
+        let width = terminal
+            .filter(|(_, height)| rows.len() < *height)
+            .map(|(width, _)| width);
+        Self {
+            hosts: hosts.to_vec(),
+            rows,
+            results: vec![None; hosts.len()],
+            width,
+            written: 0,
+            frame: 0,
+        }
+    }
+
+    /// Record the result of the host at `idx` and show it
+    pub fn update(&mut self, idx: usize, result: CheckResult) {
+        self.results[idx] = Some(result);
+        self.draw();
+    }
+
+    /// Advance the spinners
+    pub fn tick(&mut self) {
+        self.frame += 1;
+        if self.width.is_some() {
+            self.draw();
+        }
+    }
+
+    /// Write whatever has changed since the last draw
+    pub fn draw(&mut self) {
+        let output = self.render();
+        if !output.is_empty() {
+            print!("{}", output);
+            io::stdout().flush().ok();
+        }
+    }
+
+    /// Text to write to bring the screen up to date
+    fn render(&mut self) -> String {
+        let mut output = String::new();
+        if self.width.is_some() {
+            // Back to the first row, then rewrite every row
+            if self.written > 0 {
+                output.push_str(&format!("\x1b[{}A", self.written));
+            }
+            for row in &self.rows {
+                output.push_str(&format!("\r\x1b[2K{}\n", self.line(row)));
+            }
+            self.written = self.rows.len();
+        } else {
+            while let Some(row) = self.rows.get(self.written)
+                && self.is_done(row)
+            {
+                output.push_str(&format!("{}\n", self.line(row)));
+                self.written += 1;
+            }
+        }
+        output
+    }
+
+    /// Check if every host of a row has a result
+    fn is_done(&self, row: &Row) -> bool {
+        match row {
+            Row::Group(_) => true,
+            Row::Host(idx) => self.results[*idx].is_some(),
+            Row::Sweep(range) => self.results[range.clone()].iter().all(Option::is_some),
+        }
+    }
+
+    /// Format a row: status mark, host, and a short description of the outcome
+    fn line(&self, row: &Row) -> String {
+        let spinner = SPINNER[self.frame % SPINNER.len()].cyan();
+        let (first, mark, label, details) = match row {
+            Row::Group(name) => return format!("== {} ==", name).bold().to_string(),
+            Row::Host(idx) => {
+                let host = &self.hosts[*idx];
+                let label = format!(
+                    "{} ({})",
+                    interpolate::redact(&host.name),
+                    interpolate::redact(&host.address)
+                );
+                match self.results[*idx] {
+                    Some(ref result) => {
+                        let (mark, details) = describe(result);
+                        (*idx, mark, label, details)
+                    }
+                    None => (*idx, spinner, label, String::new().normal()),
+                }
+            }
+            Row::Sweep(range) => {
+                let sweep = self.hosts[range.start]
+                    .sweep
+                    .as_ref()
+                    .expect("sweep rows come from sweeps");
+                let label = format!(
+                    "{} ({})",
+                    interpolate::redact(&sweep.name),
+                    interpolate::redact(&sweep.pattern)
+                );
+                let results: Vec<CheckResult> = self.results[range.clone()].iter().flatten().cloned().collect();
+                if results.len() < range.len() {
+                    let progress = format!("{}/{} checked", results.len(), range.len());
+                    (range.start, spinner, label, progress.dimmed())
+                } else {
+                    let (mark, details) = describe_sweep(&results);
+                    (range.start, mark, label, details)
+                }
+            }
+        };
+
+        let indent = if self.hosts[first].group.is_some() { "  " } else { "" };
+        let mut details = details;
+        if let Some(width) = self.width {
+            // A wrapped line would throw off the redraw, so cut the details to fit
+            let used = indent.chars().count() + 2 + label.chars().count() + 2;
+            let room = width.saturating_sub(used + 1);
+            if details.chars().count() > room {
+                let cut: String = details.chars().take(room.saturating_sub(1)).collect();
+                details.input = format!("{}…", cut);
+            }
+        }
+        let label = match mark.fgcolor {
+            Some(Color::Red) => label.red(),
+            Some(Color::Yellow) => label.yellow(),
+            _ => label.normal(),
+        };
+        match details.is_empty() {
+            true => format!("{}{} {}", indent, mark, label),
+            false => format!("{}{} {}  {}", indent, mark, label, details),
+        }
+    }
+}
+
+/// Lay out hosts in config order: a header before each group, then one row per host or sweep
+fn layout(hosts: &[HostConfig]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    for group in hosts.chunk_by(|a, b| a.group == b.group) {
+        if let Some(ref name) = group[0].group {
+            rows.push(Row::Group(name.clone()));
+        }
+        for run in group.chunk_by(|a, b| a.sweep.is_some() && a.sweep == b.sweep) {
+            rows.push(match run[0].sweep {
+                Some(_) => Row::Sweep(start..start + run.len()),
+                None => Row::Host(start),
+            });
+            start += run.len();
+        }
+    }
+    rows
+}
+
+/// Status mark and one-line summary of a host's checks
+fn describe(result: &CheckResult) -> (ColoredString, ColoredString) {
+    if let Some(ref error) = result.error {
+        return ("✗".red(), interpolate::redact(error).red());
+    }
+    if let Some(ref parent) = result.blocked_by {
+        return (
+            "⊘".yellow(),
+            format!("unreachable (parent {} down)", interpolate::redact(parent)).yellow(),
+        );
+    }
+    if result.not_checked {
+        return ("⧗".yellow(), "not checked (deadline reached)".yellow());
+    }
+
+    // One part per check type; a lone check that didn't pass explains why
+    let mut parts = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for outcome in &result.outcomes {
+        if seen.contains(&outcome.check.as_str()) {
+            continue;
+        }
+        seen.push(&outcome.check);
+        let outcomes: Vec<_> = result.outcomes_of(&outcome.check).collect();
+        parts.push(match outcomes.as_slice() {
+            [only] if only.status != Status::Ok => format!("{} {}", only.check, only.summary),
+            _ => {
+                let briefs: Vec<&str> = outcomes.iter().map(|o| o.brief.as_str()).collect();
+                format!("{} {}", outcome.check, briefs.join(","))
+            }
+        });
+    }
+
+    let details = interpolate::redact(&parts.join(", "));
+    match result.outcomes.iter().map(|o| o.status).max() {
+        Some(status) => (status.mark(), status.paint(&details)),
+        None => ("-".dimmed(), "no checks".dimmed()),
+    }
+}
+
+/// Status mark and one-line summary of a finished sweep
+fn describe_sweep(results: &[CheckResult]) -> (ColoredString, ColoredString) {
+    let (ok, checked) = check::summarize(results);
+    let not_checked = results.iter().filter(|r| r.not_checked).count();
+    if let Some(ref parent) = results[0].blocked_by {
+        // Every address in a sweep shares its dependencies
+        return (
+            "⊘".yellow(),
+            format!("unreachable (parent {} down)", interpolate::redact(parent)).yellow(),
+        );
+    }
+    let mut details = format!("{}/{} up", ok, checked);
+    let down = check::sweep_down(results);
+    if !down.is_empty() {
+        details.push_str(&format!(", down: {}", down));
+    }
+    if not_checked > 0 {
+        details.push_str(&format!(", {} not checked", not_checked));
+    }
+    if ok == checked {
+        ("✓".green(), details.green())
+    } else if ok + not_checked == checked {
+        ("⧗".yellow(), details.yellow())
+    } else {
+        ("✗".red(), details.red())
+    }
+}
+
+/// Columns and rows of the terminal on stdout, if it is one
+fn terminal_size() -> Option<(usize, usize)> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a winsize through the pointer, which outlives the call
+    let status = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (status == 0 && size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col as usize, size.ws_row as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cxn::config::Config;
+
+    fn hosts() -> Vec<HostConfig> {
+        let yaml = r#"
+hosts:
+  Gateway:
+    address: "10.0.0.1"
+    ping: true
+groups:
+  lab:
+    hosts:
+      Lab:
+        address: "10.0.4.0/30"
+        ping: true
+      Printer:
+        address: "10.0.5.9"
+        ping: true
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.hosts()
+    }
+
+    #[test]
+    fn test_layout_groups_and_sweeps() {
+        let rows = layout(&hosts());
+        assert_eq!(
+            rows,
+            vec![
+                Row::Host(0),
+                Row::Group("lab".to_string()),
+                Row::Sweep(1..5),
+                Row::Host(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_prints_finished_prefix_in_order() {
+        let hosts = hosts();
+        let mut view = LiveView::with_terminal(&hosts, None);
+        assert_eq!(view.render(), "");
+
+        // The printer finishes first but waits for the rows above it
+        view.results[5] = Some(CheckResult::blocked(&hosts[5], "Gateway".to_string()));
+        assert_eq!(view.render(), "");
+
+        view.results[0] = Some(CheckResult::failed(&hosts[0], "no route".to_string()));
+        let output = view.render();
+        assert!(
+            output.contains("Gateway (10.0.0.1)") && output.contains("no route"),
+            "{}",
+            output
+        );
+        assert!(output.contains("== lab =="), "{}", output);
+        assert!(!output.contains("Lab"), "{}", output);
+
+        for (idx, host) in hosts.iter().enumerate().take(5).skip(1) {
+            view.results[idx] = Some(CheckResult::not_checked(host));
+        }
+        let output = view.render();
+        assert!(
+            output.contains("Lab (10.0.4.0/30)") && output.contains("0/4 up, 4 not checked"),
+            "{}",
+            output
+        );
+        assert!(output.contains("unreachable (parent Gateway down)"), "{}", output);
+        assert_eq!(view.written, 4);
+    }
+
+    #[test]
+    fn test_in_place_redraws_every_row() {
+        let hosts = hosts();
+        let mut view = LiveView::with_terminal(&hosts, Some((40, 24)));
+        let first = view.render();
+        assert_eq!(first.matches('\n').count(), 4);
+        assert!(first.contains("0/4 checked"), "{}", first);
+
+        view.results[0] = Some(CheckResult::failed(&hosts[0], "x".repeat(60)));
+        let second = view.render();
+        assert!(second.starts_with("\x1b[4A"), "{:?}", second);
+        // Long details are cut to the terminal width
+        assert!(second.contains('…') && !second.contains(&"x".repeat(40)), "{}", second);
+
+        // Too many rows for the terminal: stream instead
+        assert!(LiveView::with_terminal(&hosts, Some((80, 4))).width.is_none());
+    }
+}
@@ -1,41 +1,65 @@
 use clap::Parser;
 use colored::*;
-use comfy_table::{presets::NOTHING, Cell, CellAlignment, Color, Table};
+use comfy_table::{Attribute, Cell, CellAlignment, Color, Table, presets::NOTHING};
 use eyre::{Context, Result};
 use log::info;
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
 use std::net::IpAddr;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::signal;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
 
-mod check;
 mod cli;
-mod config;
-mod dns;
-mod ping;
+mod config_edit;
+mod lint;
+mod live;
+mod reload;
 
-use cli::{Cli, Commands};
-use config::Config;
+use cxn::Status;
+use cxn::{check, config, dns, filter, format, import, interpolate, mtu, netns, ping, profile, source, trace};
 
-/// Resolve watch interval with precedence: CLI > env > config > default
-/// Returns None if watch mode not enabled, Some(interval) otherwise
-fn resolve_watch_interval(cli_value: Option<u64>, config: &Config) -> Option<u64> {
+use cli::{Cli, Commands, ConfigCommands};
+use config::{Config, HostEntry};
+use filter::HostFilter;
+use import::ImportSource;
+use indexmap::IndexMap;
+use source::SourceBinding;
+
+/// Resolve a watch interval that overrides every host: CLI > env
+/// Returns None when per-host / group / global config intervals should apply
+fn watch_interval_override(cli_value: Option<u64>) -> Option<u64> {
     match cli_value {
-        None => None, // --watch not specified
-        Some(0) => {
-            // --watch with no value, use config/env
-            let env_val = std::env::var("CXN_WATCH_INTERVAL")
+        Some(0) | None => {
+            // --watch with no value (or not given), use env if set
+            std::env::var("CXN_WATCH_INTERVAL")
                 .ok()
-                .and_then(|s| s.parse().ok());
-            Some(env_val.unwrap_or(config.interval))
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
         }
         Some(n) => Some(n), // --watch N, use explicit value
     }
 }
 
+/// Activate the profile named with --profile or CXN_PROFILE, else the first
+/// one whose `match` conditions fit the current network
+fn select_profile(config: &mut Config, cli_value: Option<String>) -> Result<()> {
+    let name = cli_value
+        .or_else(|| std::env::var("CXN_PROFILE").ok().filter(|v| !v.is_empty()))
+        .or_else(|| {
+            let detected = config.detect_profile(&profile::NetworkState::detect())?;
+            info!("Auto-selected profile '{}'", detected);
+            Some(detected.to_string())
+        });
+    if let Some(name) = name {
+        config.apply_profile(&name)?;
+    }
+    Ok(())
+}
+
 fn setup_logging() -> Result<()> {
     // Create log directory
     let log_dir = dirs::data_local_dir()
@@ -56,8 +80,19 @@ fn setup_logging() -> Result<()> {
             .context("Failed to open log file")?,
     );
 
+    // Log lines pass through redaction so interpolated secrets never reach the file
     env_logger::Builder::from_default_env()
         .target(env_logger::Target::Pipe(target))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                interpolate::redact(&record.args().to_string())
+            )
+        })
         .init();
 
     info!("Logging initialized, writing to: {}", log_file.display());
@@ -65,14 +100,162 @@ fn setup_logging() -> Result<()> {
 }
 
 /// Handle the `cxn ping` subcommand
-async fn cmd_ping(host: &str, count: u32, timeout_ms: u64) -> Result<()> {
-    // Parse or resolve the host to an IP address
-    let address: IpAddr = if let Ok(ip) = host.parse() {
-        ip
+async fn cmd_ping(
+    host: &str,
+    count: u32,
+    timeout_ms: u64,
+    options: &ping::PingOptions,
+    source: &SourceBinding,
+) -> Result<()> {
+    let address = resolve_host_arg(host, source).await?;
+
+    let client = ping::create_client_with(address, options, source)?;
+    let timeout = Duration::from_millis(timeout_ms);
+    let result = ping::ping_host_detailed(&client, address, timeout, count, options).await;
+    println!("{}", result.format());
+
+    if result.packets_received == 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Resolve a host argument to a single IP address, exiting on failure
+async fn resolve_host_arg(host: &str, source: &SourceBinding) -> Result<IpAddr> {
+    if let Ok(ip) = host.parse() {
+        return Ok(ip);
+    }
+
+    let resolver = dns::create_resolver_with(source)?;
+    let result = dns::resolve_dns(&resolver, host, host, false).await;
+    if !result.success {
+        eprintln!(
+            "{}: {} - {}",
+            "Error".red(),
+            host,
+            result.error.unwrap_or_else(|| "DNS resolution failed".to_string())
+        );
+        std::process::exit(1);
+    }
+    result
+        .addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre::eyre!("No IP addresses found for {}", host))
+}
+
+/// Arguments for the `cxn trace` subcommand
+struct TraceArgs {
+    count: u32,
+    max_hops: u8,
+    timeout_ms: u64,
+    protocol: trace::TraceProtocol,
+    port: Option<u16>,
+    no_dns: bool,
+    watch: Option<u64>,
+}
+
+/// Handle the `cxn trace` subcommand
+async fn cmd_trace(host: &str, args: TraceArgs, source: &SourceBinding) -> Result<()> {
+    let address = resolve_host_arg(host, source).await?;
+    let port = args.port.unwrap_or(match args.protocol {
+        trace::TraceProtocol::Tcp => trace::DEFAULT_TCP_PORT,
+        _ => trace::DEFAULT_UDP_PORT,
+    });
+    let timeout = Duration::from_millis(args.timeout_ms);
+    let mut tracer = trace::Tracer::new(address, args.protocol, port, args.max_hops, timeout, source.clone());
+    let resolver = if args.no_dns {
+        None
+    } else {
+        Some(dns::create_resolver_with(source)?)
+    };
+
+    let mut header = format!(
+        "TRACE {} ({}), {} hops max, {} probes",
+        host, address, args.max_hops, args.protocol
+    );
+    if !source.is_default() {
+        header.push_str(&format!(", {}", source.describe()));
+    }
+
+    match args.watch {
+        None => {
+            for round in 0..args.count {
+                // Routers rate-limit ICMP errors, so pace rounds like mtr does
+                if round > 0 {
+                    tokio::time::sleep(trace::ROUND_INTERVAL).await;
+                }
+                tracer.run_round().await?;
+            }
+            if let Some(ref resolver) = resolver {
+                tracer.resolve_names(resolver).await;
+            }
+            println!("{}\n", header);
+            println!("{}", tracer.format_table());
+        }
+        Some(seconds) => {
+            // Same layout as `cxn check --watch`: header line plus table per cycle
+            let interval_duration = Duration::from_secs(seconds);
+            let mut first = true;
+            loop {
+                let cycle_start = Instant::now();
+                tracer.run_round().await?;
+                if let Some(ref resolver) = resolver {
+                    tracer.resolve_names(resolver).await;
+                }
+
+                if !first {
+                    println!();
+                }
+                first = false;
+
+                let now = chrono::Local::now();
+                println!(
+                    "{} [{}] {} (every {}s)\n",
+                    "cxn".cyan().bold(),
+                    now.format("%H:%M:%S"),
+                    header,
+                    seconds
+                );
+                println!("{}", tracer.format_table());
+                io::stdout().flush().ok();
+
+                let remaining = interval_duration.saturating_sub(cycle_start.elapsed());
+                tokio::select! {
+                    _ = tokio::time::sleep(remaining) => {}
+                    _ = signal::ctrl_c() => {
+                        println!("\n\n{}", "Watch mode stopped.".yellow());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    if !tracer.reached() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Handle the `cxn mtu` subcommand
+async fn cmd_mtu(
+    host: &str,
+    max: usize,
+    attempts: u32,
+    timeout_ms: u64,
+    ipv4: bool,
+    ipv6: bool,
+    source: &SourceBinding,
+) -> Result<()> {
+    // Probe both families for hostnames unless restricted
+    let addresses: Vec<IpAddr> = if let Ok(ip) = host.parse() {
+        vec![ip]
     } else {
-        // Need to resolve hostname first
-        let resolver = dns::create_resolver();
-        let result = dns::resolve_dns(&resolver, host, host, false).await;
+        let resolver = dns::create_resolver_with(source)?;
+        let result = dns::resolve_dns(&resolver, host, host, true).await;
         if !result.success {
             eprintln!(
                 "{}: {} - {}",
@@ -82,19 +265,32 @@ async fn cmd_ping(host: &str, count: u32, timeout_ms: u64) -> Result<()> {
             );
             std::process::exit(1);
         }
-        result
-            .addresses
-            .into_iter()
-            .next()
-            .ok_or_else(|| eyre::eyre!("No IP addresses found for {}", host))?
+        let v4 = result.addresses.iter().find(|a| a.is_ipv4()).copied();
+        let v6 = result.addresses.iter().find(|a| a.is_ipv6()).copied();
+        [v4, v6].into_iter().flatten().collect()
     };
 
-    let client = ping::create_client()?;
+    let addresses: Vec<IpAddr> = addresses
+        .into_iter()
+        .filter(|a| (!ipv4 || a.is_ipv4()) && (!ipv6 || a.is_ipv6()))
+        .collect();
+    if addresses.is_empty() {
+        eyre::bail!("No matching addresses found for {}", host);
+    }
+
     let timeout = Duration::from_millis(timeout_ms);
-    let result = ping::ping_host_detailed(&client, address, timeout, count).await;
-    println!("{}", result.format());
+    let mut any_found = false;
+    for (i, address) in addresses.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let options = ping::PingOptions::default();
+        let result = mtu::discover_path_mtu(*address, max, timeout, attempts, &options, source).await;
+        println!("{}", result.format());
+        any_found |= result.path_mtu.is_some();
+    }
 
-    if result.packets_received == 0 {
+    if !any_found {
         std::process::exit(1);
     }
 
@@ -102,8 +298,8 @@ async fn cmd_ping(host: &str, count: u32, timeout_ms: u64) -> Result<()> {
 }
 
 /// Handle the `cxn dns` subcommand
-async fn cmd_dns(hostname: &str, include_ipv6: bool) -> Result<()> {
-    let resolver = dns::create_resolver();
+async fn cmd_dns(hostname: &str, include_ipv6: bool, source: &SourceBinding) -> Result<()> {
+    let resolver = dns::create_resolver_with(source)?;
     let result = dns::resolve_dns_detailed(&resolver, hostname, include_ipv6).await;
     println!("{}", result.format());
 
@@ -114,161 +310,760 @@ async fn cmd_dns(hostname: &str, include_ipv6: bool) -> Result<()> {
     Ok(())
 }
 
+/// How a single check run reports its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckOutput {
+    /// Grouped report once every host is checked
+    Report,
+    /// Rows in config order, filled in as hosts finish
+    Stream,
+    /// One JSON object per line, in completion order
+    Ndjson,
+}
+
+/// Print why there is nothing to check
+fn print_no_hosts(config: &Config) {
+    if config.is_filtered() {
+        println!("{}", "No hosts match the given filters".yellow());
+        return;
+    }
+    println!("{}", "No hosts configured".yellow());
+    println!("Add hosts to ~/.config/cxn/cxn.yml or ./cxn.yml to get started.");
+}
+
+/// Print the line announcing a run
+fn print_checking(config: &Config, count: usize) {
+    match config.profile() {
+        Some(profile) => println!("Checking {} hosts (profile {})...\n", count, profile.bold()),
+        None => println!("Checking {} hosts...\n", count),
+    }
+}
+
 /// Handle the `cxn check` subcommand (default) - verbose output
 /// Returns true if all checks passed, false otherwise
 async fn cmd_check(config: &Config, sequential: bool) -> Result<bool> {
     let hosts = config.hosts();
     if hosts.is_empty() {
-        println!("{}", "No hosts configured".yellow());
-        println!("Add hosts to ~/.config/cxn/cxn.yml or ./cxn.yml to get started.");
+        print_no_hosts(config);
         return Ok(true);
     }
 
     let start_time = Instant::now();
-    println!("Checking {} hosts...\n", hosts.len());
-
-    // Create shared clients
-    let ping_client = Arc::new(ping::create_client()?);
-    let dns_resolver = Arc::new(dns::create_resolver());
+    print_checking(config, hosts.len());
 
     // Run checks (parallel by default)
-    let parallel = !sequential;
-    let results = check::run_all_checks(config, ping_client, dns_resolver, parallel).await;
+    let checker = check::Checker::builder()
+        .config(config)
+        .sequential(sequential)
+        .build()?;
+    let results = checker.run(&hosts).await;
+
+    // Display results, with a header and summary line around each group
+    for group in check::group_results(&results) {
+        let group_name = group[0].group.as_deref();
+        if let Some(name) = group_name {
+            println!("{}\n", format!("== {} ==", name).bold());
+        }
+
+        for run in check::sweep_results(group) {
+            // Hosts expanded from one CIDR / range / brace entry are shown as one summary
+            if let Some(ref sweep) = run[0].sweep {
+                let (ok, checked) = check::summarize(run);
+                println!(
+                    "{} ({})",
+                    interpolate::redact(&sweep.name).cyan(),
+                    interpolate::redact(&sweep.pattern)
+                );
+                let not_checked = run.iter().filter(|r| r.not_checked).count();
+                if let Some(blocked) = run[0].format_blocked() {
+                    // Every address in a sweep shares its dependencies
+                    println!("{}", blocked);
+                } else if ok == checked {
+                    println!("  {} {}: {}/{} up", "✓".green(), sweep.pattern, ok, checked);
+                } else if ok + not_checked == checked {
+                    println!(
+                        "  {} {}: {}/{} up, {} not checked",
+                        "⧗".yellow(),
+                        sweep.pattern,
+                        ok,
+                        checked,
+                        not_checked
+                    );
+                } else {
+                    let not_checked = match not_checked {
+                        0 => String::new(),
+                        n => format!(", {} not checked", n),
+                    };
+                    println!(
+                        "  {} {}: {}/{} up, down: {}{}",
+                        "✗".red(),
+                        sweep.pattern,
+                        ok,
+                        checked,
+                        check::sweep_down(run),
+                        not_checked
+                    );
+                }
+                println!();
+                continue;
+            }
+
+            let result = &run[0];
+            println!(
+                "{} ({})",
+                interpolate::redact(&result.name).cyan(),
+                interpolate::redact(&result.address)
+            );
+
+            if let Some(error) = result.format_error() {
+                println!("{}", error);
+            }
+
+            if let Some(blocked) = result.format_blocked() {
+                println!("{}", blocked);
+            }
 
-    // Display results
-    let mut success_count = 0;
-    for result in &results {
-        println!("{} ({})", result.name.cyan(), result.address);
+            if let Some(not_checked) = result.format_not_checked() {
+                println!("{}", not_checked);
+            }
+
+            for outcome in &result.outcomes {
+                println!("{}", outcome.format());
+            }
 
-        if let Some(ref dns_result) = result.dns {
-            println!("{}", dns_result.format());
+            println!();
         }
 
-        if let Some(ref ping_result) = result.ping {
-            println!("{}", ping_result.format());
+        if let Some(name) = group_name {
+            let tally = check::Tally::of(group);
+            let mut line = format!("{}: {}/{} hosts ", name.bold(), tally.ok, tally.checked());
+            if tally.all_ok() {
+                line.push_str(&"OK".green().to_string());
+            } else {
+                line.push_str("OK");
+                if tally.failed > 0 {
+                    line.push_str(&format!(", {} {}", tally.failed, "failed".red()));
+                }
+                if tally.unreachable > 0 {
+                    line.push_str(&format!(", {} {}", tally.unreachable, "unreachable".yellow()));
+                }
+                if tally.not_checked > 0 {
+                    line.push_str(&format!(", {} {}", tally.not_checked, "not checked".yellow()));
+                }
+            }
+            println!("{}\n", line);
         }
+    }
 
-        if result.is_success() {
-            success_count += 1;
+    Ok(print_summary(&results, start_time.elapsed()))
+}
+
+/// Handle `cxn check --stream`: one row per host in config order, filled in as each finishes
+///
+/// Returns true if all checks passed, false otherwise
+async fn cmd_check_stream(config: &Config, sequential: bool) -> Result<bool> {
+    let hosts = config.hosts();
+    if hosts.is_empty() {
+        print_no_hosts(config);
+        return Ok(true);
+    }
+
+    let start_time = Instant::now();
+    print_checking(config, hosts.len());
+
+    let checker = check::Checker::builder()
+        .config(config)
+        .sequential(sequential)
+        .build()?;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let run = checker.stream(&hosts, sender);
+
+    let mut view = live::LiveView::new(&hosts);
+    view.draw();
+    let show = async {
+        let mut spinner = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            tokio::select! {
+                result = receiver.recv() => match result {
+                    Some((idx, result)) => view.update(idx, result),
+                    None => break,
+                },
+                _ = spinner.tick() => view.tick(),
+            }
+        }
+    };
+    let (results, ()) = tokio::join!(run, show);
+    println!();
+
+    Ok(print_summary(&results, start_time.elapsed()))
+}
+
+/// Handle `cxn check --ndjson`: a `host` object per line as each check finishes, then a `summary`
+///
+/// Returns true if all checks passed, false otherwise
+async fn cmd_check_ndjson(config: &Config, sequential: bool) -> Result<bool> {
+    let hosts = config.hosts();
+    let start_time = Instant::now();
+    let checker = check::Checker::builder()
+        .config(config)
+        .sequential(sequential)
+        .build()?;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let run = checker.stream(&hosts, sender);
+
+    // Stdout is line buffered, so each object reaches the consumer as soon as it's printed
+    let show = async {
+        while let Some((_, result)) = receiver.recv().await {
+            println!("{}", serde_json::json!({ "type": "host", "host": result.to_json() }));
         }
+    };
+    let (results, ()) = tokio::join!(run, show);
+
+    let tally = check::Tally::of(&results);
+    let summary = serde_json::json!({
+        "ok": tally.ok,
+        "checked": tally.checked(),
+        "failed": tally.failed,
+        "unreachable": tally.unreachable,
+        "not_checked": tally.not_checked,
+        "elapsed_ms": start_time.elapsed().as_millis() as u64,
+    });
+    println!("{}", serde_json::json!({ "type": "summary", "summary": summary }));
+    Ok(tally.all_ok())
+}
 
+/// Print the root causes and summary line of a finished run
+///
+/// Returns true if all hosts with checks succeeded.
+fn print_summary(results: &[check::CheckResult], elapsed: Duration) -> bool {
+    // Root causes first, so they aren't buried under their dependents' failures
+    let causes = check::root_causes(results);
+    for (root, count) in &causes {
+        println!("{}", format_root_cause(root, *count));
+    }
+    if !causes.is_empty() {
         println!();
     }
 
-    // Summary
-    let elapsed = start_time.elapsed();
-    let hosts_checked = hosts.iter().filter(|h| h.has_checks()).count();
-    if success_count == hosts_checked {
-        println!(
+    let tally = check::Tally::of(results);
+    println!("{}", format_summary(&tally, elapsed));
+    tally.all_ok()
+}
+
+/// Summary line of a finished run
+fn format_summary(tally: &check::Tally, elapsed: Duration) -> String {
+    if tally.all_ok() {
+        return format!(
             "Summary: {}/{} hosts {} in {:.1}s",
-            success_count,
-            hosts_checked,
+            tally.ok,
+            tally.checked(),
             "OK".green(),
             elapsed.as_secs_f64()
         );
-        Ok(true)
-    } else {
-        let failed = hosts_checked - success_count;
-        println!(
-            "Summary: {}/{} hosts OK, {} {} in {:.1}s",
-            success_count,
-            hosts_checked,
-            failed,
-            "failed".red(),
-            elapsed.as_secs_f64()
-        );
-        Ok(false)
     }
+    let unreachable = match tally.unreachable {
+        0 => String::new(),
+        n => format!(", {} {}", n, "unreachable".yellow()),
+    };
+    let not_checked = match tally.not_checked {
+        0 => String::new(),
+        n => format!(", {} {}", n, "not checked".yellow()),
+    };
+    format!(
+        "Summary: {}/{} hosts OK, {} {}{}{} in {:.1}s",
+        tally.ok,
+        tally.checked(),
+        tally.failed,
+        "failed".red(),
+        unreachable,
+        not_checked,
+        elapsed.as_secs_f64()
+    )
 }
 
+/// Line naming a down host that made `count` dependent hosts unreachable
+fn format_root_cause(root: &str, count: usize) -> String {
+    format!(
+        "{} {} is down; {} dependent host{} unreachable",
+        "Root cause:".red().bold(),
+        interpolate::redact(root).bold(),
+        count,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// Table cell for one check type of a host: its outcomes' brief texts in the worst status's color
+fn outcome_cell(result: &check::CheckResult, check: &str) -> Cell {
+    let briefs: Vec<&str> = result.outcomes_of(check).map(|o| o.brief.as_str()).collect();
+    let color = match result.outcomes_of(check).map(|o| o.status).max() {
+        Some(Status::Ok) => Color::Green,
+        Some(Status::Warning) => Color::Yellow,
+        Some(Status::Unknown) => Color::Magenta,
+        Some(Status::Failed) => Color::Red,
+        None => return Cell::new("-").fg(Color::DarkGrey),
+    };
+    Cell::new(interpolate::redact(&briefs.join(","))).fg(color)
+}
+
+/// Last result for each host in watch mode, with the cycle it was checked in
+type ResultCache = HashMap<String, (Instant, check::CheckResult)>;
+
 /// Handle check in compact table format for watch mode
-async fn cmd_check_compact(config: &Config, sequential: bool) -> Result<bool> {
+///
+/// Only hosts whose interval has elapsed since their last check are probed;
+/// the others show their cached result.
+async fn cmd_check_compact(
+    config: &Config,
+    checker: &check::Checker,
+    cache: &mut ResultCache,
+    cycle_start: Instant,
+    tick: Duration,
+) -> Result<bool> {
     let hosts = config.hosts();
     if hosts.is_empty() {
-        println!("{}", "No hosts configured".yellow());
+        let message = if config.is_filtered() {
+            "No hosts match the given filters"
+        } else {
+            "No hosts configured"
+        };
+        println!("{}", message.yellow());
         return Ok(true);
     }
 
-    // Create shared clients
-    let ping_client = Arc::new(ping::create_client()?);
-    let dns_resolver = Arc::new(dns::create_resolver());
-
-    // Run checks
-    let parallel = !sequential;
-    let results = check::run_all_checks(config, ping_client, dns_resolver, parallel).await;
+    // Run checks for hosts that are due (half a tick of slack absorbs timer drift)
+    let due: Vec<_> = hosts
+        .iter()
+        .filter(|h| {
+            cache.get(&h.name).is_none_or(|(checked, _)| {
+                cycle_start.duration_since(*checked) + tick / 2 >= Duration::from_secs(h.interval)
+            })
+        })
+        .cloned()
+        .collect();
+    // Parents that aren't due are judged by their cached results
+    let previous: Vec<_> = cache.values().map(|(_, r)| r.clone()).collect();
+    for result in checker.run_after(&due, &previous).await {
+        cache.insert(result.name.clone(), (cycle_start, result));
+    }
+    let results: Vec<_> = hosts
+        .iter()
+        .filter_map(|h| cache.get(&h.name).map(|(_, r)| r.clone()))
+        .collect();
 
     // Build table
     let mut table = Table::new();
     table.load_preset(NOTHING);
 
-    // Header
-    table.set_header(vec![
-        Cell::new("NAME").fg(Color::DarkGrey),
-        Cell::new("PING").fg(Color::DarkGrey).set_alignment(CellAlignment::Right),
-        Cell::new("DNS").fg(Color::DarkGrey),
-    ]);
-
-    // Results
-    let mut success_count = 0;
-    for result in &results {
-        let (ping_text, ping_color) = match &result.ping {
-            Some(p) if p.success && p.rtt.is_some() => {
-                (format!("{:.1}ms", p.rtt.unwrap().as_secs_f64() * 1000.0), Color::Green)
-            }
-            Some(p) if p.success => ("ok".to_string(), Color::Green),
-            Some(_) => ("fail".to_string(), Color::Red),
-            None => ("-".to_string(), Color::DarkGrey),
-        };
+    // Header: one column per check type some host ran, in the order checks run
+    let columns: Vec<&str> = checker
+        .check_types()
+        .filter(|check| results.iter().any(|r| r.outcomes_of(check).next().is_some()))
+        .collect();
+    let mut header = vec![Cell::new("NAME").fg(Color::DarkGrey)];
+    header.extend(
+        columns
+            .iter()
+            .map(|check| Cell::new(check.to_uppercase()).fg(Color::DarkGrey)),
+    );
+    table.set_header(header);
 
-        let (dns_text, dns_color) = match &result.dns {
-            Some(d) if d.success => {
-                let addr = d.addresses.first().map(|a| a.to_string()).unwrap_or_default();
-                (addr, Color::Green)
-            }
-            Some(_) => ("fail".to_string(), Color::Red),
-            None => ("-".to_string(), Color::DarkGrey),
+    // Results, grouped under a row with the group name and its summary
+    for group in check::group_results(&results) {
+        let indent = if let Some(ref name) = group[0].group {
+            let (ok, checked) = check::summarize(group);
+            let color = if ok == checked { Color::Green } else { Color::Red };
+            table.add_row(vec![
+                Cell::new(name).add_attribute(Attribute::Bold),
+                Cell::new(format!("{}/{}", ok, checked))
+                    .fg(color)
+                    .set_alignment(CellAlignment::Right),
+            ]);
+            "  "
+        } else {
+            ""
         };
 
-        let name_color = if result.is_success() { Color::Reset } else { Color::Red };
+        for run in check::sweep_results(group) {
+            if let Some(ref sweep) = run[0].sweep {
+                let (ok, checked) = check::summarize(run);
+                let (name_color, color) = match (ok == checked, &run[0].blocked_by) {
+                    (true, _) => (Color::Reset, Color::Green),
+                    (false, Some(_)) => (Color::Yellow, Color::Red),
+                    (false, None) => (Color::Red, Color::Red),
+                };
+                table.add_row(vec![
+                    Cell::new(format!("{}{}", indent, interpolate::redact(&sweep.name))).fg(name_color),
+                    match run[0].blocked_by {
+                        Some(_) => Cell::new("-").fg(Color::DarkGrey),
+                        None => Cell::new(format!("{}/{} up", ok, checked)).fg(color),
+                    }
+                    .set_alignment(CellAlignment::Right),
+                    match run[0].blocked_by {
+                        Some(ref parent) => {
+                            Cell::new(format!("unreachable ({} down)", interpolate::redact(parent))).fg(Color::Yellow)
+                        }
+                        None => Cell::new(check::sweep_down(run)).fg(Color::Red),
+                    },
+                ]);
+                continue;
+            }
 
-        table.add_row(vec![
-            Cell::new(&result.name).fg(name_color),
-            Cell::new(ping_text).fg(ping_color).set_alignment(CellAlignment::Right),
-            Cell::new(dns_text).fg(dns_color),
-        ]);
+            let result = &run[0];
+            let name_color = match (result.is_success(), &result.blocked_by) {
+                (true, _) => Color::Reset,
+                (false, Some(_)) => Color::Yellow,
+                (false, None) if result.not_checked => Color::Yellow,
+                (false, None) => Color::Red,
+            };
 
-        if result.is_success() {
-            success_count += 1;
+            let mut row = vec![Cell::new(format!("{}{}", indent, interpolate::redact(&result.name))).fg(name_color)];
+            // Host-level errors have no check outcomes; show the reason instead
+            match (&result.error, &result.blocked_by) {
+                (Some(e), _) => row.push(Cell::new(interpolate::redact(e)).fg(Color::Red)),
+                (None, Some(parent)) => {
+                    row.push(Cell::new(format!("unreachable ({} down)", interpolate::redact(parent))).fg(Color::Yellow))
+                }
+                (None, None) if result.not_checked => row.push(Cell::new("not checked").fg(Color::Yellow)),
+                (None, None) => row.extend(columns.iter().map(|check| outcome_cell(result, check))),
+            }
+            table.add_row(row);
         }
     }
 
     println!("{table}");
     println!();
+    for (root, count) in check::root_causes(&results) {
+        println!("{}", format_root_cause(root, count));
+    }
     io::stdout().flush().ok();
 
-    let hosts_checked = hosts.iter().filter(|h| h.has_checks()).count();
-    Ok(success_count == hosts_checked)
+    Ok(check::Tally::of(&results).all_ok())
 }
 
-/// Run check command with optional watch mode
-async fn run_check_with_watch(config: &Config, sequential: bool, watch: Option<u64>) -> Result<()> {
-    let interval = resolve_watch_interval(watch, config);
+/// Handle the `cxn config validate` subcommand
+fn cmd_config_validate(config_path: Option<&PathBuf>, strict: bool) {
+    let config = match Config::load_strict(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{} {}", "✗".red(), interpolate::redact(&format!("{:#}", e)));
+            std::process::exit(1);
+        }
+    };
+
+    if config.files().is_empty() {
+        println!("No config file found; defaults are in use");
+    }
+    for file in config.files() {
+        println!("{} {}", "✓".green(), file.display());
+    }
+
+    let findings = lint::lint(&config);
+    for finding in &findings {
+        println!("{}", finding.format(&config));
+    }
+
+    let host_count = config.host_entries().count();
+    println!("\n{} hosts, {} warnings", host_count, findings.len());
+    if strict && !findings.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Handle the `cxn config add` subcommand
+fn cmd_config_add(config_path: Option<&PathBuf>, name: &str, group: Option<&str>, entry: &HostEntry) -> Result<()> {
+    let config = Config::load_strict(config_path).context("Failed to load configuration")?;
+    if config.host_entries().any(|(n, _, _)| n == name) {
+        let origin = config
+            .origin(name)
+            .map(|p| format!(" in {}", p.display()))
+            .unwrap_or_default();
+        eyre::bail!("Host '{}' already exists{}", name, origin);
+    }
+
+    // Edit the main file: the one given with -c, the first one loaded, or the default location
+    let path = config_path
+        .cloned()
+        .or_else(|| config.files().first().cloned())
+        .or_else(Config::default_path)
+        .ok_or_else(|| eyre::eyre!("No config directory found; pass -c <path>"))?;
+    // The edit is rolled back unless the whole config still loads
+    config_edit::add_host(&path, group, name, entry, || Config::load_strict(config_path).map(drop))?;
+    println!("Added '{}' to {}", name, path.display());
+    Ok(())
+}
+
+/// Handle the `cxn import` subcommand: print the hosts as YAML, or add them to the config file
+fn cmd_import(
+    config_path: Option<&PathBuf>,
+    hosts: IndexMap<String, HostEntry>,
+    group: Option<&str>,
+    write: bool,
+) -> Result<()> {
+    if !write {
+        let block = match group {
+            Some(group) => serde_yaml::to_string(&IndexMap::from([(
+                "groups",
+                IndexMap::from([(group, IndexMap::from([("hosts", hosts)]))]),
+            )]))?,
+            None => serde_yaml::to_string(&IndexMap::from([("hosts", hosts)]))?,
+        };
+        print!("{}", block);
+        return Ok(());
+    }
+
+    let config = Config::load_strict(config_path).context("Failed to load configuration")?;
+    let path = config_path
+        .cloned()
+        .or_else(|| config.files().first().cloned())
+        .or_else(Config::default_path)
+        .ok_or_else(|| eyre::eyre!("No config directory found; pass -c <path>"))?;
+    let (new, present): (Vec<_>, Vec<_>) = hosts
+        .iter()
+        .partition(|(name, _)| !config.host_entries().any(|(n, _, _)| n == name.as_str()));
+    for (name, _) in &present {
+        info!("Skipping '{}': already in the config", name);
+    }
+    let entries: Vec<(&str, &HostEntry)> = new.iter().map(|(name, entry)| (name.as_str(), *entry)).collect();
+    config_edit::add_hosts(&path, group, &entries, || Config::load_strict(config_path).map(drop))?;
+    let (added, skipped) = (entries.len(), present.len());
+    println!(
+        "Added {} hosts to {} ({} already present)",
+        added,
+        path.display(),
+        skipped
+    );
+    Ok(())
+}
+
+/// Handle the `cxn config convert` subcommand
+fn cmd_config_convert(config_path: Option<&PathBuf>, to: format::ConfigFormat, output: Option<&Path>) -> Result<()> {
+    let path = match config_path {
+        Some(path) => path.clone(),
+        None => Config::load_strict(None)
+            .context("Failed to load configuration")?
+            .files()
+            .first()
+            .cloned()
+            .ok_or_else(|| eyre::eyre!("No config file found; pass -c <path>"))?,
+    };
+    let converted = format::convert(&path, to)?;
+    match output {
+        Some(output) => {
+            if output.exists() {
+                eyre::bail!("{} already exists", output.display());
+            }
+            fs::write(output, converted).context(format!("Failed to write {}", output.display()))?;
+            println!("Wrote {} as {}", output.display(), to);
+        }
+        None => print!("{}", converted),
+    }
+    Ok(())
+}
+
+/// Handle the `cxn config remove` subcommand
+fn cmd_config_remove(config_path: Option<&PathBuf>, name: &str) -> Result<()> {
+    let config = Config::load_strict(config_path).context("Failed to load configuration")?;
+    let path = config
+        .origin(name)
+        .ok_or_else(|| eyre::eyre!("Host '{}' not found", name))?
+        .to_path_buf();
+    config_edit::remove_host(&path, name, || Config::load_strict(config_path).map(drop))?;
+    println!("Removed '{}' from {}", name, path.display());
+    Ok(())
+}
+
+/// Handle the `cxn config show` subcommand
+fn cmd_config_show(config: &Config, resolved: bool) -> Result<()> {
+    if !resolved {
+        let yaml = config
+            .to_annotated_yaml()
+            .context("Failed to serialize configuration")?;
+        print!("{}", interpolate::redact(&yaml));
+        return Ok(());
+    }
+
+    let hosts = config.hosts();
+    let mut table = Table::new();
+    table.load_preset(NOTHING);
+
+    // Binding / namespace columns only when some host uses them
+    let show_via = hosts.iter().any(|h| !h.source.is_default());
+    let show_netns = hosts.iter().any(|h| h.netns.is_some());
+    let mut header = vec![
+        "NAME", "GROUP", "ADDRESS", "CHECKS", "TIMEOUT", "COUNT", "RETRIES", "INTERVAL",
+    ];
+    if show_via {
+        header.push("VIA");
+    }
+    if show_netns {
+        header.push("NETNS");
+    }
+    table.set_header(header.into_iter().map(|h| Cell::new(h).fg(Color::DarkGrey)));
+
+    for host in &hosts {
+        let mut checks = Vec::new();
+        if host.dns {
+            checks.push("dns".to_string());
+        }
+        if host.ping {
+            checks.push("ping".to_string());
+        }
+        if !host.tcp.is_empty() {
+            let ports: Vec<String> = host.tcp.iter().map(u16::to_string).collect();
+            checks.push(format!("tcp/{}", ports.join(",")));
+        }
+        if let Some(mtu_min) = host.mtu_min {
+            checks.push(format!("mtu>={}", mtu_min));
+        }
+        if !host.exec.is_empty() {
+            checks.push("exec".to_string());
+        }
+
+        let mut row = vec![
+            Cell::new(interpolate::redact(&host.name)),
+            Cell::new(host.group.as_deref().unwrap_or("-")),
+            Cell::new(interpolate::redact(&host.address)),
+            Cell::new(if checks.is_empty() {
+                "-".to_string()
+            } else {
+                checks.join(",")
+            }),
+            Cell::new(format!("{}ms", host.timeout.as_millis())).set_alignment(CellAlignment::Right),
+            Cell::new(host.count).set_alignment(CellAlignment::Right),
+            Cell::new(host.retries).set_alignment(CellAlignment::Right),
+            Cell::new(format!("{}s", host.interval)).set_alignment(CellAlignment::Right),
+        ];
+        if show_via {
+            row.push(Cell::new(host.source.describe()));
+        }
+        if show_netns {
+            row.push(Cell::new(host.netns.as_deref().unwrap_or("-")));
+        }
+        table.add_row(row);
+    }
+
+    println!("{table}");
+    Ok(())
+}
+
+/// Command-line settings that shape the config of a check run, kept so the
+/// config can be reloaded with the same overrides
+#[derive(Default)]
+struct CheckSetup {
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    source: SourceBinding,
+    netns: Vec<String>,
+    interval: Option<u64>,
+    filter: HostFilter,
+    from: Option<ImportSource>,
+    /// Hosts from the command line, checked instead of the configured ones
+    adhoc: Option<IndexMap<String, HostEntry>>,
+    jobs: Option<usize>,
+    rate: Option<u32>,
+    deadline: Option<Duration>,
+}
+
+impl CheckSetup {
+    /// Load the config and apply the profile, overrides and filter
+    ///
+    /// `strict` fails on a broken config file instead of falling back to the next one.
+    fn load(&self, strict: bool) -> Result<Config> {
+        let mut config = match strict {
+            true => Config::load_strict(self.config_path.as_ref()),
+            false => Config::load(self.config_path.as_ref()),
+        }
+        .context("Failed to load configuration")?;
+        select_profile(&mut config, self.profile.clone())?;
+        if let Some(from) = self.from {
+            config.replace_hosts(import::import(from, None, false)?)?;
+        }
+        if let Some(ref hosts) = self.adhoc {
+            config.replace_hosts(hosts.clone())?;
+        }
+        config.set_source_override(self.source.clone());
+        config.set_netns_override(self.netns.clone());
+        config.set_interval_override(self.interval);
+        config.set_filter(self.filter.clone());
+        config.set_run_overrides(self.jobs, self.rate, self.deadline);
+        Ok(config)
+    }
+}
+
+/// Watch the files and fragment directories `config` was loaded from, if any
+fn watch_config(config: &Config) -> Option<reload::ConfigWatcher> {
+    if config.files().is_empty() && config.fragment_dirs().is_empty() {
+        return None;
+    }
+    reload::ConfigWatcher::new(config.files(), config.fragment_dirs())
+        .inspect_err(|e| log::warn!("Config changes won't be picked up: {:#}", e))
+        .ok()
+}
+
+/// Reload the config for watch mode, keeping `checker`'s clients unless the name servers changed
+///
+/// A kept checker forgets the hosts that are gone from the new config and takes on its limits.
+fn reload_checker(setup: &CheckSetup, checker: &check::Checker, sequential: bool) -> Result<(Config, check::Checker)> {
+    let config = setup.load(true)?;
+    if config.nameservers != checker.nameservers() {
+        let checker = check::Checker::builder()
+            .config(&config)
+            .sequential(sequential)
+            .build()?;
+        return Ok((config, checker));
+    }
+    let names: Vec<String> = config.hosts().into_iter().map(|h| h.name).collect();
+    checker.retain_hosts(&names);
+    let checker = checker.with_limits(config.limits());
+    Ok((config, checker))
+}
 
-    match interval {
+/// Run check command with optional watch mode
+///
+/// In watch mode the config files are watched and reloaded between cycles;
+/// a config that fails to load is reported and the previous one kept.
+async fn run_check_with_watch(
+    setup: &CheckSetup,
+    sequential: bool,
+    watch: Option<u64>,
+    output: CheckOutput,
+) -> Result<()> {
+    let mut config = setup.load(false)?;
+    match watch {
         None => {
             // Single run mode
-            let success = cmd_check(config, sequential).await?;
+            let success = match output {
+                CheckOutput::Report => cmd_check(&config, sequential).await?,
+                CheckOutput::Stream => cmd_check_stream(&config, sequential).await?,
+                CheckOutput::Ndjson => cmd_check_ndjson(&config, sequential).await?,
+            };
             if !success {
                 std::process::exit(1);
             }
         }
-        Some(seconds) => {
-            // Watch mode - true fixed interval from cycle start
-            let interval_duration = Duration::from_secs(seconds);
+        Some(_) => {
+            let mut watcher = watch_config(&config);
+            // One checker for the whole session, so sockets, DNS answers and ping sessions carry over
+            let mut checker = check::Checker::builder()
+                .config(&config)
+                .sequential(sequential)
+                .build()?;
+            let mut cache = ResultCache::new();
+            let mut reload_error: Option<String> = None;
+            let mut reloaded = false;
 
             let mut first = true;
             loop {
                 let cycle_start = Instant::now();
+                // True fixed interval from cycle start, ticking at the shortest
+                // host interval so every host is re-checked on time
+                let seconds = config
+                    .hosts()
+                    .iter()
+                    .map(|h| h.interval)
+                    .min()
+                    .unwrap_or(config.interval);
+                let interval_duration = Duration::from_secs(seconds);
 
                 // Separator between iterations (skip on first)
                 if !first {
@@ -277,25 +1072,61 @@ async fn run_check_with_watch(config: &Config, sequential: bool, watch: Option<u
                 first = false;
 
                 let now = chrono::Local::now();
+                let profile = config.profile().map(|p| format!(", profile {}", p)).unwrap_or_default();
                 println!(
-                    "{} [{}] (every {}s)\n",
+                    "{} [{}] (every {}s{})\n",
                     "cxn".cyan().bold(),
                     now.format("%H:%M:%S"),
-                    seconds
+                    seconds,
+                    profile
                 );
+                if let Some(ref error) = reload_error {
+                    let banner = "⚠ Config reload failed, still using the previous config:";
+                    println!("{} {}", banner.yellow().bold(), error);
+                    println!();
+                } else if std::mem::take(&mut reloaded) {
+                    println!("{}\n", "Config reloaded".green());
+                }
 
                 // Run the compact check
-                let _ = cmd_check_compact(config, sequential).await?;
+                let _ = cmd_check_compact(&config, &checker, &mut cache, cycle_start, interval_duration).await?;
 
                 // Calculate remaining time in interval
                 let elapsed = cycle_start.elapsed();
                 let remaining = interval_duration.saturating_sub(elapsed);
 
-                // Wait for remaining interval or Ctrl+C
+                // Wait for remaining interval, a config change or Ctrl+C
+                let config_changed = async {
+                    match watcher.as_mut() {
+                        Some(watcher) => watcher.changed().await,
+                        None => std::future::pending().await,
+                    }
+                };
                 tokio::select! {
                     _ = tokio::time::sleep(remaining) => {
                         // Continue to next iteration
                     }
+                    _ = config_changed => {
+                        match reload_checker(setup, &checker, sequential) {
+                            Ok((new, new_checker)) => {
+                                info!("Config reloaded");
+                                // Keep results of hosts that still exist so they aren't re-probed early
+                                let names: Vec<String> = new.hosts().into_iter().map(|h| h.name).collect();
+                                cache.retain(|name, _| names.contains(name));
+                                checker = new_checker;
+                                if new.files() != config.files() {
+                                    watcher = watch_config(&new);
+                                }
+                                config = new;
+                                reload_error = None;
+                                reloaded = true;
+                            }
+                            Err(e) => {
+                                log::warn!("Config reload failed: {:#}", e);
+                                reload_error = Some(format!("{:#}", e));
+                            }
+                        }
+                    }
                     _ = signal::ctrl_c() => {
                         println!("\n\n{}", "Watch mode stopped.".yellow());
                         break;
@@ -318,25 +1149,296 @@ async fn main() -> Result<()> {
 
     info!("Starting with config from: {:?}", cli.config);
 
+    let source = SourceBinding {
+        interface: cli.interface.clone(),
+        address: cli.source,
+    };
+
+    // Single-target commands run entirely inside one namespace; check can fan out
+    let ns = match cli.command {
+        Some(Commands::Check { .. }) | Some(Commands::Config { .. }) | Some(Commands::Import { .. }) | None => {
+            netns::CURRENT.to_string()
+        }
+        _ => match cli.netns.as_slice() {
+            [] => netns::CURRENT.to_string(),
+            [ns] => ns.clone(),
+            _ => eyre::bail!("--netns can only be repeated for the check command"),
+        },
+    };
+
     // Dispatch to the appropriate command
     match cli.command {
-        Some(Commands::Ping { host, count, timeout }) => {
-            cmd_ping(&host, count, timeout).await?;
+        Some(Commands::Ping {
+            host,
+            count,
+            timeout,
+            size,
+            pattern,
+            ttl,
+            dont_fragment,
+            dscp,
+        }) => {
+            if size > ping::MAX_PAYLOAD_SIZE {
+                eyre::bail!("Payload size {} exceeds maximum of {}", size, ping::MAX_PAYLOAD_SIZE);
+            }
+            let options = ping::PingOptions {
+                size,
+                pattern: pattern
+                    .as_deref()
+                    .map(ping::parse_pattern)
+                    .transpose()?
+                    .unwrap_or_default(),
+                ttl,
+                dont_fragment,
+                dscp,
+            };
+            netns::run_in(&ns, move || async move {
+                cmd_ping(&host, count, timeout, &options, &source).await
+            })
+            .await??;
+        }
+        Some(Commands::Mtu {
+            host,
+            max,
+            attempts,
+            timeout,
+            ipv4,
+            ipv6,
+        }) => {
+            netns::run_in(&ns, move || async move {
+                cmd_mtu(&host, max, attempts, timeout, ipv4, ipv6, &source).await
+            })
+            .await??;
+        }
+        Some(Commands::Trace {
+            host,
+            count,
+            max_hops,
+            timeout,
+            protocol,
+            port,
+            no_dns,
+            watch,
+        }) => {
+            let args = TraceArgs {
+                count,
+                max_hops,
+                timeout_ms: timeout,
+                protocol,
+                port,
+                no_dns,
+                watch,
+            };
+            netns::run_in(&ns, move || async move { cmd_trace(&host, args, &source).await }).await??;
         }
         Some(Commands::Dns { hostname, ipv6 }) => {
-            cmd_dns(&hostname, ipv6).await?;
+            netns::run_in(&ns, move || async move { cmd_dns(&hostname, ipv6, &source).await }).await??;
         }
-        Some(Commands::Check { sequential, watch }) => {
-            // Load configuration for check command
-            let config = Config::load(cli.config.as_ref()).context("Failed to load configuration")?;
-            run_check_with_watch(&config, sequential, watch).await?;
+        Some(Commands::Check {
+            sequential,
+            watch,
+            tags,
+            exclude_tags,
+            hosts,
+            from,
+            targets,
+            ping,
+            dns,
+            tcp,
+            jobs,
+            rate,
+            deadline,
+            stream,
+            ndjson,
+        }) => {
+            let adhoc = match targets.is_empty() {
+                true => None,
+                false => {
+                    let template = HostEntry {
+                        ping: ping || (!dns && tcp.is_empty()),
+                        dns,
+                        tcp,
+                        ..Default::default()
+                    };
+                    Some(import::adhoc_hosts(&targets, &template, io::stdin().lock())?)
+                }
+            };
+            let setup = CheckSetup {
+                config_path: cli.config,
+                profile: cli.profile,
+                source,
+                netns: cli.netns,
+                interval: watch_interval_override(watch),
+                filter: HostFilter {
+                    tags,
+                    exclude_tags,
+                    hosts,
+                },
+                from,
+                adhoc,
+                jobs: jobs.map(|j| j as usize),
+                rate,
+                deadline: deadline.map(Duration::from_secs),
+            };
+            let output = match (stream, ndjson) {
+                (_, true) => CheckOutput::Ndjson,
+                (true, false) => CheckOutput::Stream,
+                (false, false) => CheckOutput::Report,
+            };
+            run_check_with_watch(&setup, sequential, watch, output).await?;
         }
+        Some(Commands::Import {
+            from,
+            file,
+            ping,
+            group,
+            write,
+        }) => {
+            let hosts = import::import(from, file.as_deref(), ping)?;
+            cmd_import(cli.config.as_ref(), hosts, group.as_deref(), write)?;
+        }
+        Some(Commands::Config { command }) => match command {
+            ConfigCommands::Validate { strict } => cmd_config_validate(cli.config.as_ref(), strict),
+            ConfigCommands::Show { resolved } => {
+                let mut config = Config::load(cli.config.as_ref()).context("Failed to load configuration")?;
+                select_profile(&mut config, cli.profile)?;
+                config.set_source_override(source);
+                config.set_netns_override(cli.netns);
+                cmd_config_show(&config, resolved)?;
+            }
+            ConfigCommands::Schema => println!("{}", Config::json_schema()?),
+            ConfigCommands::Convert { to, output } => cmd_config_convert(cli.config.as_ref(), to, output.as_deref())?,
+            ConfigCommands::Init { force } => {
+                let path = cli
+                    .config
+                    .or_else(Config::default_path)
+                    .ok_or_else(|| eyre::eyre!("No config directory found; pass -c <path>"))?;
+                config_edit::init(&path, force)?;
+                println!("Wrote {}", path.display());
+            }
+            ConfigCommands::Add {
+                name,
+                address,
+                ping,
+                dns,
+                tcp,
+                group,
+                tags,
+            } => {
+                let entry = HostEntry {
+                    address,
+                    ping,
+                    dns,
+                    tcp,
+                    tags,
+                    ..Default::default()
+                };
+                cmd_config_add(cli.config.as_ref(), &name, group.as_deref(), &entry)?;
+            }
+            ConfigCommands::Remove { name } => cmd_config_remove(cli.config.as_ref(), &name)?,
+        },
         None => {
             // Default: run check command with parallel execution (no watch)
-            let config = Config::load(cli.config.as_ref()).context("Failed to load configuration")?;
-            run_check_with_watch(&config, false, None).await?;
+            let setup = CheckSetup {
+                config_path: cli.config,
+                profile: cli.profile,
+                source,
+                netns: cli.netns,
+                ..Default::default()
+            };
+            run_check_with_watch(&setup, false, None, CheckOutput::Report).await?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cxn::fake::{Echo, FakePinger, FakeResolver};
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_cycles_recheck_hosts_on_their_interval() {
+        let yaml = r#"
+interval: 1
+hosts:
+  Fast:
+    address: "10.0.0.1"
+    ping: true
+  Slow:
+    address: "10.0.0.2"
+    ping: true
+    interval: 3
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let pinger = Arc::new(FakePinger::new());
+        pinger.reply("10.0.0.1".parse().unwrap(), Echo::Reply(Duration::from_millis(5)));
+        pinger.reply("10.0.0.2".parse().unwrap(), Echo::Reply(Duration::from_millis(400)));
+        let checker = check::Checker::builder()
+            .pinger(pinger.clone())
+            .resolver(Arc::new(FakeResolver::new()))
+            .build()
+            .unwrap();
+
+        // Cycles start a tick apart however long their checks take, as in run_check_with_watch
+        let tick = Duration::from_secs(1);
+        let mut cache = ResultCache::new();
+        for _ in 0..4 {
+            let cycle_start = Instant::now();
+            cmd_check_compact(&config, &checker, &mut cache, cycle_start, tick)
+                .await
+                .unwrap();
+            tokio::time::sleep(tick.saturating_sub(cycle_start.elapsed())).await;
+        }
+        assert_eq!(pinger.pinged("Fast").len(), 4);
+        assert_eq!(pinger.pinged("Slow").len(), 2);
+
+        // Between its checks the slow host shows its cached result
+        let (checked, result) = &cache["Slow"];
+        assert_eq!(checked.elapsed(), Duration::from_secs(1));
+        assert!(result.is_success());
+
+        // A host that stops answering shows as down on its next check
+        pinger.reply("10.0.0.1".parse().unwrap(), Echo::Lost);
+        cmd_check_compact(&config, &checker, &mut cache, Instant::now(), tick)
+            .await
+            .unwrap();
+        assert!(!cache["Fast"].1.is_success());
+        assert!(cache["Slow"].1.is_success());
+    }
+
+    #[tokio::test]
+    async fn test_summary_counts_check_less_dependents_of_down_parent() {
+        let yaml = r#"
+hosts:
+  Router:
+    address: "10.0.0.1"
+    ping: true
+  Printer:
+    address: "10.0.0.2"
+    depends_on: [Router]
+  Scanner:
+    address: "10.0.0.3"
+    depends_on: [Router]
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let checker = check::Checker::builder()
+            .pinger(Arc::new(FakePinger::new()))
+            .resolver(Arc::new(FakeResolver::new()))
+            .build()
+            .unwrap();
+
+        let results = checker.run(&config.hosts()).await;
+        let tally = check::Tally::of(&results);
+        assert_eq!(
+            (tally.ok, tally.failed, tally.unreachable, tally.not_checked),
+            (0, 1, 2, 0)
+        );
+        assert_eq!(tally.checked(), 3);
+        assert!(!tally.all_ok());
+        let summary = format_summary(&tally, Duration::ZERO);
+        assert!(summary.starts_with("Summary: 0/3 hosts OK, 1 "), "{}", summary);
+    }
+}
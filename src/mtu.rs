@@ -0,0 +1,337 @@
+use crate::ping::{self, IcmpError, PingOptions};
+use crate::source::SourceBinding;
+use colored::*;
+use std::net::IpAddr;
+use std::time::Duration;
+use surge_ping::Client;
+
+/// IPv4 header (20) + ICMP header (8)
+const IPV4_OVERHEAD: usize = 28;
+
+/// IPv6 header (40) + ICMPv6 header (8)
+const IPV6_OVERHEAD: usize = 48;
+
+/// Smallest MTU every IPv4 link must support
+pub const IPV4_MIN_MTU: usize = 68;
+
+/// Largest MTU an IP packet's 16-bit total length can describe
+pub const MAX_MTU: usize = 65535;
+
+/// Smallest MTU every IPv6 link must support
+const IPV6_MIN_MTU: usize = 1280;
+
+/// Header bytes added on top of the ICMP payload for the address family
+pub fn overhead(address: IpAddr) -> usize {
+    if address.is_ipv6() {
+        IPV6_OVERHEAD
+    } else {
+        IPV4_OVERHEAD
+    }
+}
+
+/// Minimum link MTU for the address family
+pub fn min_mtu(address: IpAddr) -> usize {
+    if address.is_ipv6() { IPV6_MIN_MTU } else { IPV4_MIN_MTU }
+}
+
+/// A single DF probe sent during discovery
+#[derive(Debug, Clone)]
+pub struct MtuProbe {
+    /// Total packet size (payload + headers)
+    pub mtu: usize,
+    /// Whether an echo reply came back
+    pub passed: bool,
+    /// Error message if the probe failed
+    pub error: Option<String>,
+}
+
+/// Result of path MTU discovery for one address
+#[derive(Debug, Clone)]
+pub struct MtuResult {
+    /// The address that was probed
+    pub address: IpAddr,
+    /// Largest packet size that passed with DF set
+    pub path_mtu: Option<usize>,
+    /// Probes in the order they were sent
+    pub probes: Vec<MtuProbe>,
+    /// Fragmentation-needed / packet-too-big messages received while probing
+    pub frag_needed: Vec<IcmpError>,
+    /// Error message if discovery could not run
+    pub error: Option<String>,
+}
+
+impl MtuResult {
+    fn failure(address: IpAddr, error: String) -> Self {
+        Self {
+            address,
+            path_mtu: None,
+            probes: vec![],
+            frag_needed: vec![],
+            error: Some(error),
+        }
+    }
+
+    /// Format detailed output for the mtu subcommand
+    pub fn format(&self) -> String {
+        let mut output = vec![format!("MTU {}", self.address)];
+
+        for probe in &self.probes {
+            if probe.passed {
+                output.push(format!("  {} {} bytes", "✓".green(), probe.mtu));
+            } else {
+                let err_str = probe.error.as_deref().unwrap_or("no reply");
+                output.push(format!("  {} {} bytes: {}", "✗".red(), probe.mtu, err_str));
+            }
+        }
+
+        output.push(String::new());
+        match (self.path_mtu, &self.error) {
+            (_, Some(err)) => output.push(format!("{}: {}", "Error".red(), err)),
+            (Some(mtu), None) => output.push(format!(
+                "Path MTU: {} ({} byte payload + {} byte header)",
+                mtu.to_string().green(),
+                mtu - overhead(self.address),
+                overhead(self.address)
+            )),
+            (None, None) => output.push(format!("Path MTU: {}", "unknown".red())),
+        }
+
+        for err in &self.frag_needed {
+            output.push(format!(
+                "  frag-needed from {} (next-hop mtu {})",
+                err.source(),
+                err.info
+            ));
+        }
+
+        output.join("\n")
+    }
+}
+
+/// Result of a per-host `mtu_min` assertion in `cxn check`
+#[derive(Debug, Clone)]
+pub struct MtuCheckResult {
+    /// Required packet size
+    pub mtu_min: usize,
+    /// Whether a DF packet of `mtu_min` bytes got through
+    pub success: bool,
+    /// Error message if failed
+    pub error: Option<String>,
+}
+
+impl MtuCheckResult {
+    /// Format the result for display
+    pub fn format(&self) -> String {
+        if self.success {
+            format!("  {} mtu:  >= {}", "✓".green(), self.mtu_min)
+        } else {
+            let err_str = self.error.as_deref().unwrap_or("unknown error");
+            format!("  {} mtu:  < {} ({})", "✗".red(), self.mtu_min, err_str)
+        }
+    }
+}
+
+/// Create a DF client with ICMP error reporting enabled
+fn create_df_client(address: IpAddr, options: &PingOptions, source: &SourceBinding) -> eyre::Result<Client> {
+    let options = PingOptions {
+        dont_fragment: true,
+        ..options.clone()
+    };
+    let client = ping::create_client_with(address, &options, source)?;
+    ping::enable_error_queue(&client, address)?;
+    Ok(client)
+}
+
+/// Send one DF probe of `mtu` total bytes, retrying up to `attempts` times
+async fn probe(
+    client: &Client,
+    address: IpAddr,
+    mtu: usize,
+    timeout: Duration,
+    attempts: u32,
+    options: &PingOptions,
+) -> (MtuProbe, Vec<IcmpError>) {
+    let options = PingOptions {
+        size: mtu - overhead(address),
+        dont_fragment: true,
+        ..options.clone()
+    };
+    let result = ping::ping_host(client, "", address, timeout, attempts, &options).await;
+    let frag_needed: Vec<IcmpError> = ping::read_error_queue(client)
+        .into_iter()
+        .filter(|e| e.is_frag_needed())
+        .collect();
+
+    let error = if result.success {
+        None
+    } else if let Some(err) = frag_needed.iter().find(|e| e.from.is_some()) {
+        Some(format!("frag-needed from {} (mtu {})", err.source(), err.info))
+    } else {
+        result.error
+    };
+
+    let probe = MtuProbe {
+        mtu,
+        passed: result.success,
+        error,
+    };
+    (probe, frag_needed)
+}
+
+/// Discover the path MTU to `address` by binary-searching DF probe sizes
+///
+/// Searches between the family minimum and `max_mtu`. Fragmentation-needed
+/// messages shrink the upper bound directly when they report a smaller MTU.
+pub async fn discover_path_mtu(
+    address: IpAddr,
+    max_mtu: usize,
+    timeout: Duration,
+    attempts: u32,
+    options: &PingOptions,
+    source: &SourceBinding,
+) -> MtuResult {
+    let client = match create_df_client(address, options, source) {
+        Ok(client) => client,
+        Err(e) => return MtuResult::failure(address, format!("{:#}", e)),
+    };
+
+    let mut probes = Vec::new();
+    let mut frag_needed: Vec<IcmpError> = Vec::new();
+    let mut record = |probe: MtuProbe, errors: Vec<IcmpError>, probes: &mut Vec<MtuProbe>| {
+        for err in errors {
+            if !frag_needed.contains(&err) {
+                frag_needed.push(err);
+            }
+        }
+        let passed = probe.passed;
+        probes.push(probe);
+        passed
+    };
+
+    let mut lo = min_mtu(address);
+    let mut hi = max_mtu.max(lo);
+
+    // The minimum must pass or the host is simply unreachable
+    let (p, errors) = probe(&client, address, lo, timeout, attempts, options).await;
+    if !record(p, errors, &mut probes) {
+        return MtuResult {
+            address,
+            path_mtu: None,
+            probes,
+            frag_needed,
+            error: Some(format!("no reply at minimum MTU {}", lo)),
+        };
+    }
+
+    // Try the maximum first since most paths pass at full size, then any
+    // MTU reported by a frag-needed message, falling back to bisection
+    let mut next = Some(hi);
+    while lo < hi {
+        let size = next.take().unwrap_or_else(|| lo + (hi - lo).div_ceil(2));
+        let (p, errors) = probe(&client, address, size, timeout, attempts, options).await;
+        let hinted = errors.iter().filter(|e| e.info > 0).map(|e| e.info as usize).min();
+        if record(p, errors, &mut probes) {
+            lo = size;
+        } else {
+            hi = size - 1;
+            if let Some(hint) = hinted
+                && hint > lo
+                && hint <= hi
+            {
+                hi = hint;
+                next = Some(hint);
+            }
+        }
+    }
+
+    MtuResult {
+        address,
+        path_mtu: Some(lo),
+        probes,
+        frag_needed,
+        error: None,
+    }
+}
+
+/// Verify that a DF packet of `mtu_min` bytes reaches `address`
+pub async fn check_min_mtu(
+    address: IpAddr,
+    mtu_min: usize,
+    timeout: Duration,
+    options: &PingOptions,
+    source: &SourceBinding,
+) -> MtuCheckResult {
+    let client = match create_df_client(address, options, source) {
+        Ok(client) => client,
+        Err(e) => {
+            return MtuCheckResult {
+                mtu_min,
+                success: false,
+                error: Some(format!("{:#}", e)),
+            };
+        }
+    };
+
+    let (probe, _) = probe(&client, address, mtu_min, timeout, 1, options).await;
+    MtuCheckResult {
+        mtu_min,
+        success: probe.passed,
+        error: probe.error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_overhead_by_family() {
+        assert_eq!(overhead("10.0.0.1".parse().unwrap()), 28);
+        assert_eq!(overhead("2001:db8::1".parse().unwrap()), 48);
+        assert_eq!(min_mtu("2001:db8::1".parse().unwrap()), 1280);
+    }
+
+    #[test]
+    fn test_mtu_result_format() {
+        let result = MtuResult {
+            address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            path_mtu: Some(1400),
+            probes: vec![
+                MtuProbe {
+                    mtu: 1500,
+                    passed: false,
+                    error: Some("frag-needed from 10.0.0.254 (mtu 1400)".to_string()),
+                },
+                MtuProbe {
+                    mtu: 1400,
+                    passed: true,
+                    error: None,
+                },
+            ],
+            frag_needed: vec![IcmpError {
+                from: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254))),
+                errno: 90,
+                icmp_type: 3,
+                icmp_code: 4,
+                info: 1400,
+            }],
+            error: None,
+        };
+
+        let output = result.format();
+        assert!(output.contains("MTU 10.0.0.1"));
+        assert!(output.contains("1372 byte payload"));
+        assert!(output.contains("frag-needed from 10.0.0.254 (next-hop mtu 1400)"));
+    }
+
+    #[test]
+    fn test_mtu_check_result_format() {
+        let result = MtuCheckResult {
+            mtu_min: 1400,
+            success: false,
+            error: Some("timeout after 1000ms".to_string()),
+        };
+        assert!(result.format().contains("< 1400"));
+    }
+}
@@ -0,0 +1,102 @@
+use eyre::{Context, Result};
+use std::future::Future;
+use std::path::PathBuf;
+
+/// Directory where `ip netns add` creates named namespaces
+const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+/// Namespace name meaning "stay in the namespace cxn was started in"
+pub const CURRENT: &str = "current";
+
+/// Map a namespace name or path to the file to open
+///
+/// Bare names refer to `/var/run/netns/<name>`; anything containing a `/` is
+/// taken as a path (e.g. `/proc/1234/ns/net`).
+pub fn resolve_path(netns: &str) -> PathBuf {
+    if netns.contains('/') {
+        PathBuf::from(netns)
+    } else {
+        PathBuf::from(NETNS_RUN_DIR).join(netns)
+    }
+}
+
+/// Run a future on a dedicated thread that has entered the given network namespace
+///
+/// The thread drives its own single-threaded runtime, so every socket created
+/// by the future (ping clients, resolver connections, trace probes) lives in
+/// that namespace. Sockets keep their namespace for their whole lifetime.
+pub async fn run_in<F, Fut, T>(netns: &str, f: F) -> Result<T>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = T>,
+    T: Send + 'static,
+{
+    if netns == CURRENT {
+        return Ok(f().await);
+    }
+
+    let path = resolve_path(netns);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    std::thread::Builder::new()
+        .name(format!("netns-{}", netns))
+        .spawn(move || {
+            let result = enter(&path).and_then(|()| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .context("Failed to start namespace runtime")?;
+                Ok(runtime.block_on(f()))
+            });
+            let _ = tx.send(result);
+        })
+        .context("Failed to spawn namespace thread")?;
+
+    rx.await
+        .map_err(|_| eyre::eyre!("namespace thread for {} exited unexpectedly", netns))?
+}
+
+/// Switch the calling thread into the network namespace at `path`
+#[cfg(target_os = "linux")]
+fn enter(path: &std::path::Path) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::File::open(path).context(format!("Failed to open network namespace {}", path.display()))?;
+    // SAFETY: file is an open namespace descriptor; setns only affects the calling thread
+    let rc = unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error()).context(format!(
+            "Failed to enter network namespace {} (need CAP_SYS_ADMIN)",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enter(path: &std::path::Path) -> Result<()> {
+    eyre::bail!("network namespaces are only supported on Linux ({})", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(resolve_path("blue"), PathBuf::from("/var/run/netns/blue"));
+        assert_eq!(resolve_path("/proc/42/ns/net"), PathBuf::from("/proc/42/ns/net"));
+    }
+
+    #[tokio::test]
+    async fn test_run_in_current_namespace() {
+        let value = run_in(CURRENT, || async { 42 }).await.unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[tokio::test]
+    async fn test_run_in_missing_namespace() {
+        let result = run_in("cxn-test-does-not-exist", || async { 42 }).await;
+        assert!(result.is_err());
+    }
+}
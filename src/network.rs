@@ -0,0 +1,188 @@
+//! The network access behind host checks, swappable for fakes in tests
+//!
+//! A [`Checker`](crate::Checker) pings through a [`Pinger`] and looks names up
+//! through a [`Resolver`]. By default these are [`SystemPinger`] and
+//! [`SystemResolver`], which use real sockets; `cxn::fake` (with the `testing`
+//! feature) has scriptable stand-ins that need no network and run under
+//! tokio's paused clock.
+
+use crate::config::HostConfig;
+use crate::dns::{self, DnsResult};
+use crate::ping::{self, HostPinger, PingOptions, PingResult};
+use crate::probe::BoxFuture;
+use crate::source::SourceBinding;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use surge_ping::Client as PingClient;
+use tokio::time::Instant;
+
+/// Sends a host's ping check
+pub trait Pinger: Send + Sync {
+    /// Ping `host` at `ip` with its configured count, timeout and packet options
+    fn ping<'a>(&'a self, host: &'a HostConfig, ip: IpAddr) -> BoxFuture<'a, PingResult>;
+
+    /// Forget state kept for hosts not in `names`, e.g. after a config reload
+    fn retain_hosts(&self, _names: &[String]) {}
+}
+
+/// Looks up a host's name
+pub trait Resolver: Send + Sync {
+    /// Resolve `host.address` (IPv4 only unless `include_ipv6`), also returning
+    /// when a successful answer's TTL runs out
+    fn resolve<'a>(&'a self, host: &'a HostConfig, include_ipv6: bool) -> BoxFuture<'a, (DnsResult, Option<Instant>)>;
+}
+
+/// Pings over ICMP sockets, continuing each host's session across runs
+pub struct SystemPinger {
+    client: PingClient,
+    /// Clients with socket-level options, shared by hosts that need the same ones
+    dedicated: Mutex<Vec<DedicatedClient>>,
+    /// Ping sessions by host name
+    sessions: Mutex<HashMap<String, PingSession>>,
+}
+
+/// Client for one address family (true for IPv6), packet options and source binding
+type DedicatedClient = (bool, PingOptions, SourceBinding, Arc<PingClient>);
+
+/// A host's ping session, with the address and options it was started for
+struct PingSession {
+    address: IpAddr,
+    options: PingOptions,
+    source: SourceBinding,
+    pinger: Arc<tokio::sync::Mutex<HostPinger>>,
+}
+
+impl SystemPinger {
+    /// Create the shared ICMP socket
+    pub fn new() -> eyre::Result<Self> {
+        Ok(Self {
+            client: ping::create_client()?,
+            dedicated: Mutex::new(Vec::new()),
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The ping session for a host, started anew if its address or options changed
+    async fn session(&self, host: &HostConfig, ip: IpAddr) -> eyre::Result<Arc<tokio::sync::Mutex<HostPinger>>> {
+        if let Some(session) = self.sessions.lock().unwrap().get(&host.name)
+            && session.address == ip
+            && session.options == host.ping_options
+            && session.source == host.source
+        {
+            return Ok(session.pinger.clone());
+        }
+
+        let pinger = if host.ping_options.is_default() && host.source.is_default() {
+            HostPinger::new(&self.client, ip).await
+        } else {
+            HostPinger::with_client(self.dedicated_client(host, ip)?, ip).await
+        };
+        let pinger = Arc::new(tokio::sync::Mutex::new(pinger));
+        let session = PingSession {
+            address: ip,
+            options: host.ping_options.clone(),
+            source: host.source.clone(),
+            pinger: pinger.clone(),
+        };
+        self.sessions.lock().unwrap().insert(host.name.clone(), session);
+        Ok(pinger)
+    }
+
+    /// Client for a host's socket-level options (TTL, DF, DSCP, binding), opening
+    /// one only if no other host uses the same ones, so each combination costs one raw socket
+    fn dedicated_client(&self, host: &HostConfig, ip: IpAddr) -> eyre::Result<Arc<PingClient>> {
+        let mut clients = self.dedicated.lock().unwrap();
+        if let Some((_, _, _, client)) = clients.iter().find(|(v6, options, source, _)| {
+            *v6 == ip.is_ipv6() && *options == host.ping_options && *source == host.source
+        }) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(ping::create_client_with(ip, &host.ping_options, &host.source)?);
+        clients.push((
+            ip.is_ipv6(),
+            host.ping_options.clone(),
+            host.source.clone(),
+            client.clone(),
+        ));
+        Ok(client)
+    }
+}
+
+impl Pinger for SystemPinger {
+    fn ping<'a>(&'a self, host: &'a HostConfig, ip: IpAddr) -> BoxFuture<'a, PingResult> {
+        Box::pin(async move {
+            let pinger = match self.session(host, ip).await {
+                Ok(pinger) => pinger,
+                Err(e) => return PingResult::failure(host.name.clone(), ip, format!("{:#}", e)),
+            };
+            let mut pinger = pinger.lock().await;
+            pinger
+                .ping(&host.name, host.timeout, host.count, &host.ping_options)
+                .await
+        })
+    }
+
+    fn retain_hosts(&self, names: &[String]) {
+        self.sessions.lock().unwrap().retain(|name, _| names.contains(name));
+        // Close dedicated clients no remaining session sends through
+        self.dedicated
+            .lock()
+            .unwrap()
+            .retain(|(_, _, _, client)| Arc::strong_count(client) > 1);
+    }
+}
+
+/// Resolves through the system's or the configured name servers, with
+/// separate resolvers for hosts bound to an interface or source address
+pub struct SystemResolver {
+    shared: Arc<dns::DnsResolver>,
+    /// Resolvers of hosts bound to an interface or source address
+    bound: Mutex<Vec<BoundResolver>>,
+}
+
+/// Resolver for hosts with a given source binding and name servers
+type BoundResolver = (SourceBinding, Vec<IpAddr>, Arc<dns::DnsResolver>);
+
+impl SystemResolver {
+    /// Create a resolver querying `nameservers` (the system's if empty)
+    pub fn new(nameservers: &[IpAddr]) -> eyre::Result<Self> {
+        Ok(Self {
+            shared: Arc::new(dns::create_resolver_for(&SourceBinding::default(), nameservers)?),
+            bound: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Resolver for a host: the shared one, or one bound to the host's source
+    fn resolver_for(&self, host: &HostConfig) -> Result<Arc<dns::DnsResolver>, String> {
+        if host.source.is_default() {
+            return Ok(self.shared.clone());
+        }
+        let mut resolvers = self.bound.lock().unwrap();
+        if let Some((_, _, resolver)) = resolvers
+            .iter()
+            .find(|(source, servers, _)| *source == host.source && *servers == host.nameservers)
+        {
+            return Ok(resolver.clone());
+        }
+        let resolver =
+            Arc::new(dns::create_resolver_for(&host.source, &host.nameservers).map_err(|e| format!("{:#}", e))?);
+        resolvers.push((host.source.clone(), host.nameservers.clone(), resolver.clone()));
+        Ok(resolver)
+    }
+}
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a HostConfig, include_ipv6: bool) -> BoxFuture<'a, (DnsResult, Option<Instant>)> {
+        Box::pin(async move {
+            match self.resolver_for(host) {
+                Ok(resolver) => {
+                    let (result, valid_until) =
+                        dns::resolve_dns_expiring(&resolver, &host.name, &host.address, include_ipv6).await;
+                    (result, valid_until.map(Instant::from_std))
+                }
+                Err(e) => (DnsResult::failure(host.name.clone(), host.address.clone(), e), None),
+            }
+        })
+    }
+}
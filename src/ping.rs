@@ -1,9 +1,102 @@
+use crate::source::SourceBinding;
 use colored::*;
 use eyre::{Context, Result};
 use rand::random;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
-use surge_ping::{Client, Config as PingConfig, PingIdentifier, PingSequence};
+use surge_ping::{Client, Config as PingConfig, ICMP, PingIdentifier, PingSequence, Pinger};
+
+/// Standard ping payload size in bytes (excluding the 8-byte ICMP header)
+pub const DEFAULT_PAYLOAD_SIZE: usize = 56;
+
+/// Largest ICMP payload that fits in an IPv4 datagram
+pub const MAX_PAYLOAD_SIZE: usize = 65507;
+
+/// Size of the ICMP echo header preceding the payload
+const ICMP_HEADER_SIZE: usize = 8;
+
+/// ICMP packet parameters applied to echo requests and the socket they are sent on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingOptions {
+    /// Payload size in bytes (excluding the ICMP header)
+    pub size: usize,
+    /// Byte pattern repeated to fill the payload (zeros if empty)
+    pub pattern: Vec<u8>,
+    /// IP TTL / IPv6 hop limit
+    pub ttl: Option<u32>,
+    /// Set the don't-fragment bit (prohibit fragmentation along the path)
+    pub dont_fragment: bool,
+    /// DSCP code point (0-63) written to the ToS / traffic class field
+    pub dscp: Option<u8>,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_PAYLOAD_SIZE,
+            pattern: Vec::new(),
+            ttl: None,
+            dont_fragment: false,
+            dscp: None,
+        }
+    }
+}
+
+impl PingOptions {
+    /// Check if these options match the defaults (shared client can be used)
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Build the echo request payload
+    pub fn payload(&self) -> Vec<u8> {
+        if self.pattern.is_empty() {
+            vec![0u8; self.size]
+        } else {
+            self.pattern.iter().copied().cycle().take(self.size).collect()
+        }
+    }
+
+    /// Short description for output headers, e.g. "1472 bytes, DF"
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("{} bytes", self.size)];
+        if self.dont_fragment {
+            parts.push("DF".to_string());
+        }
+        if let Some(ttl) = self.ttl {
+            parts.push(format!("ttl={}", ttl));
+        }
+        if let Some(dscp) = self.dscp {
+            parts.push(format!("dscp={}", dscp));
+        }
+        if !self.pattern.is_empty() {
+            parts.push(format!("pattern={}", format_pattern(&self.pattern)));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Parse a payload pattern given as hex bytes (e.g. "ff00" or "0xdead")
+pub fn parse_pattern(s: &str) -> Result<Vec<u8>> {
+    let hex = s.trim().trim_start_matches("0x");
+    if hex.is_empty() || !hex.len().is_multiple_of(2) || hex.len() > 32 {
+        eyre::bail!("pattern must be 1-16 hex bytes, got '{}'", s);
+    }
+    // Checked up front so the byte slicing below stays on char boundaries
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        eyre::bail!("invalid hex in pattern '{}'", s);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
+
+/// Format a payload pattern back into hex
+pub fn format_pattern(pattern: &[u8]) -> String {
+    pattern.iter().map(|b| format!("{:02x}", b)).collect()
+}
 
 /// Result of a ping operation
 #[derive(Debug, Clone)]
@@ -66,46 +159,353 @@ pub fn create_client() -> Result<Client> {
     Client::new(&PingConfig::default()).context("Failed to create ping client")
 }
 
+/// Create a ping client for the address family of `address` with the given packet options
+///
+/// TTL and source binding are applied through surge-ping; DF and DSCP are set
+/// directly on the socket.
+pub fn create_client_with(address: IpAddr, options: &PingOptions, source: &SourceBinding) -> Result<Client> {
+    let kind = if address.is_ipv6() { ICMP::V6 } else { ICMP::V4 };
+    let mut builder = PingConfig::builder().kind(kind);
+    if let Some(ttl) = options.ttl {
+        builder = builder.ttl(ttl);
+    }
+    if let Some(ref interface) = source.interface {
+        builder = builder.interface(interface);
+    }
+    if let Some(local) = source.local_address(address.is_ipv6())? {
+        builder = builder.bind(SocketAddr::new(local, 0));
+    }
+    let client = Client::new(&builder.build()).context("Failed to create ping client")?;
+    apply_socket_options(&client, address, options)?;
+    Ok(client)
+}
+
+/// Set DF and DSCP socket options that surge-ping does not expose
+#[cfg(target_os = "linux")]
+fn apply_socket_options(client: &Client, address: IpAddr, options: &PingOptions) -> Result<()> {
+    let fd = client.get_socket().get_native_sock();
+    let v6 = address.is_ipv6();
+
+    if options.dont_fragment {
+        if v6 {
+            set_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO)
+                .context("Failed to set IPV6_MTU_DISCOVER")?;
+            set_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_DONTFRAG, 1).context("Failed to set IPV6_DONTFRAG")?;
+        } else {
+            set_sockopt(fd, libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO)
+                .context("Failed to set IP_MTU_DISCOVER")?;
+        }
+    }
+
+    if let Some(dscp) = options.dscp {
+        let tos = (dscp as libc::c_int) << 2;
+        if v6 {
+            set_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos).context("Failed to set IPV6_TCLASS")?;
+        } else {
+            set_sockopt(fd, libc::IPPROTO_IP, libc::IP_TOS, tos).context("Failed to set IP_TOS")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_socket_options(_client: &Client, _address: IpAddr, options: &PingOptions) -> Result<()> {
+    if options.dont_fragment || options.dscp.is_some() {
+        eyre::bail!("DF and DSCP options are only supported on Linux");
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_sockopt(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: fd is a live socket owned by the client and value outlives the call
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// ICMP error delivered to a ping socket through the Linux socket error queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcmpError {
+    /// Router or host that generated the error (None if raised by the local stack)
+    pub from: Option<IpAddr>,
+    /// errno the error maps to (e.g. EMSGSIZE for fragmentation needed)
+    pub errno: i32,
+    /// ICMP type of the error message (0 for local errors)
+    pub icmp_type: u8,
+    /// ICMP code of the error message
+    pub icmp_code: u8,
+    /// Extra info; the next-hop MTU for fragmentation-needed errors
+    pub info: u32,
+}
+
+impl IcmpError {
+    /// Fragmentation needed / packet too big (info carries the MTU)
+    pub fn is_frag_needed(&self) -> bool {
+        self.errno == EMSGSIZE
+    }
+
+    /// TTL / hop limit expired in transit (ICMP type 11 / ICMPv6 type 3)
+    pub fn is_time_exceeded(&self) -> bool {
+        match self.from {
+            Some(IpAddr::V4(_)) => self.icmp_type == 11,
+            Some(IpAddr::V6(_)) => self.icmp_type == 3,
+            None => false,
+        }
+    }
+
+    /// Describe where the error originated
+    pub fn source(&self) -> String {
+        match self.from {
+            Some(addr) => addr.to_string(),
+            None => "local interface".to_string(),
+        }
+    }
+}
+
+const EMSGSIZE: i32 = 90;
+
+/// Enable delivery of ICMP errors (frag-needed, time-exceeded) to the client's error queue
+#[cfg(target_os = "linux")]
+pub fn enable_error_queue(client: &Client, address: IpAddr) -> Result<()> {
+    enable_error_queue_fd(client.get_socket().get_native_sock(), address.is_ipv6())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enable_error_queue(_client: &Client, _address: IpAddr) -> Result<()> {
+    eyre::bail!("ICMP error reporting is only supported on Linux")
+}
+
+/// Enable ICMP error delivery on any IP socket
+#[cfg(target_os = "linux")]
+pub fn enable_error_queue_fd(fd: std::os::fd::RawFd, v6: bool) -> Result<()> {
+    if v6 {
+        set_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVERR, 1).context("Failed to set IPV6_RECVERR")
+    } else {
+        set_sockopt(fd, libc::IPPROTO_IP, libc::IP_RECVERR, 1).context("Failed to set IP_RECVERR")
+    }
+}
+
+/// Set the IP TTL / IPv6 unicast hop limit on any IP socket
+#[cfg(target_os = "linux")]
+pub fn set_hop_limit_fd(fd: std::os::fd::RawFd, v6: bool, ttl: u32) -> Result<()> {
+    if v6 {
+        set_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, ttl as libc::c_int)
+            .context("Failed to set IPV6_UNICAST_HOPS")
+    } else {
+        set_sockopt(fd, libc::IPPROTO_IP, libc::IP_TTL, ttl as libc::c_int).context("Failed to set IP_TTL")
+    }
+}
+
+/// Wait until at least one ICMP error is queued on `fd`, then drain and return them
+#[cfg(target_os = "linux")]
+pub async fn wait_error_queue_fd(fd: std::os::fd::RawFd) -> std::io::Result<Vec<IcmpError>> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::Interest;
+    use tokio::io::unix::AsyncFd;
+
+    // Register a duplicate so the owner's own reactor registration is untouched
+    // SAFETY: dup returns a fresh descriptor we take ownership of
+    let dup = unsafe { libc::dup(fd) };
+    if dup < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let owned = unsafe { OwnedFd::from_raw_fd(dup) };
+    // SAFETY: the AsyncFd owns the duplicate, which stays open until it is dropped
+    let async_fd = unsafe { AsyncFd::register_with_interest(owned, Interest::ERROR)? };
+
+    loop {
+        let mut guard = async_fd.ready(Interest::ERROR).await?;
+        let errors = read_error_queue_fd(fd);
+        if !errors.is_empty() {
+            return Ok(errors);
+        }
+        guard.clear_ready();
+    }
+}
+
+/// Drain all pending ICMP errors from the client's error queue without blocking
+#[cfg(target_os = "linux")]
+pub fn read_error_queue(client: &Client) -> Vec<IcmpError> {
+    read_error_queue_fd(client.get_socket().get_native_sock())
+}
+
+/// Drain all pending ICMP errors from any IP socket without blocking
+#[cfg(target_os = "linux")]
+pub fn read_error_queue_fd(fd: std::os::fd::RawFd) -> Vec<IcmpError> {
+    let mut errors = Vec::new();
+
+    loop {
+        let mut data = [0u8; 2048];
+        let mut control = [0u8; 512];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        // SAFETY: msghdr is plain data; all pointers reference live stack buffers
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        // SAFETY: fd is a live socket owned by the client
+        let rc = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+        if rc < 0 {
+            break;
+        }
+
+        // SAFETY: cmsg traversal stays within msg_controllen as reported by the kernel
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let hdr = &*cmsg;
+                let is_recverr = (hdr.cmsg_level == libc::IPPROTO_IP && hdr.cmsg_type == libc::IP_RECVERR)
+                    || (hdr.cmsg_level == libc::IPPROTO_IPV6 && hdr.cmsg_type == libc::IPV6_RECVERR);
+                if is_recverr {
+                    let ee_ptr = libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err;
+                    let ee = std::ptr::read_unaligned(ee_ptr);
+                    let from = match ee.ee_origin {
+                        libc::SO_EE_ORIGIN_ICMP | libc::SO_EE_ORIGIN_ICMP6 => {
+                            sockaddr_to_ip(libc::SO_EE_OFFENDER(ee_ptr) as *const libc::sockaddr)
+                        }
+                        _ => None,
+                    };
+                    errors.push(IcmpError {
+                        from,
+                        errno: ee.ee_errno as i32,
+                        icmp_type: ee.ee_type,
+                        icmp_code: ee.ee_code,
+                        info: ee.ee_info,
+                    });
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_error_queue(_client: &Client) -> Vec<IcmpError> {
+    Vec::new()
+}
+
+/// Convert a kernel sockaddr into an IP address
+#[cfg(target_os = "linux")]
+unsafe fn sockaddr_to_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    // SAFETY: caller guarantees addr points at a sockaddr_in or sockaddr_in6 as tagged by sa_family
+    unsafe {
+        match (*addr).sa_family as libc::c_int {
+            libc::AF_INET => {
+                let sin = std::ptr::read_unaligned(addr as *const libc::sockaddr_in);
+                Some(IpAddr::V4(std::net::Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr))))
+            }
+            libc::AF_INET6 => {
+                let sin6 = std::ptr::read_unaligned(addr as *const libc::sockaddr_in6);
+                Some(IpAddr::V6(std::net::Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Ping a host and return the result
 ///
 /// Sends ICMP echo requests to the specified address and measures RTT.
 /// Returns the average RTT on success.
 #[allow(dead_code)] // Used in later phases
-pub async fn ping_host(client: &Client, name: &str, address: IpAddr, timeout: Duration, count: u32) -> PingResult {
-    let mut rtts = Vec::with_capacity(count as usize);
-    let mut last_error = None;
+pub async fn ping_host(
+    client: &Client,
+    name: &str,
+    address: IpAddr,
+    timeout: Duration,
+    count: u32,
+    options: &PingOptions,
+) -> PingResult {
+    HostPinger::new(client, address)
+        .await
+        .ping(name, timeout, count, options)
+        .await
+}
 
-    // Generate a random identifier for this ping session
-    let identifier = PingIdentifier(random());
+/// Echo session with one address, kept across checks of a host
+///
+/// The identifier is chosen once and sequence numbers carry on from one call
+/// to the next, so repeated checks look like one ongoing ping to the target.
+pub struct HostPinger {
+    pinger: Pinger,
+    /// Dedicated client the pinger sends through, shared behind an `Arc` since
+    /// dropping any clone of a surge-ping client shuts it down
+    _client: Option<Arc<Client>>,
+    next_sequence: u16,
+}
 
-    // Create pinger for this address
-    let mut pinger = client.pinger(address, identifier).await;
-    pinger.timeout(timeout);
+impl HostPinger {
+    /// Start a session through a shared client
+    pub async fn new(client: &Client, address: IpAddr) -> Self {
+        Self {
+            pinger: client.pinger(address, PingIdentifier(random())).await,
+            _client: None,
+            next_sequence: 0,
+        }
+    }
 
-    let payload = [0u8; 56]; // Standard ping payload size
+    /// Start a session through a dedicated client, e.g. one with socket-level options
+    pub async fn with_client(client: Arc<Client>, address: IpAddr) -> Self {
+        Self {
+            _client: Some(client.clone()),
+            ..Self::new(&client, address).await
+        }
+    }
 
-    for seq in 0..count {
-        match pinger.ping(PingSequence(seq as u16), &payload).await {
-            Ok((_, rtt)) => {
-                rtts.push(rtt);
-            }
-            Err(e) => {
-                last_error = Some(format_ping_error(&e, timeout));
+    /// Send `count` echo requests and return the average RTT of the replies
+    pub async fn ping(&mut self, name: &str, timeout: Duration, count: u32, options: &PingOptions) -> PingResult {
+        let mut rtts = Vec::with_capacity(count as usize);
+        let mut last_error = None;
+        let address = self.pinger.host;
+        self.pinger.timeout(timeout);
+
+        let payload = options.payload();
+
+        for _ in 0..count {
+            let sequence = PingSequence(self.next_sequence);
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+            match self.pinger.ping(sequence, &payload).await {
+                Ok((_, rtt)) => {
+                    rtts.push(rtt);
+                }
+                Err(e) => {
+                    last_error = Some(format_ping_error(&e, timeout));
+                }
             }
         }
-    }
 
-    if rtts.is_empty() {
-        // All pings failed
-        PingResult::failure(
-            name.to_string(),
-            address,
-            last_error.unwrap_or_else(|| "all pings failed".to_string()),
-        )
-    } else {
-        // Calculate average RTT
-        let avg_rtt = rtts.iter().sum::<Duration>() / rtts.len() as u32;
-        PingResult::success(name.to_string(), address, avg_rtt)
+        if rtts.is_empty() {
+            // All pings failed
+            PingResult::failure(
+                name.to_string(),
+                address,
+                last_error.unwrap_or_else(|| "all pings failed".to_string()),
+            )
+        } else {
+            // Calculate average RTT
+            let avg_rtt = rtts.iter().sum::<Duration>() / rtts.len() as u32;
+            PingResult::success(name.to_string(), address, avg_rtt)
+        }
     }
 }
 
@@ -123,6 +523,8 @@ fn format_ping_error(error: &surge_ping::SurgeError, timeout: Duration) -> Strin
                 "network unreachable".to_string()
             } else if io_err.raw_os_error() == Some(113) {
                 "no route to host".to_string()
+            } else if io_err.raw_os_error() == Some(EMSGSIZE) {
+                "message too long (exceeds path MTU)".to_string()
             } else {
                 format!("io error: {}", io_err)
             }
@@ -135,6 +537,7 @@ fn format_ping_error(error: &surge_ping::SurgeError, timeout: Duration) -> Strin
 #[allow(dead_code)] // Used in later phases
 pub struct DetailedPingResult {
     pub address: IpAddr,
+    pub options: PingOptions,
     pub results: Vec<(u16, Result<Duration, String>)>,
     pub packets_sent: u32,
     pub packets_received: u32,
@@ -144,13 +547,14 @@ pub struct DetailedPingResult {
 impl DetailedPingResult {
     /// Format detailed output similar to traditional ping command
     pub fn format(&self) -> String {
-        let mut output = vec![format!("PING {}", self.address)];
+        let mut output = vec![format!("PING {} ({})", self.address, self.options.describe())];
 
         for (seq, result) in &self.results {
             match result {
                 Ok(rtt) => {
                     output.push(format!(
-                        "  64 bytes: seq={} time={:.1}ms",
+                        "  {} bytes: seq={} time={:.1}ms",
+                        self.options.size + ICMP_HEADER_SIZE,
                         seq,
                         rtt.as_secs_f64() * 1000.0
                     ));
@@ -196,12 +600,18 @@ impl DetailedPingResult {
 
 /// Run detailed ping for the ping subcommand
 #[allow(dead_code)] // Used in later phases
-pub async fn ping_host_detailed(client: &Client, address: IpAddr, timeout: Duration, count: u32) -> DetailedPingResult {
+pub async fn ping_host_detailed(
+    client: &Client,
+    address: IpAddr,
+    timeout: Duration,
+    count: u32,
+    options: &PingOptions,
+) -> DetailedPingResult {
     let identifier = PingIdentifier(random());
     let mut pinger = client.pinger(address, identifier).await;
     pinger.timeout(timeout);
 
-    let payload = [0u8; 56];
+    let payload = options.payload();
     let mut results = Vec::with_capacity(count as usize);
     let mut packets_received = 0u32;
 
@@ -218,6 +628,7 @@ pub async fn ping_host_detailed(client: &Client, address: IpAddr, timeout: Durat
 
     DetailedPingResult {
         address,
+        options: options.clone(),
         results,
         packets_sent: count,
         packets_received,
@@ -255,6 +666,7 @@ mod tests {
     fn test_detailed_ping_result_format() {
         let result = DetailedPingResult {
             address: IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+            options: PingOptions::default(),
             results: vec![
                 (0, Ok(Duration::from_millis(10))),
                 (1, Ok(Duration::from_millis(12))),
@@ -270,4 +682,46 @@ mod tests {
         assert!(output.contains("4 packets transmitted, 3 received"));
         assert!(output.contains("25% packet loss"));
     }
+
+    #[test]
+    fn test_detailed_ping_result_header_with_options() {
+        let options = PingOptions {
+            size: 1472,
+            dont_fragment: true,
+            ..Default::default()
+        };
+        let result = DetailedPingResult {
+            address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            options,
+            results: vec![(0, Ok(Duration::from_millis(10)))],
+            packets_sent: 1,
+            packets_received: 1,
+        };
+
+        let output = result.format();
+        assert!(output.contains("PING 10.0.0.1 (1472 bytes, DF)"));
+        assert!(output.contains("1480 bytes: seq=0"));
+    }
+
+    #[test]
+    fn test_ping_options_payload_pattern() {
+        let options = PingOptions {
+            size: 5,
+            pattern: vec![0xab, 0xcd],
+            ..Default::default()
+        };
+        assert_eq!(options.payload(), vec![0xab, 0xcd, 0xab, 0xcd, 0xab]);
+        assert_eq!(PingOptions::default().payload(), vec![0u8; DEFAULT_PAYLOAD_SIZE]);
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        assert_eq!(parse_pattern("ff00").unwrap(), vec![0xff, 0x00]);
+        assert_eq!(parse_pattern("0xdead").unwrap(), vec![0xde, 0xad]);
+        assert!(parse_pattern("abc").is_err());
+        assert!(parse_pattern("zz").is_err());
+        assert!(parse_pattern("aéb").is_err());
+        assert!(parse_pattern("+f").is_err());
+        assert!(parse_pattern("").is_err());
+    }
 }
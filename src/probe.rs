@@ -0,0 +1,641 @@
+//! Pluggable checks run against each host
+//!
+//! A [`Check`] probes one aspect of a host and reports a [`CheckOutcome`]: a
+//! status, text for display, and numeric metrics that [`Threshold`]s can
+//! judge. The [`Registry`] turns a host's config into the checks to run, so a
+//! new probe type is a `Check` plus a registry entry; results, renderers and
+//! JSON output pick it up as they are.
+
+use crate::check::CheckContext;
+use crate::config::{self, HostConfig};
+use crate::dns::DnsResult;
+use crate::exec::ExecCheck;
+use crate::interpolate;
+use crate::mtu::{self, MtuCheckResult};
+use crate::ping::PingResult;
+use crate::tcp::{self, TcpResult};
+use colored::*;
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Future returned by [`Check::run`]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Builds the checks a host asks for; empty if the host doesn't use this check type
+pub type Factory = fn(&HostConfig) -> Vec<Box<dyn Check>>;
+
+/// One probe against a host, e.g. a ping or a TCP connect to one port
+///
+/// ```
+/// use cxn::{BoxFuture, Check, CheckContext, CheckOutcome, Status};
+///
+/// /// Passes when the host has a literal IP address
+/// struct Literal;
+///
+/// impl Check for Literal {
+///     fn name(&self) -> &str {
+///         "literal"
+///     }
+///
+///     fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+///         Box::pin(async move {
+///             match ctx.host().is_ip_address() {
+///                 true => CheckOutcome::new("literal", Status::Ok, "an IP address"),
+///                 false => CheckOutcome::new("literal", Status::Failed, "a host name"),
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait Check: Send + Sync {
+    /// Check type, shared by all outcomes it produces (e.g. "tcp" for every port)
+    fn name(&self) -> &str;
+
+    /// Probe the host once
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome>;
+
+    /// Whether a failed run is repeated up to the host's `retries`
+    fn retryable(&self) -> bool {
+        true
+    }
+}
+
+/// How a check went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Passed, but a metric is past its warning threshold
+    Warning,
+    /// The check couldn't tell, e.g. a plugin exiting with UNKNOWN
+    Unknown,
+    Failed,
+}
+
+impl Status {
+    /// Whether the host counts as up as far as this check goes
+    pub fn passed(self) -> bool {
+        self <= Status::Warning
+    }
+
+    /// Status mark for display
+    pub fn mark(self) -> ColoredString {
+        match self {
+            Status::Ok => "✓".green(),
+            Status::Warning => "!".yellow(),
+            Status::Unknown => "?".magenta(),
+            Status::Failed => "✗".red(),
+        }
+    }
+
+    /// Text in this status's color
+    pub fn paint(self, text: &str) -> ColoredString {
+        match self {
+            Status::Ok => text.green(),
+            Status::Warning => text.yellow(),
+            Status::Unknown => text.magenta(),
+            Status::Failed => text.red(),
+        }
+    }
+}
+
+/// A measurement taken by a check, e.g. ping round-trip time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// Name thresholds refer to it by, e.g. "rtt_ms"
+    pub name: String,
+    pub value: f64,
+}
+
+/// What one check found
+#[derive(Debug, Clone)]
+pub struct CheckOutcome {
+    /// Check type that produced it, e.g. "ping"
+    pub check: String,
+    pub status: Status,
+    /// Short text for tables and one-line views, e.g. "12.3ms" or "443✗"
+    pub brief: String,
+    /// Text for detailed output: addresses, round-trip time or error
+    pub summary: String,
+    pub metrics: Vec<Metric>,
+    /// Check-specific data for JSON output
+    pub details: serde_json::Value,
+}
+
+impl CheckOutcome {
+    /// An outcome whose brief text is its summary, without metrics or details
+    pub fn new(check: impl Into<String>, status: Status, summary: impl Into<String>) -> Self {
+        let summary = summary.into();
+        Self {
+            check: check.into(),
+            status,
+            brief: summary.clone(),
+            summary,
+            metrics: Vec::new(),
+            details: serde_json::Value::Null,
+        }
+    }
+
+    /// Use other text in tables than in detailed output
+    pub fn brief(mut self, brief: impl Into<String>) -> Self {
+        self.brief = brief.into();
+        self
+    }
+
+    /// Record a measurement
+    pub fn metric(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.metrics.push(Metric {
+            name: name.into(),
+            value,
+        });
+        self
+    }
+
+    /// Attach check-specific data for JSON output
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// Value of the named metric, if the check took it
+    pub fn metric_value(&self, name: &str) -> Option<f64> {
+        self.metrics.iter().find(|m| m.name == name).map(|m| m.value)
+    }
+
+    /// Worsen the status of a passing outcome whose metrics exceed their thresholds
+    ///
+    /// Thresholds are keyed `<check>.<metric>`, e.g. `ping.rtt_ms`.
+    pub fn judge(&mut self, thresholds: &IndexMap<String, Threshold>) {
+        if !self.status.passed() {
+            return;
+        }
+        for metric in &self.metrics {
+            let Some(threshold) = thresholds.get(&format!("{}.{}", self.check, metric.name)) else {
+                continue;
+            };
+            let (status, limit) = match (threshold.fail, threshold.warn) {
+                (Some(fail), _) if metric.value > fail => (Status::Failed, fail),
+                (_, Some(warn)) if metric.value > warn => (Status::Warning, warn),
+                _ => continue,
+            };
+            if status > self.status {
+                self.status = status;
+                self.summary = format!("{} ({} {} > {})", self.summary, metric.name, metric.value, limit);
+            }
+        }
+    }
+
+    /// Format the outcome for display, e.g. `  ✓ ping: 12.3ms`
+    pub fn format(&self) -> String {
+        format!(
+            "  {} {:<5} {}",
+            self.status.mark(),
+            format!("{}:", self.check),
+            interpolate::redact(&self.summary)
+        )
+    }
+
+    /// The outcome as a JSON object for machine-readable output
+    pub fn to_json(&self) -> serde_json::Value {
+        let metrics: serde_json::Map<String, serde_json::Value> =
+            self.metrics.iter().map(|m| (m.name.clone(), json!(m.value))).collect();
+        json!({
+            "check": self.check,
+            "status": self.status,
+            "summary": interpolate::redact(&self.summary),
+            "metrics": metrics,
+            "details": interpolate::redact_json(&self.details),
+        })
+    }
+}
+
+/// Limits on a check's metric, e.g. `ping.rtt_ms: { warn: 50, fail: 200 }`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    /// Mark the check as a warning when the metric is above this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn: Option<f64>,
+    /// Fail the check when the metric is above this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail: Option<f64>,
+}
+
+impl Threshold {
+    /// Reject keys not of the form `<check>.<metric>` and limits that can't apply
+    ///
+    /// A key naming a check `registry` doesn't have, or a metric its check
+    /// doesn't report, only gets a warning, since it may be meant for another setup.
+    pub fn validate(key: &str, threshold: &Threshold, registry: &Registry) -> eyre::Result<()> {
+        let (check, metric) = match key.split_once('.') {
+            Some((check, metric)) if !check.is_empty() && !metric.is_empty() => (check, metric),
+            _ => eyre::bail!("threshold '{}' must be named <check>.<metric>, e.g. ping.rtt_ms", key),
+        };
+        if let Some(problem) = unknown_key(check, metric, registry) {
+            log::warn!("Threshold '{}' will never apply: {}", key, problem);
+        }
+        match (threshold.warn, threshold.fail) {
+            (None, None) => eyre::bail!("threshold '{}' sets neither warn nor fail", key),
+            (Some(warn), Some(fail)) if warn > fail => {
+                eyre::bail!("threshold '{}' warns at {} but fails at {}", key, warn, fail)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What is wrong with a threshold key's check or metric name, if anything
+fn unknown_key(check: &str, metric: &str, registry: &Registry) -> Option<String> {
+    let checks: Vec<&str> = registry.names().collect();
+    if !checks.contains(&check) {
+        let hint = config::did_you_mean(check, &checks).map(|c| format!(" (did you mean '{}'?)", c));
+        return Some(format!("there is no '{}' check{}", check, hint.unwrap_or_default()));
+    }
+    let metrics = registry.metrics(check)?;
+    if metrics.contains(&metric) {
+        return None;
+    }
+    let hint = match config::did_you_mean(metric, metrics) {
+        Some(m) => format!(" (did you mean '{}'?)", m),
+        None if metrics.is_empty() => " (it reports none)".to_string(),
+        None => format!(" (it reports {})", metrics.join(", ")),
+    };
+    Some(format!("the {} check doesn't report '{}'{}", check, metric, hint))
+}
+
+/// Check types and how to build them for a host, run in registration order
+///
+/// The default registry has the built-in checks: dns, ping, mtu, tcp and exec. DNS
+/// comes first so its answer is the address the others probe.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    /// Each check type with its factory and, for built-in ones, the metrics it reports
+    factories: Vec<(String, Factory, Option<&'static [&'static str]>)>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register_builtin("dns", &[], |host| boxed(host.should_resolve_dns().then_some(DnsCheck)));
+        registry.register_builtin("ping", &["rtt_ms"], |host| boxed(host.ping.then_some(PingCheck)));
+        registry.register_builtin("mtu", &[], |host| {
+            boxed(host.mtu_min.map(|mtu_min| MtuCheck { mtu_min }))
+        });
+        registry.register_builtin("tcp", &["rtt_ms"], |host| {
+            boxed(host.tcp.iter().map(|&port| TcpCheck { port }))
+        });
+        // Exec metrics are whatever the plugin prints, so any name goes
+        registry.register("exec", |host| {
+            boxed(host.exec.iter().map(|command| ExecCheck {
+                command: command.clone(),
+            }))
+        });
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry without any checks
+    pub fn empty() -> Self {
+        Self { factories: Vec::new() }
+    }
+
+    /// Add a check type after the existing ones, replacing one of the same name in place
+    pub fn register(&mut self, name: impl Into<String>, factory: Factory) {
+        self.insert(name.into(), factory, None);
+    }
+
+    /// Add a built-in check type that reports only `metrics`
+    fn register_builtin(&mut self, name: &str, metrics: &'static [&'static str], factory: Factory) {
+        self.insert(name.to_string(), factory, Some(metrics));
+    }
+
+    fn insert(&mut self, name: String, factory: Factory, metrics: Option<&'static [&'static str]>) {
+        match self.factories.iter_mut().find(|(n, _, _)| *n == name) {
+            Some(entry) => *entry = (name, factory, metrics),
+            None => self.factories.push((name, factory, metrics)),
+        }
+    }
+
+    /// Names of the registered check types, in the order they run
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().map(|(name, _, _)| name.as_str())
+    }
+
+    /// Metrics a check type reports, if known (built-in checks other than exec)
+    pub fn metrics(&self, name: &str) -> Option<&'static [&'static str]> {
+        self.factories
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .and_then(|(_, _, metrics)| *metrics)
+    }
+
+    /// The checks to run against a host
+    pub fn checks(&self, host: &HostConfig) -> Vec<Box<dyn Check>> {
+        self.factories
+            .iter()
+            .flat_map(|(_, factory, _)| factory(host))
+            .collect()
+    }
+}
+
+fn boxed<C: Check + 'static>(checks: impl IntoIterator<Item = C>) -> Vec<Box<dyn Check>> {
+    checks.into_iter().map(|c| Box::new(c) as Box<dyn Check>).collect()
+}
+
+/// Round-trip time in milliseconds, as reported in metrics
+fn millis(rtt: Duration) -> f64 {
+    rtt.as_micros() as f64 / 1000.0
+}
+
+/// Resolves the host name, making the first answer the address other checks probe
+pub struct DnsCheck;
+
+impl Check for DnsCheck {
+    fn name(&self) -> &str {
+        "dns"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move { ctx.lookup().await.into() })
+    }
+}
+
+/// Pings the host with its configured count, timeout and packet options
+pub struct PingCheck;
+
+impl Check for PingCheck {
+    fn name(&self) -> &str {
+        "ping"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            match ctx.address().await {
+                Some(ip) => ctx.ping(ip).await.into(),
+                None => PingResult::failure(
+                    ctx.host().name.clone(),
+                    "0.0.0.0".parse().unwrap(),
+                    "could not resolve hostname".to_string(),
+                )
+                .into(),
+            }
+        })
+    }
+}
+
+/// Asserts that DF packets of `mtu_min` bytes reach the host
+pub struct MtuCheck {
+    pub mtu_min: usize,
+}
+
+impl Check for MtuCheck {
+    fn name(&self) -> &str {
+        "mtu"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            let host = ctx.host();
+            match ctx.address().await {
+                Some(ip) => {
+                    ctx.pace().await;
+                    mtu::check_min_mtu(ip, self.mtu_min, host.timeout, &host.ping_options, &host.source).await
+                }
+                None => MtuCheckResult {
+                    mtu_min: self.mtu_min,
+                    success: false,
+                    error: Some("could not resolve hostname".to_string()),
+                },
+            }
+            .into()
+        })
+    }
+
+    /// The probe already makes several attempts per size
+    fn retryable(&self) -> bool {
+        false
+    }
+}
+
+/// Connects to one TCP port on the host
+pub struct TcpCheck {
+    pub port: u16,
+}
+
+impl Check for TcpCheck {
+    fn name(&self) -> &str {
+        "tcp"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a CheckContext<'a>) -> BoxFuture<'a, CheckOutcome> {
+        Box::pin(async move {
+            let host = ctx.host();
+            match ctx.address().await {
+                Some(ip) => {
+                    ctx.pace().await;
+                    tcp::check_port(ip, self.port, host.timeout, &host.source).await
+                }
+                None => TcpResult::failure(self.port, "could not resolve hostname".to_string()),
+            }
+            .into()
+        })
+    }
+}
+
+impl From<DnsResult> for CheckOutcome {
+    fn from(result: DnsResult) -> Self {
+        let details = json!({ "addresses": result.addresses, "error": result.error });
+        let outcome = if result.success {
+            let addresses: Vec<String> = result.addresses.iter().map(|a| a.to_string()).collect();
+            let summary = if addresses.is_empty() {
+                "(none)".to_string()
+            } else {
+                addresses.join(", ")
+            };
+            let first = addresses.first().cloned().unwrap_or_else(|| "ok".to_string());
+            CheckOutcome::new("dns", Status::Ok, summary).brief(first)
+        } else {
+            let error = result.error.as_deref().unwrap_or("unknown error");
+            CheckOutcome::new("dns", Status::Failed, error).brief("fail")
+        };
+        outcome.details(details)
+    }
+}
+
+impl From<PingResult> for CheckOutcome {
+    fn from(result: PingResult) -> Self {
+        let rtt_ms = result.rtt.map(millis);
+        let details = json!({ "address": result.address, "rtt_ms": rtt_ms, "error": result.error });
+        let outcome = match (result.success, rtt_ms) {
+            (true, Some(rtt_ms)) => {
+                let text = format!("{:.1}ms", rtt_ms);
+                CheckOutcome::new("ping", Status::Ok, text).metric("rtt_ms", rtt_ms)
+            }
+            (true, None) => CheckOutcome::new("ping", Status::Ok, "?").brief("ok"),
+            (false, _) => {
+                let error = result.error.as_deref().unwrap_or("unknown error");
+                CheckOutcome::new("ping", Status::Failed, error).brief("fail")
+            }
+        };
+        outcome.details(details)
+    }
+}
+
+impl From<MtuCheckResult> for CheckOutcome {
+    fn from(result: MtuCheckResult) -> Self {
+        let details = json!({ "mtu_min": result.mtu_min, "error": result.error });
+        let outcome = if result.success {
+            CheckOutcome::new("mtu", Status::Ok, format!(">= {}", result.mtu_min))
+                .brief(format!(">={}", result.mtu_min))
+        } else {
+            let error = result.error.as_deref().unwrap_or("unknown error");
+            CheckOutcome::new("mtu", Status::Failed, format!("< {} ({})", result.mtu_min, error))
+                .brief(format!("<{}", result.mtu_min))
+        };
+        outcome.details(details)
+    }
+}
+
+impl From<TcpResult> for CheckOutcome {
+    fn from(result: TcpResult) -> Self {
+        let rtt_ms = result.rtt.map(millis);
+        let details = json!({ "port": result.port, "rtt_ms": rtt_ms, "error": result.error });
+        let port = result.port.to_string();
+        let outcome = match (result.success, rtt_ms) {
+            (true, Some(rtt_ms)) => CheckOutcome::new("tcp", Status::Ok, format!("{} open ({:.1}ms)", port, rtt_ms))
+                .brief(port)
+                .metric("rtt_ms", rtt_ms),
+            (true, None) => CheckOutcome::new("tcp", Status::Ok, format!("{} open", port)).brief(port),
+            (false, _) => {
+                let error = result.error.as_deref().unwrap_or("unknown error");
+                CheckOutcome::new("tcp", Status::Failed, format!("{} {}", port, error)).brief(format!("{}✗", port))
+            }
+        };
+        outcome.details(details)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ping(rtt_ms: u64) -> CheckOutcome {
+        PingResult::success(
+            "Test".to_string(),
+            "10.0.0.1".parse().unwrap(),
+            Duration::from_millis(rtt_ms),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_outcome_format_keeps_label_alignment() {
+        assert!(ping(15).format().ends_with("ping: 15.0ms"));
+        let tcp: CheckOutcome = TcpResult::failure(443, "refused".to_string()).into();
+        assert!(tcp.format().ends_with("tcp:  443 refused"));
+        assert_eq!(tcp.brief, "443✗");
+        assert_eq!(tcp.status, Status::Failed);
+    }
+
+    #[test]
+    fn test_thresholds_judge_metrics() {
+        let thresholds: IndexMap<String, Threshold> = [(
+            "ping.rtt_ms".to_string(),
+            Threshold {
+                warn: Some(50.0),
+                fail: Some(200.0),
+            },
+        )]
+        .into_iter()
+        .collect();
+
+        let mut fast = ping(10);
+        fast.judge(&thresholds);
+        assert_eq!(fast.status, Status::Ok);
+
+        let mut slow = ping(80);
+        slow.judge(&thresholds);
+        assert_eq!(slow.status, Status::Warning);
+        assert!(slow.status.passed());
+        assert!(slow.summary.contains("rtt_ms 80 > 50"));
+
+        let mut slower = ping(300);
+        slower.judge(&thresholds);
+        assert_eq!(slower.status, Status::Failed);
+
+        // Thresholds only apply to the check they name
+        let mut tcp: CheckOutcome = TcpResult {
+            port: 22,
+            success: true,
+            rtt: Some(Duration::from_millis(300)),
+            error: None,
+        }
+        .into();
+        tcp.judge(&thresholds);
+        assert_eq!(tcp.status, Status::Ok);
+    }
+
+    #[test]
+    fn test_threshold_validation() {
+        let both = |warn, fail| Threshold {
+            warn: Some(warn),
+            fail: Some(fail),
+        };
+        let registry = Registry::default();
+        assert!(Threshold::validate("ping.rtt_ms", &both(50.0, 200.0), &registry).is_ok());
+        assert!(Threshold::validate("rtt_ms", &both(50.0, 200.0), &registry).is_err());
+        assert!(Threshold::validate("ping.rtt_ms", &both(200.0, 50.0), &registry).is_err());
+        assert!(Threshold::validate("ping.rtt_ms", &Threshold::default(), &registry).is_err());
+        // Unknown names only warn
+        assert!(Threshold::validate("pign.rtt_ms", &both(50.0, 200.0), &registry).is_ok());
+    }
+
+    #[test]
+    fn test_threshold_keys_checked_against_registry() {
+        let mut registry = Registry::default();
+        assert_eq!(unknown_key("ping", "rtt_ms", &registry), None);
+        assert_eq!(unknown_key("exec", "pl", &registry), None);
+        assert_eq!(
+            unknown_key("pign", "rtt_ms", &registry).as_deref(),
+            Some("there is no 'pign' check (did you mean 'ping'?)")
+        );
+        assert_eq!(
+            unknown_key("tcp", "rtt", &registry).as_deref(),
+            Some("the tcp check doesn't report 'rtt' (did you mean 'rtt_ms'?)")
+        );
+        assert_eq!(
+            unknown_key("dns", "rtt_ms", &registry).as_deref(),
+            Some("the dns check doesn't report 'rtt_ms' (it reports none)")
+        );
+
+        // Metrics of added check types aren't known up front
+        registry.register("http", |_| Vec::new());
+        assert_eq!(unknown_key("http", "status", &registry), None);
+    }
+
+    #[test]
+    fn test_registry_builds_checks_in_order() {
+        let host = HostConfig {
+            name: "Wiki".to_string(),
+            address: "wiki.internal".to_string(),
+            ping: true,
+            dns: true,
+            tcp: vec![22, 443],
+            ..Default::default()
+        };
+        let registry = Registry::default();
+        let names: Vec<String> = registry.checks(&host).iter().map(|c| c.name().to_string()).collect();
+        assert_eq!(names, vec!["dns", "ping", "tcp", "tcp"]);
+
+        let mut registry = Registry::empty();
+        registry.register("ping", |_| boxed([PingCheck]));
+        assert_eq!(registry.names().collect::<Vec<_>>(), vec!["ping"]);
+        assert_eq!(registry.checks(&HostConfig::default()).len(), 1);
+    }
+}
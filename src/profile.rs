@@ -0,0 +1,223 @@
+use crate::interpolate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Named network context: which groups to check and settings to use there
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Groups to check; ungrouped hosts are always checked (all groups if empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Timeout in milliseconds, replacing the top-level value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<u64>,
+    /// Ping packets per check, replacing the top-level value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub count: Option<u32>,
+    /// Retry attempts, replacing the top-level value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retries: Option<u32>,
+    /// Watch interval in seconds, replacing the top-level value
+    #[serde(
+        default,
+        deserialize_with = "interpolate::number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub interval: Option<u64>,
+    /// Interface to bind probe sockets to, replacing the top-level value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Local address to send probes from, replacing the top-level value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+    /// DNS servers to query, replacing the top-level value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nameservers: Vec<IpAddr>,
+    /// Select this profile automatically when all of these hold
+    #[serde(default, rename = "match", skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<ProfileMatch>,
+}
+
+/// Facts about the current network that auto-select a profile
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileMatch {
+    /// A default route goes through this gateway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<IpAddr>,
+    /// This interface exists and is not down (e.g. a VPN tunnel like wg0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// /etc/resolv.conf lists this name server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nameserver: Option<IpAddr>,
+}
+
+impl ProfileMatch {
+    /// Check if no condition is set (such a match would select the profile everywhere)
+    pub fn is_empty(&self) -> bool {
+        self.gateway.is_none() && self.interface.is_none() && self.nameserver.is_none()
+    }
+
+    /// Check if every condition holds on this network
+    pub fn matches(&self, network: &NetworkState) -> bool {
+        !self.is_empty()
+            && self.gateway.is_none_or(|g| network.gateways.contains(&g))
+            && self.interface.as_ref().is_none_or(|i| network.interfaces.contains(i))
+            && self.nameserver.is_none_or(|n| network.nameservers.contains(&n))
+    }
+}
+
+/// What the host's network looks like right now, for profile auto-selection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkState {
+    /// Default-route gateways (IPv4 and IPv6)
+    pub gateways: Vec<IpAddr>,
+    /// Interfaces that are not administratively down
+    pub interfaces: Vec<String>,
+    /// Name servers from /etc/resolv.conf
+    pub nameservers: Vec<IpAddr>,
+}
+
+impl NetworkState {
+    /// Read the routing table, interfaces and resolver config; unreadable sources are skipped
+    pub fn detect() -> Self {
+        let read = |path: &str| fs::read_to_string(path).unwrap_or_default();
+        let mut gateways = parse_route_gateways(&read("/proc/net/route"));
+        gateways.extend(parse_ipv6_route_gateways(&read("/proc/net/ipv6_route")));
+
+        let interfaces = fs::read_dir("/sys/class/net")
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| {
+                        let state = fs::read_to_string(entry.path().join("operstate")).unwrap_or_default();
+                        state.trim() != "down"
+                    })
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let state = Self {
+            gateways,
+            interfaces,
+            nameservers: parse_resolv_conf(&read("/etc/resolv.conf")),
+        };
+        log::debug!("Detected network state: {:?}", state);
+        state
+    }
+}
+
+/// Gateways of IPv4 default routes in /proc/net/route
+fn parse_route_gateways(content: &str) -> Vec<IpAddr> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+            if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+                return None;
+            }
+            // Addresses are hex in host (little-endian) byte order
+            let gateway = u32::from_str_radix(fields[2], 16).ok().filter(|g| *g != 0)?;
+            Some(IpAddr::V4(Ipv4Addr::from(gateway.to_le_bytes())))
+        })
+        .collect()
+}
+
+/// Next hops of IPv6 default routes in /proc/net/ipv6_route
+fn parse_ipv6_route_gateways(content: &str) -> Vec<IpAddr> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // dest dest_prefix src src_prefix next_hop metric refcnt use flags iface
+            if fields.len() < 10 || fields[1] != "00" || u128::from_str_radix(fields[0], 16).ok()? != 0 {
+                return None;
+            }
+            let next_hop = u128::from_str_radix(fields[4], 16).ok().filter(|n| *n != 0)?;
+            Some(IpAddr::V6(Ipv6Addr::from(next_hop)))
+        })
+        .collect()
+}
+
+/// `nameserver` entries of a resolv.conf
+fn parse_resolv_conf(content: &str) -> Vec<IpAddr> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split(['#', ';']).next()?.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("nameserver"), Some(address)) => address.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_network_sources() {
+        let route = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t00000000\t010200C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n\
+                     eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n";
+        assert_eq!(
+            parse_route_gateways(route),
+            vec!["192.0.2.1".parse::<IpAddr>().unwrap()]
+        );
+
+        let ipv6_route = "fd000000000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001 eth0\n\
+                          00000000000000000000000000000000 00 00000000000000000000000000000000 00 fd000000000000000000000000000001 00000400 00000001 00000000 00000003 eth0\n";
+        assert_eq!(
+            parse_ipv6_route_gateways(ipv6_route),
+            vec!["fd00::1".parse::<IpAddr>().unwrap()]
+        );
+
+        let resolv = "# generated\nsearch corp.example\nnameserver 10.1.1.53\nnameserver 2001:db8::53 # backup\n";
+        let expected: Vec<IpAddr> = vec!["10.1.1.53".parse().unwrap(), "2001:db8::53".parse().unwrap()];
+        assert_eq!(parse_resolv_conf(resolv), expected);
+    }
+
+    #[test]
+    fn test_profile_match() {
+        let network = NetworkState {
+            gateways: vec!["10.1.0.1".parse().unwrap()],
+            interfaces: vec!["lo".to_string(), "wg0".to_string()],
+            nameservers: vec!["10.1.1.53".parse().unwrap()],
+        };
+        let office = ProfileMatch {
+            gateway: Some("10.1.0.1".parse().unwrap()),
+            nameserver: Some("10.1.1.53".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(office.matches(&network));
+
+        let home = ProfileMatch {
+            gateway: Some("192.168.1.1".parse().unwrap()),
+            interface: Some("wg0".to_string()),
+            ..Default::default()
+        };
+        assert!(!home.matches(&network));
+        assert!(!ProfileMatch::default().matches(&network));
+    }
+}
@@ -0,0 +1,209 @@
+use cxn::format::ConfigFormat;
+use eyre::{Context, Result};
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long to wait for an editor to finish writing before reloading
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Watches the loaded config files so watch mode can reload them between cycles
+///
+/// Directories are watched rather than files, since editors often save by
+/// writing a new file and renaming it over the old one. A fragment directory
+/// that doesn't exist yet is picked up through its parent once it is created.
+pub struct ConfigWatcher {
+    watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<()>,
+    /// Fragment directories, and whether each currently has a watch of its own
+    fragment_dirs: Vec<(PathBuf, bool)>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `files` (and new config files next to them) and the
+    /// fragment directories `fragment_dirs` (e.g. conf.d)
+    pub fn new(files: &[PathBuf], fragment_dirs: &[PathBuf]) -> Result<Self> {
+        let files: Vec<PathBuf> = files.iter().map(|f| absolute(f)).collect();
+        let fragment_dirs: Vec<PathBuf> = fragment_dirs.iter().map(|d| absolute(d)).collect();
+        let mut dirs: Vec<PathBuf> = files.iter().filter_map(|f| f.parent().map(Path::to_path_buf)).collect();
+        // Watching a fragment directory's parent too notices it being created or replaced
+        dirs.extend(
+            fragment_dirs
+                .iter()
+                .filter_map(|d| d.parent())
+                .filter(|p| p.is_dir())
+                .map(Path::to_path_buf),
+        );
+        dirs.sort();
+        dirs.dedup();
+
+        let (tx, changes) = mpsc::unbounded_channel();
+        let (watched, watched_dirs) = (files.clone(), fragment_dirs.clone());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if is_config_change(&event, &watched, &watched_dirs) => {
+                log::debug!("Config change: {:?}", event);
+                let _ = tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Config watch error: {}", e),
+        })
+        .context("Failed to start config watcher")?;
+        for dir in &dirs {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .context(format!("Failed to watch {}", dir.display()))?;
+        }
+        let names: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+        log::info!("Watching {} for config changes", names.join(", "));
+
+        let mut watcher = Self {
+            watcher,
+            changes,
+            fragment_dirs: fragment_dirs.into_iter().map(|d| (d, false)).collect(),
+        };
+        watcher.watch_fragment_dirs();
+        Ok(watcher)
+    }
+
+    /// Wait until a config file changes, then let the burst of events settle
+    pub async fn changed(&mut self) {
+        if self.changes.recv().await.is_none() {
+            // Watcher is gone; never report another change
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(SETTLE_TIME).await;
+        while self.changes.try_recv().is_ok() {}
+        self.watch_fragment_dirs();
+    }
+
+    /// Watch fragment directories that have appeared since the last look
+    ///
+    /// A removed directory loses its watch, so it is watched again if recreated.
+    fn watch_fragment_dirs(&mut self) {
+        for (dir, watched) in &mut self.fragment_dirs {
+            if !dir.is_dir() {
+                *watched = false;
+            } else if !*watched {
+                match self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        log::info!("Watching {} for config changes", dir.display());
+                        *watched = true;
+                    }
+                    Err(e) => log::warn!("Failed to watch {}: {}", dir.display(), e),
+                }
+            }
+        }
+    }
+}
+
+/// Make a path absolute so it can be compared with the paths in watch events
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Check if an event writes, creates or removes a loaded file, a config file
+/// beside one or in a fragment directory, or a fragment directory itself
+fn is_config_change(event: &Event, files: &[PathBuf], fragment_dirs: &[PathBuf]) -> bool {
+    let writes = matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(_)
+            | EventKind::Remove(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    );
+    writes
+        && event.paths.iter().any(|path| {
+            let is_config = ConfigFormat::from_extension(path).is_some();
+            let in_config_dir = path.parent().is_some_and(|parent| {
+                files.iter().any(|f| f.parent() == Some(parent)) || fragment_dirs.iter().any(|d| d == parent)
+            });
+            files.contains(path) || fragment_dirs.contains(path) || (is_config && in_config_dir)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind};
+
+    #[test]
+    fn test_is_config_change() {
+        let files = vec![
+            PathBuf::from("/etc/cxn/cxn.yml"),
+            PathBuf::from("/etc/cxn/conf.d/db.yml"),
+        ];
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+
+        assert!(is_config_change(
+            &event(EventKind::Modify(ModifyKind::Any), "/etc/cxn/cxn.yml"),
+            &files,
+            &[]
+        ));
+        assert!(is_config_change(
+            &event(EventKind::Create(CreateKind::File), "/etc/cxn/conf.d/new.yaml"),
+            &files,
+            &[]
+        ));
+        assert!(is_config_change(
+            &event(EventKind::Create(CreateKind::File), "/etc/cxn/conf.d/new.toml"),
+            &files,
+            &[]
+        ));
+        // Editor swap files and reads don't count
+        assert!(!is_config_change(
+            &event(EventKind::Create(CreateKind::File), "/etc/cxn/.cxn.yml.swp"),
+            &files,
+            &[]
+        ));
+        let read = EventKind::Access(AccessKind::Close(AccessMode::Read));
+        assert!(!is_config_change(&event(read, "/etc/cxn/cxn.yml"), &files, &[]));
+        assert!(!is_config_change(
+            &event(EventKind::Modify(ModifyKind::Any), "/tmp/other.yml"),
+            &files,
+            &[]
+        ));
+
+        // A fragment directory counts before any file in it has been loaded
+        let fragment_dirs = [PathBuf::from("/home/me/.config/cxn/conf.d")];
+        let created = event(EventKind::Create(CreateKind::Folder), "/home/me/.config/cxn/conf.d");
+        assert!(is_config_change(&created, &[], &fragment_dirs));
+        let added = event(
+            EventKind::Create(CreateKind::File),
+            "/home/me/.config/cxn/conf.d/db.yml",
+        );
+        assert!(is_config_change(&added, &[], &fragment_dirs));
+        let sibling = event(EventKind::Create(CreateKind::File), "/home/me/.config/cxn/other.yml");
+        assert!(!is_config_change(&sibling, &[], &fragment_dirs));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reports_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cxn.yml");
+        std::fs::write(&file, "hosts: {}\n").unwrap();
+
+        let mut watcher = ConfigWatcher::new(std::slice::from_ref(&file), &[]).unwrap();
+        std::fs::write(&file, "timeout: 500\nhosts: {}\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .expect("edit was not reported");
+    }
+
+    #[tokio::test]
+    async fn test_watcher_picks_up_new_fragment_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+
+        let mut watcher = ConfigWatcher::new(&[], std::slice::from_ref(&conf_d)).unwrap();
+        std::fs::create_dir(&conf_d).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .expect("new directory was not reported");
+        std::fs::write(conf_d.join("db.yml"), "hosts: {}\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .expect("fragment in the new directory was not reported");
+    }
+}
@@ -0,0 +1,211 @@
+use eyre::{Context, Result};
+use std::net::IpAddr;
+
+/// Where probe sockets originate: a local address and/or a network interface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceBinding {
+    /// Interface to bind sockets to (SO_BINDTODEVICE)
+    pub interface: Option<String>,
+    /// Local address to send from
+    pub address: Option<IpAddr>,
+}
+
+impl SourceBinding {
+    /// Check if no binding is requested (shared clients can be used)
+    pub fn is_default(&self) -> bool {
+        self.interface.is_none() && self.address.is_none()
+    }
+
+    /// Layer `other` on top of this binding; fields set in `other` win
+    pub fn overridden_by(&self, other: &SourceBinding) -> SourceBinding {
+        SourceBinding {
+            interface: other.interface.clone().or_else(|| self.interface.clone()),
+            address: other.address.or(self.address),
+        }
+    }
+
+    /// Local address to bind for a socket talking to an address of the given family
+    ///
+    /// An explicit source address must match the family. Otherwise the first
+    /// routable address of that family on the bound interface is used, if any;
+    /// link-local addresses would need a scope id and loopback ones can't leave
+    /// the host, so those are left to the kernel along with the interface binding.
+    pub fn local_address(&self, v6: bool) -> Result<Option<IpAddr>> {
+        if let Some(address) = self.address {
+            if address.is_ipv6() != v6 {
+                eyre::bail!(
+                    "source address {} cannot reach an {} destination",
+                    address,
+                    if v6 { "IPv6" } else { "IPv4" }
+                );
+            }
+            return Ok(Some(address));
+        }
+        match &self.interface {
+            Some(name) => Ok(interface_addresses(name)?
+                .into_iter()
+                .find(|a| a.is_ipv6() == v6 && routable(a))),
+            None => Ok(None),
+        }
+    }
+
+    /// Short description for output, e.g. "via wlan0 from 192.168.1.20"
+    pub fn describe(&self) -> String {
+        match (&self.interface, self.address) {
+            (Some(iface), Some(addr)) => format!("via {} from {}", iface, addr),
+            (Some(iface), None) => format!("via {}", iface),
+            (None, Some(addr)) => format!("from {}", addr),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Bind an unconnected socket to the interface and source address for `v6` traffic
+    #[cfg(target_os = "linux")]
+    pub fn bind_fd(&self, fd: std::os::fd::RawFd, v6: bool) -> Result<()> {
+        if let Some(ref iface) = self.interface {
+            // SAFETY: fd is a live socket and the name buffer outlives the call
+            let rc = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_BINDTODEVICE,
+                    iface.as_ptr() as *const libc::c_void,
+                    iface.len() as libc::socklen_t,
+                )
+            };
+            if rc != 0 {
+                return Err(std::io::Error::last_os_error()).context(format!("Failed to bind to interface {}", iface));
+            }
+        }
+        if let Some(local) = self.address {
+            let local = std::net::SocketAddr::new(local, 0);
+            if local.is_ipv6() != v6 {
+                eyre::bail!("source address {} does not match destination family", local.ip());
+            }
+            // SAFETY: fd stays open for the duration of this call
+            let borrowed = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+            socket2::SockRef::from(&borrowed)
+                .bind(&local.into())
+                .context(format!("Failed to bind to source address {}", local.ip()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether an interface address can serve as a source without further scoping
+fn routable(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => !v4.is_loopback() && !v4.is_link_local(),
+        IpAddr::V6(v6) => !v6.is_loopback() && !v6.is_unicast_link_local(),
+    }
+}
+
+/// List the addresses assigned to a network interface
+#[cfg(target_os = "linux")]
+pub fn interface_addresses(name: &str) -> Result<Vec<IpAddr>> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates the list; it is released with freeifaddrs below
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to list interface addresses");
+    }
+
+    let mut found = false;
+    let mut result = Vec::new();
+    let mut cur = addrs;
+    while !cur.is_null() {
+        // SAFETY: cur walks the list returned by getifaddrs until the terminating null
+        let ifa = unsafe { &*cur };
+        let ifa_name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) };
+        if ifa_name.to_bytes() == name.as_bytes() {
+            found = true;
+            if !ifa.ifa_addr.is_null() {
+                let addr = unsafe { &*ifa.ifa_addr };
+                match addr.sa_family as libc::c_int {
+                    libc::AF_INET => {
+                        let sin = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+                        result.push(IpAddr::V4(u32::from_be(sin.sin_addr.s_addr).into()));
+                    }
+                    libc::AF_INET6 => {
+                        let sin6 = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in6) };
+                        result.push(IpAddr::V6(sin6.sin6_addr.s6_addr.into()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        cur = ifa.ifa_next;
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+
+    if !found {
+        eyre::bail!("no such interface: {}", name);
+    }
+    Ok(result)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interface_addresses(name: &str) -> Result<Vec<IpAddr>> {
+    eyre::bail!("binding to interface {} is only supported on Linux", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_binding_override() {
+        let host = SourceBinding {
+            interface: Some("eth0".to_string()),
+            address: Some("10.0.0.5".parse().unwrap()),
+        };
+        let cli = SourceBinding {
+            interface: Some("wg0".to_string()),
+            address: None,
+        };
+        let merged = host.overridden_by(&cli);
+        assert_eq!(merged.interface.as_deref(), Some("wg0"));
+        assert_eq!(merged.address, Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(merged.describe(), "via wg0 from 10.0.0.5");
+    }
+
+    #[test]
+    fn test_local_address_family_mismatch() {
+        let binding = SourceBinding {
+            interface: None,
+            address: Some("10.0.0.5".parse().unwrap()),
+        };
+        assert!(binding.local_address(false).unwrap().is_some());
+        assert!(binding.local_address(true).is_err());
+        assert_eq!(SourceBinding::default().local_address(false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_routable_skips_link_local_and_loopback() {
+        let routable = |a: &str| routable(&a.parse().unwrap());
+        assert!(routable("192.168.1.20"));
+        assert!(routable("2001:db8::5"));
+        assert!(!routable("fe80::1"));
+        assert!(!routable("169.254.3.4"));
+        assert!(!routable("::1"));
+        assert!(!routable("127.0.0.1"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_local_address_leaves_loopback_to_the_interface() {
+        let binding = SourceBinding {
+            interface: Some("lo".to_string()),
+            address: None,
+        };
+        assert_eq!(binding.local_address(false).unwrap(), None);
+        assert_eq!(binding.local_address(true).unwrap(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_interface_addresses_loopback() {
+        let addrs = interface_addresses("lo").unwrap();
+        assert!(addrs.contains(&"127.0.0.1".parse().unwrap()));
+        assert!(interface_addresses("does-not-exist0").is_err());
+    }
+}
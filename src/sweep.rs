@@ -0,0 +1,202 @@
+use eyre::Result;
+use std::net::IpAddr;
+
+/// Most addresses a single host entry may expand to
+pub const MAX_SWEEP_HOSTS: usize = 1024;
+
+/// The host entry a generated host was expanded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sweep {
+    /// Name of the host entry
+    pub name: String,
+    /// Address pattern as written, e.g. `10.0.4.0/28`
+    pub pattern: String,
+}
+
+/// Expand an address pattern into individual addresses
+///
+/// Accepts CIDR blocks (`10.0.4.0/28`, every address in the block), IP ranges
+/// (`10.0.4.10-20` or `10.0.4.10-10.0.5.20`) and brace expressions
+/// (`web-{01..12}.internal`, `{eu,us}-gw`). Returns `None` for a plain address.
+pub fn expand(address: &str) -> Result<Option<Vec<String>>> {
+    let addresses = if let Some((ip, prefix)) = address.split_once('/') {
+        expand_cidr(address, ip, prefix)?
+    } else if let Some((start, end)) = address.split_once('-')
+        && let Ok(start) = start.parse::<IpAddr>()
+    {
+        expand_range(address, start, end)?
+    } else if address.contains('{') {
+        expand_braces(address)?
+    } else {
+        return Ok(None);
+    };
+    if addresses.is_empty() {
+        eyre::bail!("{} expands to no addresses", address);
+    }
+    Ok(Some(addresses))
+}
+
+fn too_many(pattern: &str, count: impl std::fmt::Display) -> eyre::Report {
+    eyre::eyre!(
+        "{} expands to {} addresses, more than the limit of {}",
+        pattern,
+        count,
+        MAX_SWEEP_HOSTS
+    )
+}
+
+fn expand_cidr(pattern: &str, ip: &str, prefix: &str) -> Result<Vec<String>> {
+    let ip: IpAddr = ip
+        .parse()
+        .map_err(|_| eyre::eyre!("{}: '{}' is not an IP address", pattern, ip))?;
+    let bits: u32 = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix: u32 = prefix
+        .parse()
+        .ok()
+        .filter(|p| *p <= bits)
+        .ok_or_else(|| eyre::eyre!("{}: prefix length must be 0-{}", pattern, bits))?;
+
+    let host_bits = bits - prefix;
+    if 1u128
+        .checked_shl(host_bits)
+        .is_none_or(|size| size > MAX_SWEEP_HOSTS as u128)
+    {
+        return Err(too_many(pattern, format!("2^{}", host_bits)));
+    }
+    let size = 1u128 << host_bits;
+    let network = to_u128(ip) & !(size - 1);
+    Ok((0..size)
+        .map(|offset| from_u128(ip, network + offset).to_string())
+        .collect())
+}
+
+fn expand_range(pattern: &str, start: IpAddr, end: &str) -> Result<Vec<String>> {
+    let end = match (start, end.parse::<u8>()) {
+        // `10.0.4.10-20` replaces the last octet
+        (IpAddr::V4(v4), Ok(last)) => {
+            let [a, b, c, _] = v4.octets();
+            IpAddr::from([a, b, c, last])
+        }
+        _ => end
+            .parse()
+            .ok()
+            .filter(|end: &IpAddr| end.is_ipv4() == start.is_ipv4())
+            .ok_or_else(|| eyre::eyre!("{}: '{}' is not a valid range end", pattern, end))?,
+    };
+    let (first, last) = (to_u128(start), to_u128(end));
+    if last < first {
+        eyre::bail!("{}: range end is before its start", pattern);
+    }
+    if last - first >= MAX_SWEEP_HOSTS as u128 {
+        return Err(too_many(pattern, last - first + 1));
+    }
+    Ok((first..=last).map(|n| from_u128(start, n).to_string()).collect())
+}
+
+/// Expand every `{a..b}` / `{x,y}` group, producing all combinations
+fn expand_braces(pattern: &str) -> Result<Vec<String>> {
+    let mut results = vec![String::new()];
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..]
+            .find('}')
+            .map(|i| open + i)
+            .ok_or_else(|| eyre::eyre!("{}: unterminated '{{'", pattern))?;
+        let choices = brace_choices(pattern, &rest[open + 1..close])?;
+        if results.len() * choices.len() > MAX_SWEEP_HOSTS {
+            return Err(too_many(pattern, format!("at least {}", results.len() * choices.len())));
+        }
+        let prefix = &rest[..open];
+        results = results
+            .iter()
+            .flat_map(|r| choices.iter().map(move |c| format!("{}{}{}", r, prefix, c)))
+            .collect();
+        rest = &rest[close + 1..];
+    }
+    Ok(results.into_iter().map(|r| r + rest).collect())
+}
+
+/// The alternatives of one brace group: a numeric range or a comma list
+fn brace_choices(pattern: &str, body: &str) -> Result<Vec<String>> {
+    let Some((start, end)) = body.split_once("..") else {
+        if !body.contains(',') {
+            eyre::bail!(
+                "{}: '{{{}}}' needs a range like {{1..9}} or a list like {{a,b}}",
+                pattern,
+                body
+            );
+        }
+        return Ok(body.split(',').map(String::from).collect());
+    };
+    let (first, last) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(first), Ok(last)) if first <= last => (first, last),
+        _ => eyre::bail!("{}: '{{{}}}' is not an ascending numeric range", pattern, body),
+    };
+    if last - first >= MAX_SWEEP_HOSTS as u64 {
+        return Err(too_many(pattern, last - first + 1));
+    }
+    // A leading zero on either end pads every number to that width, like `{01..12}`
+    let padded = [start, end].iter().any(|s| s.len() > 1 && s.starts_with('0'));
+    let width = if padded { start.len().max(end.len()) } else { 0 };
+    Ok((first..=last)
+        .map(|n| format!("{:0width$}", n, width = width))
+        .collect())
+}
+
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u32::from(v4) as u128,
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Build an address of the same family as `like` from its numeric value
+fn from_u128(like: IpAddr, value: u128) -> IpAddr {
+    match like {
+        IpAddr::V4(_) => IpAddr::from((value as u32).to_be_bytes()),
+        IpAddr::V6(_) => IpAddr::from(value.to_be_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_cidr_and_range() {
+        let block = expand("10.0.4.5/30").unwrap().unwrap();
+        assert_eq!(block, vec!["10.0.4.4", "10.0.4.5", "10.0.4.6", "10.0.4.7"]);
+        assert_eq!(expand("10.0.4.0/28").unwrap().unwrap().len(), 16);
+        assert_eq!(expand("2001:db8::/126").unwrap().unwrap()[3], "2001:db8::3");
+
+        assert_eq!(
+            expand("10.0.4.10-12").unwrap().unwrap(),
+            vec!["10.0.4.10", "10.0.4.11", "10.0.4.12"]
+        );
+        let across = expand("10.0.4.255-10.0.5.1").unwrap().unwrap();
+        assert_eq!(across, vec!["10.0.4.255", "10.0.5.0", "10.0.5.1"]);
+
+        assert_eq!(expand("10.0.4.1").unwrap(), None);
+        assert_eq!(expand("db-1.internal").unwrap(), None);
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        let names = expand("web-{01..03}.internal").unwrap().unwrap();
+        assert_eq!(names, vec!["web-01.internal", "web-02.internal", "web-03.internal"]);
+        let names = expand("{eu,us}-gw{1..2}").unwrap().unwrap();
+        assert_eq!(names, vec!["eu-gw1", "eu-gw2", "us-gw1", "us-gw2"]);
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let err = expand("10.0.0.0/16").unwrap_err().to_string();
+        assert!(err.contains("limit of 1024"), "{}", err);
+        assert!(expand("2001:db8::/64").is_err());
+        assert!(expand("10.0.4.20-10").is_err());
+        assert!(expand("10.0.4.0/33").is_err());
+        assert!(expand("web-{1..}.internal").is_err());
+        assert!(expand("web-{01..12.internal").is_err());
+        assert!(expand("host-{0..9}{0..9}{0..99}").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::source::SourceBinding;
+use colored::*;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Result of a TCP connect check against one port
+#[derive(Debug, Clone)]
+pub struct TcpResult {
+    /// Port connected to
+    pub port: u16,
+    /// Whether the connection was accepted
+    pub success: bool,
+    /// Time to complete the handshake (if successful)
+    pub rtt: Option<Duration>,
+    /// Error message if failed
+    pub error: Option<String>,
+}
+
+impl TcpResult {
+    /// Create a failed result without attempting a connection
+    pub fn failure(port: u16, error: String) -> Self {
+        Self {
+            port,
+            success: false,
+            rtt: None,
+            error: Some(error),
+        }
+    }
+
+    /// Format the result for display
+    pub fn format(&self) -> String {
+        match (self.success, self.rtt) {
+            (true, Some(rtt)) => format!(
+                "  {} tcp:  {} open ({:.1}ms)",
+                "✓".green(),
+                self.port,
+                rtt.as_secs_f64() * 1000.0
+            ),
+            (true, None) => format!("  {} tcp:  {} open", "✓".green(), self.port),
+            (false, _) => {
+                let err_str = self.error.as_deref().unwrap_or("unknown error");
+                format!("  {} tcp:  {} {}", "✗".red(), self.port, err_str)
+            }
+        }
+    }
+}
+
+/// Check that `port` on `address` accepts a TCP connection within `timeout`
+pub async fn check_port(address: IpAddr, port: u16, timeout: Duration, source: &SourceBinding) -> TcpResult {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, connect(SocketAddr::new(address, port), source)).await {
+        Ok(Ok(())) => TcpResult {
+            port,
+            success: true,
+            rtt: Some(start.elapsed()),
+            error: None,
+        },
+        Ok(Err(e)) => match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+            Some(std::io::ErrorKind::ConnectionRefused) => TcpResult::failure(port, "refused".to_string()),
+            _ => TcpResult::failure(port, format!("{:#}", e)),
+        },
+        Err(_) => TcpResult::failure(port, format!("timeout after {}ms", timeout.as_millis())),
+    }
+}
+
+async fn connect(target: SocketAddr, source: &SourceBinding) -> eyre::Result<()> {
+    let socket = if target.is_ipv6() {
+        tokio::net::TcpSocket::new_v6()?
+    } else {
+        tokio::net::TcpSocket::new_v4()?
+    };
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        source.bind_fd(socket.as_raw_fd(), target.is_ipv6())?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    if !source.is_default() {
+        eyre::bail!("binding TCP checks to {} is only supported on Linux", source.describe());
+    }
+    socket.connect(target).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_port_open_and_refused() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+
+        let open = check_port(localhost, port, Duration::from_secs(1), &SourceBinding::default()).await;
+        assert!(open.success, "{:?}", open.error);
+
+        drop(listener);
+        let closed = check_port(localhost, port, Duration::from_secs(1), &SourceBinding::default()).await;
+        assert!(!closed.success);
+        assert_eq!(closed.error.as_deref(), Some("refused"));
+    }
+}
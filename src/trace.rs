@@ -0,0 +1,578 @@
+use crate::dns;
+use crate::ping::{self, IcmpError, PingOptions};
+use crate::source::SourceBinding;
+use clap::ValueEnum;
+use comfy_table::{Cell, CellAlignment, Color, Table, presets::NOTHING};
+use eyre::Result;
+use rand::random;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Classic traceroute base port for UDP probes
+pub const DEFAULT_UDP_PORT: u16 = 33434;
+
+/// Default destination port for TCP SYN probes
+pub const DEFAULT_TCP_PORT: u16 = 80;
+
+/// Pause between probe rounds when sending a fixed count
+pub const ROUND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Probe packet type used for tracing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TraceProtocol {
+    /// ICMP echo requests
+    Icmp,
+    /// UDP datagrams to high ports
+    Udp,
+    /// TCP SYN (connect) to a port
+    Tcp,
+}
+
+impl std::fmt::Display for TraceProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceProtocol::Icmp => write!(f, "icmp"),
+            TraceProtocol::Udp => write!(f, "udp"),
+            TraceProtocol::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// Outcome of a single TTL-limited probe
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeReply {
+    /// An intermediate router reported TTL expiry
+    Hop { from: IpAddr, rtt: Duration },
+    /// The destination itself answered
+    Destination { from: IpAddr, rtt: Duration },
+    /// Another ICMP error (unreachable, prohibited) ended the probe
+    Unreachable { from: Option<IpAddr>, rtt: Duration },
+    /// Nothing came back before the timeout
+    Timeout,
+}
+
+impl ProbeReply {
+    /// Build a reply from ICMP errors queued on the probe socket
+    fn from_errors(errors: &[IcmpError], rtt: Duration, destination: IpAddr) -> Self {
+        if let Some(from) = errors.iter().find(|e| e.is_time_exceeded()).and_then(|e| e.from) {
+            return ProbeReply::Hop { from, rtt };
+        }
+        // Port unreachable from the target itself means the probe arrived
+        if errors.iter().any(|e| e.from == Some(destination)) {
+            return ProbeReply::Destination { from: destination, rtt };
+        }
+        match errors.first() {
+            Some(err) => ProbeReply::Unreachable { from: err.from, rtt },
+            None => ProbeReply::Timeout,
+        }
+    }
+}
+
+/// Accumulated statistics for one hop
+#[derive(Debug, Clone, Default)]
+pub struct HopStats {
+    /// TTL this hop answers at
+    pub ttl: u8,
+    /// Addresses seen at this hop, in order of first appearance
+    pub addresses: Vec<IpAddr>,
+    /// Reverse name of the first address
+    pub hostname: Option<String>,
+    /// Probes sent
+    pub sent: u32,
+    /// Probes answered
+    pub received: u32,
+    /// Most recent RTT
+    pub last: Option<Duration>,
+    /// All RTTs received
+    pub rtts: Vec<Duration>,
+    /// Whether any probe ended with an unreachable error
+    pub unreachable: bool,
+}
+
+impl HopStats {
+    /// Record the outcome of one probe
+    pub fn record(&mut self, reply: &ProbeReply) {
+        self.sent += 1;
+        let (from, rtt) = match reply {
+            ProbeReply::Hop { from, rtt } | ProbeReply::Destination { from, rtt } => (Some(*from), Some(*rtt)),
+            ProbeReply::Unreachable { from, rtt } => {
+                self.unreachable = true;
+                (*from, Some(*rtt))
+            }
+            ProbeReply::Timeout => (None, None),
+        };
+        if let Some(from) = from
+            && !self.addresses.contains(&from)
+        {
+            self.addresses.push(from);
+        }
+        if let Some(rtt) = rtt {
+            self.received += 1;
+            self.last = Some(rtt);
+            self.rtts.push(rtt);
+        }
+    }
+
+    /// Percentage of probes that got no answer
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            (self.sent - self.received) as f64 / self.sent as f64 * 100.0
+        }
+    }
+
+    fn rtts_ms(&self) -> impl Iterator<Item = f64> + '_ {
+        self.rtts.iter().map(|d| d.as_secs_f64() * 1000.0)
+    }
+
+    /// Average RTT in milliseconds
+    pub fn avg_ms(&self) -> Option<f64> {
+        if self.rtts.is_empty() {
+            None
+        } else {
+            Some(self.rtts_ms().sum::<f64>() / self.rtts.len() as f64)
+        }
+    }
+
+    /// Best RTT in milliseconds
+    pub fn best_ms(&self) -> Option<f64> {
+        self.rtts_ms().reduce(f64::min)
+    }
+
+    /// Worst RTT in milliseconds
+    pub fn worst_ms(&self) -> Option<f64> {
+        self.rtts_ms().reduce(f64::max)
+    }
+
+    /// Standard deviation of RTTs in milliseconds
+    pub fn stdev_ms(&self) -> Option<f64> {
+        let avg = self.avg_ms()?;
+        let variance = self.rtts_ms().map(|r| (r - avg).powi(2)).sum::<f64>() / self.rtts.len() as f64;
+        Some(variance.sqrt())
+    }
+
+    /// Host column text: "name (ip)", "ip", or "???" if nothing answered
+    pub fn host_label(&self) -> String {
+        let Some(first) = self.addresses.first() else {
+            return "???".to_string();
+        };
+        let mut label = match &self.hostname {
+            Some(name) => format!("{} ({})", name, first),
+            None => first.to_string(),
+        };
+        if self.addresses.len() > 1 {
+            label.push_str(&format!(" +{}", self.addresses.len() - 1));
+        }
+        if self.unreachable {
+            label.push_str(" !");
+        }
+        label
+    }
+}
+
+/// Runs rounds of TTL-limited probes toward one destination and accumulates per-hop stats
+pub struct Tracer {
+    address: IpAddr,
+    protocol: TraceProtocol,
+    port: u16,
+    max_hops: u8,
+    timeout: Duration,
+    source: SourceBinding,
+    hops: Vec<HopStats>,
+    destination_ttl: Option<u8>,
+    round: u16,
+    names: HashMap<IpAddr, Option<String>>,
+}
+
+impl Tracer {
+    /// Create a tracer; `port` is ignored for ICMP
+    pub fn new(
+        address: IpAddr,
+        protocol: TraceProtocol,
+        port: u16,
+        max_hops: u8,
+        timeout: Duration,
+        source: SourceBinding,
+    ) -> Self {
+        let hops = (1..=max_hops)
+            .map(|ttl| HopStats {
+                ttl,
+                ..Default::default()
+            })
+            .collect();
+        Self {
+            address,
+            protocol,
+            port,
+            max_hops,
+            timeout,
+            source,
+            hops,
+            destination_ttl: None,
+            round: 0,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Whether the destination has answered at least once
+    pub fn reached(&self) -> bool {
+        self.destination_ttl.is_some()
+    }
+
+    /// Hops up to and including the destination (or all if never reached)
+    pub fn visible_hops(&self) -> &[HopStats] {
+        let end = self.destination_ttl.unwrap_or(self.max_hops) as usize;
+        &self.hops[..end]
+    }
+
+    /// Probe every TTL concurrently once and fold the replies into the hop stats
+    pub async fn run_round(&mut self) -> Result<()> {
+        let limit = self.destination_ttl.unwrap_or(self.max_hops);
+        let seq = self.round;
+        self.round = self.round.wrapping_add(1);
+
+        let mut join_set = JoinSet::new();
+        for ttl in 1..=limit {
+            let (address, protocol, port, timeout) = (self.address, self.protocol, self.port, self.timeout);
+            let source = self.source.clone();
+            join_set.spawn(async move {
+                let reply = probe(address, protocol, port, ttl, timeout, seq, &source).await;
+                (ttl, reply)
+            });
+        }
+
+        let mut replies = Vec::with_capacity(limit as usize);
+        while let Some(joined) = join_set.join_next().await {
+            let (ttl, reply) = joined?;
+            replies.push((ttl, reply?));
+        }
+        replies.sort_by_key(|(ttl, _)| *ttl);
+
+        for (ttl, reply) in &replies {
+            if matches!(reply, ProbeReply::Destination { .. }) {
+                self.destination_ttl = Some(self.destination_ttl.map_or(*ttl, |d| d.min(*ttl)));
+            }
+        }
+        let limit = self.destination_ttl.unwrap_or(self.max_hops);
+        for (ttl, reply) in replies.into_iter().filter(|(ttl, _)| *ttl <= limit) {
+            self.hops[ttl as usize - 1].record(&reply);
+        }
+
+        Ok(())
+    }
+
+    /// Fill in reverse names for hop addresses not looked up yet
+    pub async fn resolve_names(&mut self, resolver: &dns::DnsResolver) {
+        let end = self.visible_hops().len();
+        for hop in &mut self.hops[..end] {
+            let Some(first) = hop.addresses.first().copied() else {
+                continue;
+            };
+            if let Entry::Vacant(entry) = self.names.entry(first) {
+                entry.insert(dns::reverse_lookup(resolver, first).await);
+            }
+            hop.hostname = self.names.get(&first).cloned().flatten();
+        }
+    }
+
+    /// Render the per-hop statistics table
+    pub fn format_table(&self) -> String {
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+
+        let right = |s: &str| Cell::new(s).fg(Color::DarkGrey).set_alignment(CellAlignment::Right);
+        table.set_header(vec![
+            right("HOP"),
+            Cell::new("HOST").fg(Color::DarkGrey),
+            right("LOSS"),
+            right("SNT"),
+            right("LAST"),
+            right("AVG"),
+            right("BEST"),
+            right("WRST"),
+            right("STDEV"),
+        ]);
+
+        let ms = |v: Option<f64>| v.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "-".to_string());
+        for hop in self.visible_hops() {
+            let loss = hop.loss_percent();
+            let loss_color = if hop.sent == 0 || loss == 0.0 {
+                Color::Green
+            } else if loss >= 100.0 {
+                Color::Red
+            } else {
+                Color::Yellow
+            };
+            let host_color = if hop.addresses.is_empty() {
+                Color::DarkGrey
+            } else {
+                Color::Reset
+            };
+
+            table.add_row(vec![
+                Cell::new(hop.ttl).set_alignment(CellAlignment::Right),
+                Cell::new(hop.host_label()).fg(host_color),
+                Cell::new(format!("{:.0}%", loss))
+                    .fg(loss_color)
+                    .set_alignment(CellAlignment::Right),
+                Cell::new(hop.sent).set_alignment(CellAlignment::Right),
+                Cell::new(ms(hop.last.map(|d| d.as_secs_f64() * 1000.0))).set_alignment(CellAlignment::Right),
+                Cell::new(ms(hop.avg_ms())).set_alignment(CellAlignment::Right),
+                Cell::new(ms(hop.best_ms())).set_alignment(CellAlignment::Right),
+                Cell::new(ms(hop.worst_ms())).set_alignment(CellAlignment::Right),
+                Cell::new(ms(hop.stdev_ms())).set_alignment(CellAlignment::Right),
+            ]);
+        }
+
+        table.to_string()
+    }
+}
+
+/// Send one probe with the given TTL
+async fn probe(
+    address: IpAddr,
+    protocol: TraceProtocol,
+    port: u16,
+    ttl: u8,
+    timeout: Duration,
+    seq: u16,
+    source: &SourceBinding,
+) -> Result<ProbeReply> {
+    match protocol {
+        TraceProtocol::Icmp => probe_icmp(address, ttl, timeout, seq, source).await,
+        TraceProtocol::Udp => probe_udp(address, ttl, port.wrapping_add(ttl as u16), timeout, source).await,
+        TraceProtocol::Tcp => probe_tcp(address, ttl, port, timeout, source).await,
+    }
+}
+
+/// ICMP echo with a limited TTL; hops answer through the socket error queue
+#[cfg(target_os = "linux")]
+async fn probe_icmp(
+    address: IpAddr,
+    ttl: u8,
+    timeout: Duration,
+    seq: u16,
+    source: &SourceBinding,
+) -> Result<ProbeReply> {
+    use surge_ping::{PingIdentifier, PingSequence};
+
+    let options = PingOptions {
+        ttl: Some(ttl as u32),
+        ..Default::default()
+    };
+    let client = ping::create_client_with(address, &options, source)?;
+    ping::enable_error_queue(&client, address)?;
+    let fd = client.get_socket().get_native_sock();
+
+    let mut pinger = client.pinger(address, PingIdentifier(random())).await;
+    pinger.timeout(timeout);
+    let payload = options.payload();
+
+    let start = Instant::now();
+    tokio::select! {
+        biased;
+        errors = ping::wait_error_queue_fd(fd) => {
+            Ok(ProbeReply::from_errors(&errors?, start.elapsed(), address))
+        }
+        reply = pinger.ping(PingSequence(seq), &payload) => match reply {
+            Ok((_, rtt)) => Ok(ProbeReply::Destination { from: address, rtt }),
+            Err(_) => Ok(ProbeReply::from_errors(&ping::read_error_queue(&client), start.elapsed(), address)),
+        },
+    }
+}
+
+/// UDP datagram to a high port; the destination answers with port unreachable
+#[cfg(target_os = "linux")]
+async fn probe_udp(
+    address: IpAddr,
+    ttl: u8,
+    port: u16,
+    timeout: Duration,
+    source: &SourceBinding,
+) -> Result<ProbeReply> {
+    use std::os::fd::AsRawFd;
+
+    let unspecified: IpAddr = if address.is_ipv6() {
+        std::net::Ipv6Addr::UNSPECIFIED.into()
+    } else {
+        std::net::Ipv4Addr::UNSPECIFIED.into()
+    };
+    let bind = source.local_address(address.is_ipv6())?.unwrap_or(unspecified);
+    let socket = tokio::net::UdpSocket::bind((bind, 0)).await?;
+    let fd = socket.as_raw_fd();
+    if let Some(ref interface) = source.interface {
+        socket.bind_device(Some(interface.as_bytes()))?;
+    }
+    ping::set_hop_limit_fd(fd, address.is_ipv6(), ttl as u32)?;
+    ping::enable_error_queue_fd(fd, address.is_ipv6())?;
+
+    let start = Instant::now();
+    socket.send_to(&[0u8; 32], (address, port)).await?;
+    match tokio::time::timeout(timeout, ping::wait_error_queue_fd(fd)).await {
+        Ok(errors) => Ok(ProbeReply::from_errors(&errors?, start.elapsed(), address)),
+        Err(_) => Ok(ProbeReply::Timeout),
+    }
+}
+
+/// TCP SYN via connect; SYN-ACK or RST from the destination both count as reached
+#[cfg(target_os = "linux")]
+async fn probe_tcp(
+    address: IpAddr,
+    ttl: u8,
+    port: u16,
+    timeout: Duration,
+    source: &SourceBinding,
+) -> Result<ProbeReply> {
+    use std::os::fd::{AsFd, AsRawFd};
+
+    let socket = if address.is_ipv6() {
+        tokio::net::TcpSocket::new_v6()?
+    } else {
+        tokio::net::TcpSocket::new_v4()?
+    };
+    source.bind_fd(socket.as_raw_fd(), address.is_ipv6())?;
+    ping::set_hop_limit_fd(socket.as_raw_fd(), address.is_ipv6(), ttl as u32)?;
+    ping::enable_error_queue_fd(socket.as_raw_fd(), address.is_ipv6())?;
+    // Keep the socket alive past a failed connect so its error queue can be read
+    let errqueue = socket.as_fd().try_clone_to_owned()?;
+
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, socket.connect((address, port).into())).await {
+        Ok(Ok(_)) => Ok(ProbeReply::Destination {
+            from: address,
+            rtt: start.elapsed(),
+        }),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Ok(ProbeReply::Destination {
+            from: address,
+            rtt: start.elapsed(),
+        }),
+        Ok(Err(_)) => {
+            let errors = ping::read_error_queue_fd(errqueue.as_raw_fd());
+            Ok(match ProbeReply::from_errors(&errors, start.elapsed(), address) {
+                ProbeReply::Timeout => ProbeReply::Unreachable {
+                    from: None,
+                    rtt: start.elapsed(),
+                },
+                reply => reply,
+            })
+        }
+        Err(_) => Ok(ProbeReply::Timeout),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn probe_icmp(
+    _address: IpAddr,
+    _ttl: u8,
+    _timeout: Duration,
+    _seq: u16,
+    _source: &SourceBinding,
+) -> Result<ProbeReply> {
+    eyre::bail!("trace is only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn probe_udp(
+    _address: IpAddr,
+    _ttl: u8,
+    _port: u16,
+    _timeout: Duration,
+    _source: &SourceBinding,
+) -> Result<ProbeReply> {
+    eyre::bail!("trace is only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn probe_tcp(
+    _address: IpAddr,
+    _ttl: u8,
+    _port: u16,
+    _timeout: Duration,
+    _source: &SourceBinding,
+) -> Result<ProbeReply> {
+    eyre::bail!("trace is only supported on Linux")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_hop_stats_record() {
+        let mut hop = HopStats {
+            ttl: 3,
+            ..Default::default()
+        };
+        hop.record(&ProbeReply::Hop {
+            from: ip("10.0.0.1"),
+            rtt: Duration::from_millis(10),
+        });
+        hop.record(&ProbeReply::Timeout);
+        hop.record(&ProbeReply::Hop {
+            from: ip("10.0.0.1"),
+            rtt: Duration::from_millis(20),
+        });
+        hop.record(&ProbeReply::Timeout);
+
+        assert_eq!(hop.sent, 4);
+        assert_eq!(hop.received, 2);
+        assert_eq!(hop.loss_percent(), 50.0);
+        assert_eq!(hop.avg_ms(), Some(15.0));
+        assert_eq!(hop.best_ms(), Some(10.0));
+        assert_eq!(hop.worst_ms(), Some(20.0));
+        assert_eq!(hop.stdev_ms(), Some(5.0));
+        assert_eq!(hop.host_label(), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_hop_label_multiple_addresses_and_name() {
+        let mut hop = HopStats::default();
+        assert_eq!(hop.host_label(), "???");
+        hop.addresses = vec![ip("10.0.0.1"), ip("10.0.0.2")];
+        hop.hostname = Some("core1.example.net".to_string());
+        assert_eq!(hop.host_label(), "core1.example.net (10.0.0.1) +1");
+    }
+
+    #[test]
+    fn test_probe_reply_from_errors() {
+        let destination = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rtt = Duration::from_millis(5);
+        let time_exceeded = IcmpError {
+            from: Some(ip("10.0.0.1")),
+            errno: 113,
+            icmp_type: 11,
+            icmp_code: 0,
+            info: 0,
+        };
+        assert_eq!(
+            ProbeReply::from_errors(&[time_exceeded], rtt, destination),
+            ProbeReply::Hop {
+                from: ip("10.0.0.1"),
+                rtt
+            }
+        );
+
+        let port_unreachable = IcmpError {
+            from: Some(destination),
+            errno: 111,
+            icmp_type: 3,
+            icmp_code: 3,
+            info: 0,
+        };
+        assert_eq!(
+            ProbeReply::from_errors(&[port_unreachable], rtt, destination),
+            ProbeReply::Destination { from: destination, rtt }
+        );
+
+        assert_eq!(ProbeReply::from_errors(&[], rtt, destination), ProbeReply::Timeout);
+    }
+}